@@ -187,6 +187,32 @@ pub fn get_new_area_circle(start: (u32, u32), end: (u32, u32), old_pos: (u32, u3
 
 }
 
+///Adjusts the area resized by dragging the given corner so that its width is `ratio` times its height.
+///The opposite corner stays in place and the area grows along the side dragged the most, shrinking if needed to fit the image
+pub fn keep_aspect(area: ((u32, u32), (u32, u32)), ratio: f32, image_size: (u32, u32), corner: Corner) -> ((u32, u32), (u32, u32)) {
+    let ((x, y), (w, h)) = area;
+    let (x, y, w, h) = (x as f32, y as f32, w as f32, h as f32);
+    let (left, up) = match corner {
+        Corner::UpLeft => (true, true),
+        Corner::UpRight => (false, true),
+        Corner::DownLeft => (true, false),
+        Corner::DownRight => (false, false),
+        Corner::Centre => return area
+    };
+    let anchor = (if left { x + w } else { x }, if up { y + h } else { y });
+
+    let (mut new_w, mut new_h) = if w / h.max(1.0) > ratio { (w, w / ratio) } else { (h * ratio, h) };
+    let max_w = if left { anchor.0 } else { image_size.0 as f32 - anchor.0 };
+    let max_h = if up { anchor.1 } else { image_size.1 as f32 - anchor.1 };
+    let scale = f32::min(1.0, f32::min(max_w / new_w, max_h / new_h));
+    new_w = (new_w * scale).round().max(1.0);
+    new_h = (new_h * scale).round().max(1.0);
+
+    let new_x = if left { anchor.0 - new_w } else { anchor.0 };
+    let new_y = if up { anchor.1 - new_h } else { anchor.1 };
+    ((new_x.max(0.0) as u32, new_y.max(0.0) as u32), (new_w as u32, new_h as u32))
+}
+
 pub fn move_area(start: (u32, u32), end: (u32, u32), old_pos: (u32, u32), old_size: (u32, u32), image_size: (u32, u32)) -> ((u32, u32), (u32, u32)) {
     let start = (start.0 as i32, start.1 as i32);
     let end = (end.0 as i32, end.1 as i32);
@@ -212,4 +238,72 @@ pub fn move_area(start: (u32, u32), end: (u32, u32), old_pos: (u32, u32), old_si
 
     ((new_pos.0 as u32, new_pos.1 as u32), (size.0 as u32, size.1 as u32))
 
+}
+
+///Returns the corner of the area within `tolerance` of the point, `Centre` if the point is inside the area, `None` otherwise
+pub fn get_corner(point: (u32, u32), pos: (u32, u32), size: (u32, u32), tolerance: u32) -> Option<Corner> {
+    let point = (point.0 as i64, point.1 as i64);
+    let (x, y) = (pos.0 as i64, pos.1 as i64);
+    let (w, h) = (size.0 as i64, size.1 as i64);
+    let t = tolerance as i64;
+
+    let near = |cx: i64, cy: i64| (point.0 - cx).abs() <= t && (point.1 - cy).abs() <= t;
+
+    if near(x, y) {
+        Some(Corner::UpLeft)
+    } else if near(x + w, y) {
+        Some(Corner::UpRight)
+    } else if near(x, y + h) {
+        Some(Corner::DownLeft)
+    } else if near(x + w, y + h) {
+        Some(Corner::DownRight)
+    } else if point.0 > x && point.0 < x + w && point.1 > y && point.1 < y + h {
+        Some(Corner::Centre)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE: (u32, u32) = (200, 100);
+
+    #[test]
+    fn corners_dragged_to_the_border_are_shrunk_to_fit() {
+        assert_eq!(keep_aspect(((0, 0), (100, 40)), 2.0, IMAGE, Corner::UpLeft), ((20, 0), (80, 40)));
+        assert_eq!(keep_aspect(((150, 10), (50, 30)), 2.0, IMAGE, Corner::UpRight), ((150, 15), (50, 25)));
+        assert_eq!(keep_aspect(((0, 60), (60, 40)), 2.0, IMAGE, Corner::DownLeft), ((0, 60), (60, 30)));
+        assert_eq!(keep_aspect(((100, 50), (150, 30)), 2.0, IMAGE, Corner::DownRight), ((100, 50), (100, 50)));
+    }
+
+    #[test]
+    fn area_fitting_the_image_grows_along_the_side_dragged_the_most() {
+        assert_eq!(keep_aspect(((50, 40), (40, 10)), 2.0, IMAGE, Corner::DownRight), ((50, 40), (40, 20)));
+        assert_eq!(keep_aspect(((50, 40), (20, 30)), 2.0, IMAGE, Corner::UpLeft), ((10, 40), (60, 30)));
+    }
+
+    #[test]
+    fn opposite_corner_stays_in_place() {
+        for corner in [Corner::UpLeft, Corner::UpRight, Corner::DownLeft, Corner::DownRight] {
+            for area in [((0, 0), (200, 100)), ((0, 0), (1, 1)), ((10, 0), (190, 7)), ((0, 90), (30, 10)), ((199, 99), (1, 1))] {
+                let ((x, y), (w, h)) = keep_aspect(area, 16.0 / 9.0, IMAGE, corner);
+                assert!(x + w <= IMAGE.0 && y + h <= IMAGE.1, "{:?} {:?}", corner, area);
+                assert!(w >= 1 && h >= 1);
+                let ((ax, ay), (aw, ah)) = area;
+                match corner {
+                    Corner::UpLeft => assert_eq!((x + w, y + h), (ax + aw, ay + ah)),
+                    Corner::UpRight => assert_eq!((x, y + h), (ax, ay + ah)),
+                    Corner::DownLeft => assert_eq!((x + w, y), (ax + aw, ay)),
+                    _ => assert_eq!((x, y), (ax, ay))
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn centre_moves_without_resizing() {
+        assert_eq!(keep_aspect(((0, 0), (200, 7)), 2.0, IMAGE, Corner::Centre), ((0, 0), (200, 7)));
+    }
 }
\ No newline at end of file
@@ -136,7 +136,11 @@ impl Default for HotKeyPopUp {
                 Operation::new(HotKey::new(Some(Modifiers::SHIFT | Modifiers::ALT), Code::KeyD), "Draw capture".to_string(), true, true, false, Code::KeyD),
                 Operation::new(HotKey::new(Some(Modifiers::SHIFT | Modifiers::ALT), Code::KeyE), "Text capture".to_string(), true, true, false, Code::KeyE),
                 Operation::new(HotKey::new(Some(Modifiers::SHIFT | Modifiers::ALT), Code::KeyF), "Copy to clipboard".to_string(), true, true, false, Code::KeyF),
-                Operation::new(HotKey::new(Some(Modifiers::SHIFT | Modifiers::ALT), Code::KeyG), "Save capture".to_string(), true, true, false, Code::KeyG)
+                Operation::new(HotKey::new(Some(Modifiers::SHIFT | Modifiers::ALT), Code::KeyG), "Save capture".to_string(), true, true, false, Code::KeyG),
+                Operation::new(HotKey::new(Some(Modifiers::SHIFT | Modifiers::ALT), Code::KeyR), "Region capture".to_string(), true, true, false, Code::KeyR),
+                Operation::new(HotKey::new(Some(Modifiers::SHIFT | Modifiers::ALT), Code::KeyI), "Interval capture".to_string(), true, true, false, Code::KeyI),
+                Operation::new(HotKey::new(Some(Modifiers::SHIFT | Modifiers::ALT), Code::KeyV), "Record screen".to_string(), true, true, false, Code::KeyV),
+                Operation::new(HotKey::new(Some(Modifiers::SHIFT | Modifiers::ALT), Code::KeyS), "Scroll capture".to_string(), true, true, false, Code::KeyS)
             ]
         }
     }
@@ -196,7 +200,7 @@ pub fn parse(str: String, i: usize) -> Result<Operation, String>{
         ctrl = true;
     }
 
-    let hk: HotKey = str.parse().unwrap();
+    let hk: HotKey = str.parse().map_err(|_| "Non valid string".to_string())?;
 
     let key = str.split("+").last().unwrap().to_string();
     let code = string_to_code(&key);
@@ -209,6 +213,10 @@ pub fn parse(str: String, i: usize) -> Result<Operation, String>{
         4 => "Text capture",
         5 => "Copy to clipboard",
         6 => "Save capture",
+        7 => "Region capture",
+        8 => "Interval capture",
+        9 => "Record screen",
+        10 => "Scroll capture",
         _ => "",
     };
 
@@ -4,148 +4,421 @@ pub mod colors;
 pub mod image_errors;
 pub mod layer;
 pub mod blur_area;
+pub mod annotation;
 mod shape;
-
-use image::{DynamicImage, RgbaImage};
-use imageproc::pixelops::interpolate;
-use imageproc::point::Point;
-use imageproc::rect::Rect;
-use std::collections::VecDeque;
+mod blend;
+mod path;
+mod line;
+mod text;
+
+use image::{DynamicImage, ImageEncoder, ImageFormat, RgbaImage};
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::fs::File;
 use extensions::Extensions;
 use imageproc::drawing;
 use polygon::Polygon;
 use colors::Color;
+use blend::with_rgba8;
 use image_errors::ImageManipulationError;
 use layer::{Layer, LayerType};
 use blur_area::BlurArea;
 use std::borrow::Cow;
 use eframe::egui;
 use shape::Arrow;
+use annotation::{magnifier_position, redact, Annotation, ArrowHead, AnnotationKind, Pointing, RedactionStyle, TextFont, TextStyle, Transform};
 
 ///Incremental counter for files whose name is not specified when saved
 static UNNAMED_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
-///Structure containing the base screenshot and the additional layers of editing applied to it
+///Upper bound, in bytes, of the memory used by the history of each image
+static HISTORY_MEMORY_LIMIT: AtomicUsize = AtomicUsize::new(512 * 1024 * 1024);
+
+///Sets the upper bound, in bytes, of the memory used by the history of each image.
+///When a new edit exceeds it, the oldest steps are dropped until the history fits again
+pub fn set_history_memory_limit(bytes: usize) {
+    HISTORY_MEMORY_LIMIT.store(bytes, Ordering::Relaxed);
+}
+
+///Pixels below the annotations, shared by all the states of the history between two crops, flips or rotations.
+///Only the background of the current state is kept raw, the other ones are compressed as PNG
+#[derive(Debug, Clone, PartialEq)]
+enum Background {
+    Raw(Arc<DynamicImage>),
+    Compressed(Arc<Vec<u8>>)
+}
+
+impl Background {
+    ///Returns the number of bytes used to store the background
+    fn memory_size(&self) -> usize {
+        match self {
+            Background::Raw(image) => image.as_bytes().len(),
+            Background::Compressed(data) => data.len()
+        }
+    }
+}
+
+///State of the image after an edit: the key of its background and the annotations placed over it, in drawing order,
+///along with the name of the edit shown in the history
+#[derive(Debug, Clone, PartialEq)]
+struct Document {
+    step: String,
+    background: usize,
+    annotations: Vec<Annotation>
+}
+
+///Draws the annotations over the background, returning the resulting image
+fn render(background: &DynamicImage, annotations: &[Annotation]) -> DynamicImage {
+    let mut image = background.clone();
+    if annotations.is_empty() {
+        return image;
+    }
+    let mut canvas = Layer::new(background.clone(), LayerType::BaseImage);
+    //the redactions are applied to what the eraser uncovers as well, so that erasing them never reveals the content below
+    let mut uncovered = Cow::Borrowed(background);
+    for annotation in annotations.iter() {
+        annotation.draw(&mut image, &uncovered, &mut canvas);
+        if matches!(annotation.kind, AnnotationKind::Redaction { .. }) {
+            annotation.draw(uncovered.to_mut(), background, &mut canvas);
+        }
+    }
+    image
+}
+
+///Returns what the eraser uncovers below the given annotations: the background, with their redactions applied
+fn uncovered(background: &DynamicImage, annotations: &[Annotation]) -> DynamicImage {
+    let mut image = background.clone();
+    let mut canvas = Layer::new(DynamicImage::new_rgba8(0, 0), LayerType::BaseImage);
+    for annotation in annotations.iter().filter(|a| matches!(a.kind, AnnotationKind::Redaction { .. })) {
+        annotation.draw(&mut image, background, &mut canvas);
+    }
+    image
+}
+
+///Structure containing the screenshot along with the history of the edits applied to it.
+///Every edit adds a new state: shapes, text and drawings are kept as annotations, rasterized only when
+///the image is shown or exported, while crops, flips and rotations change the background.
+///The states undone are kept until a new edit is made, so that they can be redone.
+///The oldest states are dropped when the history takes more memory than the limit set by set_history_memory_limit
 #[derive(Debug, Clone, PartialEq)]
 pub struct Image {
-    base: DynamicImage,
-    layers: VecDeque<DynamicImage>
+    history: VecDeque<Document>,
+    //index in history of the current state, the ones before it have been undone
+    position: usize,
+    backgrounds: BTreeMap<usize, Background>,
+    next_background: usize
 }
 
 impl Image {
-    ///Returns an Image structure, wrapped in a Result, given the path where to retrieve it from. 
-    ///In case of failure an ImageManipulationError is returned, with the IOError variant
-    pub fn open(path: &str) -> Result<Self,ImageManipulationError> {
-        let image = image::open(path)?;
-        let mut layers = VecDeque::new();
-        layers.push_front(image.clone());
-        Ok(
-            Self {
-                base: image,
-                layers: layers
+    ///Returns an Image structure built directly from an image already in memory
+    pub fn from_dynamic_image(image: DynamicImage) -> Self {
+        let mut history = VecDeque::new();
+        history.push_front(Document { step: String::from("Capture"), background: 0, annotations: Vec::new() });
+        let mut backgrounds = BTreeMap::new();
+        backgrounds.insert(0, Background::Raw(Arc::new(image)));
+        Self {
+            history,
+            position: 0,
+            backgrounds,
+            next_background: 1
+        }
+    }
+    ///Returns the current state of the image
+    fn current(&self) -> &Document {
+        &self.history[self.position]
+    }
+    ///Returns the background of the current state
+    fn background(&self) -> &DynamicImage {
+        match &self.backgrounds[&self.current().background] {
+            Background::Raw(image) => image,
+            Background::Compressed(_) => unreachable!("the background of the current state is never compressed")
+        }
+    }
+    ///Stores a new background, returning its key
+    fn add_background(&mut self, image: DynamicImage) -> usize {
+        let key = self.next_background;
+        self.next_background += 1;
+        self.backgrounds.insert(key, Background::Raw(Arc::new(image)));
+        key
+    }
+    ///Compresses the given background, which is left raw if it cannot be encoded
+    fn compress_background(&mut self, key: usize) {
+        let compressed = match self.backgrounds.get(&key) {
+            Some(Background::Raw(image)) => {
+                let mut data = Vec::new();
+                let encoder = PngEncoder::new_with_quality(&mut data, CompressionType::Fast, FilterType::Sub);
+                match encoder.write_image(image.as_bytes(), image.width(), image.height(), image.color()) {
+                    Ok(_) => data,
+                    Err(_) => return
+                }
             }
-        )
+            _ => return
+        };
+        self.backgrounds.insert(key, Background::Compressed(Arc::new(compressed)));
+    }
+    ///Decompresses the given background, which is left compressed if it cannot be decoded
+    fn decompress_background(&mut self, key: usize) -> Result<(), ImageManipulationError> {
+        let image = match self.backgrounds.get(&key) {
+            Some(Background::Compressed(data)) => image::load_from_memory_with_format(data, ImageFormat::Png)?,
+            _ => return Ok(())
+        };
+        self.backgrounds.insert(key, Background::Raw(Arc::new(image)));
+        Ok(())
+    }
+    ///Makes the state at the given index of history the current one, decompressing the background reached
+    ///and compressing the one left if they are different.
+    ///The current state does not change if the background reached cannot be decompressed
+    fn set_position(&mut self, position: usize) {
+        let left = self.current().background;
+        let reached = self.history[position].background;
+        if left != reached {
+            if self.decompress_background(reached).is_err() {
+                return;
+            }
+            self.compress_background(left);
+        }
+        self.position = position;
+    }
+    ///Returns the number of bytes used by the backgrounds and the annotations of the whole history
+    fn memory_usage(&self) -> usize {
+        let backgrounds: usize = self.backgrounds.values().map(|b| b.memory_size()).sum();
+        let annotations: usize = self.history.iter()
+            .map(|d| d.annotations.iter().map(|a| a.memory_size()).sum::<usize>())
+            .sum();
+        backgrounds + annotations
+    }
+    ///Drops the backgrounds not used by any state of the history
+    fn drop_unused_backgrounds(&mut self) {
+        let history = &self.history;
+        self.backgrounds.retain(|key, _| history.iter().any(|d| d.background == *key));
+    }
+    ///Adds the state reached by a new edit with the given name, dropping the states undone.
+    ///The step markers of the new state are renumbered in drawing order, so that removing one closes the gap.
+    ///If the history then exceeds the memory limit, its oldest states are dropped, never the current one
+    fn push(&mut self, step: &str, mut document: Document) {
+        let left = self.current().background;
+        self.history.drain(..self.position);
+        self.position = 0;
+        document.step = step.to_string();
+        let mut number = 0;
+        for annotation in document.annotations.iter_mut() {
+            if let AnnotationKind::Marker { number: n, .. } = &mut annotation.kind {
+                number += 1;
+                *n = number;
+            }
+        }
+        let reached = document.background;
+        self.history.push_front(document);
+        self.drop_unused_backgrounds();
+        if left != reached {
+            self.compress_background(left);
+        }
+
+        let limit = HISTORY_MEMORY_LIMIT.load(Ordering::Relaxed);
+        while self.history.len() > 1 && self.memory_usage() > limit {
+            self.history.pop_back();
+            self.drop_unused_backgrounds();
+        }
     }
     ///Return the width of the image
     pub fn width(&self) -> u32 {
-        return self.layers[0].width();
+        self.background().width()
     }
     ///Returns the height of the image
     pub fn height(&self) -> u32 {
-        return self.layers[0].height();
+        self.background().height()
+    }
+    ///Places a new annotation over the image
+    pub fn push_annotation(&mut self, annotation: Annotation) {
+        let mut document = self.current().clone();
+        let step = annotation.name();
+        document.annotations.push(annotation);
+        self.push(step, document);
+    }
+    ///Returns the annotations placed over the image, in drawing order
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.current().annotations
+    }
+    ///Returns the index of the topmost annotation whose area contains the given point, if any.
+    ///Erased lines are not considered, since they cannot be seen
+    pub fn annotation_at(&self, point: (u32, u32)) -> Option<usize> {
+        let (x, y) = (point.0 as i32, point.1 as i32);
+        self.annotations().iter().rposition(|a| {
+            let ((ax, ay), (aw, ah)) = a.bounds();
+            !matches!(a.kind, AnnotationKind::Eraser { .. }) && x >= ax && y >= ay && x < ax + aw as i32 && y < ay + ah as i32
+        })
+    }
+    ///Returns the upper-left corner and the size of the area of the given annotation, limited to the image
+    pub fn annotation_area(&self, index: usize) -> ((u32, u32), (u32, u32)) {
+        let ((x, y), (w, h)) = self.annotations()[index].bounds();
+        let x0 = x.clamp(0, self.width() as i32 - 1);
+        let y0 = y.clamp(0, self.height() as i32 - 1);
+        let x1 = (x + w as i32).clamp(x0 + 1, self.width() as i32);
+        let y1 = (y + h as i32).clamp(y0 + 1, self.height() as i32);
+        ((x0 as u32, y0 as u32), ((x1 - x0) as u32, (y1 - y0) as u32))
+    }
+    ///Returns the image with the given annotation fitted into the given area, along with the handles to resize it
+    pub fn show_selected(&self, index: usize, area: ((u32, u32), (u32, u32))) -> DynamicImage {
+        let mut document = self.current().clone();
+        let ((x, y), size) = self.annotation_area(index);
+        document.annotations[index].fit(((x as i32, y as i32), size), ((area.0.0 as i32, area.0.1 as i32), area.1));
+        let mut image = render(self.background(), &document.annotations);
+        Layer::draw_handles(&mut image, area.0, area.1);
+        image
+    }
+    ///Moves the given annotation, fitting it into the given area
+    pub fn move_annotation(&mut self, index: usize, area: ((u32, u32), (u32, u32))) {
+        let ((x, y), size) = self.annotation_area(index);
+        if ((x, y), size) == area {
+            return;
+        }
+        let mut document = self.current().clone();
+        document.annotations[index].fit(((x as i32, y as i32), size), ((area.0.0 as i32, area.0.1 as i32), area.1));
+        let step = if size == area.1 { "Move" } else { "Resize" };
+        self.push(step, document);
+    }
+    ///Removes the given annotation
+    pub fn remove_annotation(&mut self, index: usize) {
+        let mut document = self.current().clone();
+        document.annotations.remove(index);
+        self.push("Delete", document);
+    }
+    ///Applies a flip or a rotation to the background and to the annotations.
+    ///The annotations that cannot follow it are merged into the background, along with the ones below them
+    fn transform<F: Fn(&DynamicImage) -> DynamicImage>(&mut self, step: &str, t: Transform, f: F) {
+        let current = self.current();
+        let (background, annotations) = match current.annotations.iter().rposition(|a| !a.can_transform()) {
+            Some(i) => (f(&render(self.background(), &current.annotations[..=i])), current.annotations[i + 1..].to_vec()),
+            None => (f(self.background()), current.annotations.clone())
+        };
+        let annotations = annotations.into_iter().map(|mut a| { a.transform(t); a }).collect();
+        let background = self.add_background(background);
+        self.push(step, Document { step: String::new(), background, annotations });
     }
     ///Returns a BlurArea structure, used to dynamically show which part of the image is going to be cropped
     ///The BlurArea structure is manipulated directly to modify the crop area
     ///Takes as parameters the position of the left-upper angle of the crop area and its size
     pub fn blur_area(&self, x: u32, y: u32, width: u32, height: u32) -> BlurArea {
-        let image = self.layers[0].clone();
+        let image = self.show();
         let mut blur = image.clone();
         blur = blur.brighten(100);
         BlurArea::new(image, blur, (x,y), (width,height))
     }
-    ///Crops the image given a BlurArea previously obtained via the blur_area method
+    ///Crops the image given a BlurArea previously obtained via the blur_area method.
+    ///The annotations are moved along, the ones left entirely outside of the area are dropped
     pub fn crop(&mut self, crop_area: BlurArea) {
         let ((x,y), (width, height)) = crop_area.get_crop_data();
-        let cropped = crop_area.save().crop(x, y, width, height);
-        self.layers.push_front(cropped);
-        
+        let mut document = self.current().clone();
+        document.background = self.add_background(self.background().crop_imm(x, y, width, height));
+        for annotation in document.annotations.iter_mut() {
+            annotation.translate(-(x as i32), -(y as i32));
+        }
+        document.annotations.retain(|a| {
+            let ((ax, ay), (aw, ah)) = a.bounds();
+            ax < width as i32 && ay < height as i32 && ax + aw as i32 > 0 && ay + ah as i32 > 0
+        });
+        self.push("Crop", document);
     }
     ///Flips the image orizontally
     pub fn flip_horizontally(&mut self) {
-        let flipped = self.layers[0].fliph();
-        self.layers.push_front(flipped);
+        self.transform("Flip horizontally", Transform::FlipH(self.width()), |im| im.fliph());
     }
     ///Flips the image vertically
     pub fn flip_vertically(&mut self) {
-        let flipped = self.layers[0].flipv();
-        self.layers.push_front(flipped)
+        self.transform("Flip vertically", Transform::FlipV(self.height()), |im| im.flipv());
     }
     ///Rotates the image 90 degree clockwise
     pub fn rotate90cv(&mut self) {
-        let rotated = self.layers[0].rotate90();
-        self.layers.push_front(rotated);
-        
+        self.transform("Rotate 90°", Transform::Rotate90(self.height()), |im| im.rotate90());
     }
     ///Rotates the image 270 degree clockwise
     pub fn rotate270cv(&mut self) {
-        let rotated = self.layers[0].rotate270();
-        self.layers.push_front(rotated);
+        self.transform("Rotate 270°", Transform::Rotate270(self.width()), |im| im.rotate270());
+    }
+    ///Draws a redaction with given center, size and style: the content of the rectangle is made unreadable.
+    ///The color is used by the solid fill only
+    pub fn draw_redaction(canva: &mut Layer, base: &mut Layer, center: (i32, i32), size: (i32, i32), style: RedactionStyle, color: &Color) {
+        let pos = (center.0-size.0/2, center.1-size.1/2);
+        let size = (size.0 as u32, size.1 as u32);
+        let mut new_canva = RgbaImage::new(base.layer.width(), base.layer.height());
+        if let Some(((x, y), patch)) = redact(&base.layer, pos, size, style, color) {
+            image::imageops::replace(&mut new_canva, &patch, x as i64, y as i64);
+        }
+        canva.layer = DynamicImage::ImageRgba8(new_canva);
+        canva.layer_type = LayerType::Shape(((pos.0 as u32, pos.1 as u32), size));
+        canva.annotation = Some(Annotation::new(AnnotationKind::Redaction { pos, size, style }, *color));
+    }
+    ///Draws a magnifier whose source rectangle has given center and size: a copy of the source enlarged by the zoom
+    ///is placed beside it, framed by borders with the given color and width and joined to the source by a line
+    pub fn draw_magnifier(canva: &mut Layer, base: &mut Layer, center: (i32, i32), size: (i32, i32), zoom: u32, color: &Color, width: i32) {
+        let source = ((center.0-size.0/2, center.1-size.1/2), (size.0 as u32, size.1 as u32));
+        let target_size = (source.1.0 * zoom, source.1.1 * zoom);
+        let target = magnifier_position(source, target_size, (base.layer.width(), base.layer.height()));
+        let annotation = Annotation::new(AnnotationKind::Magnifier { source_pos: source.0, source_size: source.1, pos: target, size: target_size, thickness: width }, *color);
+        canva.layer = render(&base.layer, std::slice::from_ref(&annotation));
+        canva.layer_type = LayerType::Shape(((source.0.0 as u32, source.0.1 as u32), source.1));
+        canva.annotation = Some(annotation);
+    }
+    ///Draws a bubble with given center, size and color holding the text, written with the given font.
+    ///If a tail tip is given it is a speech bubble, whose tip is marked by a handle to drag it, otherwise a label.
+    ///The canvas is as large as the image
+    pub fn draw_bubble(canva: &mut Layer, center: (i32, i32), size: (i32, i32), tail: Option<(i32, i32)>, text: &str, font: TextFont, color: &Color) {
+        let pos = (center.0-size.0/2, center.1-size.1/2);
+        let size = (size.0 as u32, size.1 as u32);
+        let annotation = Annotation::new(AnnotationKind::Bubble { pos, size, tail, text: text.to_string(), font }, *color);
+        let (width, height) = (canva.layer.width(), canva.layer.height());
+        let mut new_canva = DynamicImage::ImageRgba8(RgbaImage::new(width, height));
+        annotation.draw(&mut new_canva, &canva.layer, &mut Layer::new(DynamicImage::new_rgba8(0, 0), LayerType::BaseImage));
+        if let Some(tip) = tail {
+            let radius = (u32::min(width, height)/100) as i32;
+            drawing::draw_filled_circle_mut(&mut new_canva, tip, radius, Color::new(0, 255, 255, 1.0).color);
+        }
+        canva.layer = new_canva;
+        canva.layer_type = LayerType::Shape(((pos.0 as u32, pos.1 as u32), size));
+        canva.annotation = Some(annotation);
     }
-    ///Draws a filled circle with given center, diameter and color
-    pub fn draw_filled_circle(canva: &mut Layer, base: &mut Layer, center: (i32, i32), diameter: i32, color: &Color) {
-        let pos = (center.0-diameter/2, center.1-diameter/2);
+    ///Draws a filled ellipse with given center, size of the bounding rectangle and color
+    pub fn draw_filled_ellipse(canva: &mut Layer, base: &mut Layer, center: (i32, i32), size: (i32, i32), color: &Color) {
+        let pos = (center.0-size.0/2, center.1-size.1/2);
+        let size = (size.0 as u32, size.1 as u32);
         let mut new_canva = RgbaImage::new(base.layer.width() as u32, base.layer.height() as u32);
-        drawing::draw_filled_circle_mut(&mut new_canva, center, diameter/2, color.color);
+        path::fill_ellipse(&mut new_canva, pos, size, color.color);
         canva.layer = DynamicImage::ImageRgba8(new_canva);
-        canva.layer_type = LayerType::Shape(((pos.0 as u32, pos.1 as u32),(diameter as u32, diameter as u32)));
+        canva.layer_type = LayerType::Shape(((pos.0 as u32, pos.1 as u32), size));
+        canva.annotation = Some(Annotation::new(AnnotationKind::Ellipse { pos, size, filled: true, thickness: 0 }, *color));
     }
-    ///Draws an empty circle with given center, diameter, color and contour width
-    pub fn draw_empty_circle(canva: &mut Layer, base: &mut Layer, center: (i32, i32), mut diameter: i32, color: &Color, width: i32) {
-        let pos_2 = ((center.0-diameter/2) as u32, (center.1-diameter/2) as u32);
-        let size_2 = (diameter as u32, diameter as u32);
+    ///Draws an empty ellipse with given center, size of the bounding rectangle, color and contour width
+    pub fn draw_empty_ellipse(canva: &mut Layer, base: &mut Layer, center: (i32, i32), size: (i32, i32), color: &Color, width: i32) {
+        let pos = (center.0-size.0/2, center.1-size.1/2);
+        let size = (size.0 as u32, size.1 as u32);
         let mut new_canva = RgbaImage::new(base.layer.width() as u32, base.layer.height() as u32);
-        let mut pos = (center.0-diameter/2, center.1-diameter/2);
-        for _ in 0..width{
-            drawing::draw_hollow_circle_mut(&mut new_canva, center, diameter/2, color.color);
-            pos.0 += 1;
-            pos.1 += 1;
-            diameter-=2;
-        }
+        path::stroke_ellipse(&mut new_canva, pos, size, width, color.color);
         canva.layer = DynamicImage::ImageRgba8(new_canva);
-        canva.layer_type = LayerType::Shape(((pos_2.0, pos_2.1),(size_2.0, size_2.1)));
+        canva.layer_type = LayerType::Shape(((pos.0 as u32, pos.1 as u32), size));
+        canva.annotation = Some(Annotation::new(AnnotationKind::Ellipse { pos, size, filled: false, thickness: width }, *color));
     }
-    ///Draws a filled rectangle given the upper-left corner, its dimensions and
+    ///Draws a filled rectangle given the center, its dimensions, the radius of its corners and
     ///its color
-    pub fn draw_filled_rectangle(canva: &mut Layer, base: &mut Layer, center: (i32, i32), size: (i32, i32), color: &Color) {
+    pub fn draw_filled_rectangle(canva: &mut Layer, base: &mut Layer, center: (i32, i32), size: (i32, i32), radius: u32, color: &Color) {
         let pos = (center.0-size.0/2, center.1-size.1/2);
         let mut new_canva = RgbaImage::new(base.layer.width() as u32, base.layer.height() as u32);
-        let rect = Rect::at(center.0-size.0/2, center.1-size.1/2).of_size(size.0 as u32, size.1 as u32);
-        drawing::draw_filled_rect_mut(&mut new_canva, rect, color.color);
+        path::fill_rectangle(&mut new_canva, pos, (size.0 as u32, size.1 as u32), radius, color.color);
         canva.layer = DynamicImage::ImageRgba8(new_canva);
         canva.layer_type = LayerType::Shape(((pos.0 as u32, pos.1 as u32),(size.0 as u32, size.1 as u32)));
+        canva.annotation = Some(Annotation::new(AnnotationKind::Rectangle { pos, size: (size.0 as u32, size.1 as u32), radius, filled: true, thickness: 0 }, *color));
     }
-    ///Draws an empty rectangle given the upper-left corner, its dimensions and
-    ///its color
-    pub fn draw_empty_rectangle(canva: &mut Layer, base: &mut Layer, center: (i32, i32), mut size: (i32, i32), color: &Color, width: i32) {
-        let pos_2 = ((center.0-size.0/2) as u32, (center.1-size.1/2) as u32);
-        let size_2 = (size.0 as u32, size.1 as u32);
+    ///Draws an empty rectangle given the center, its dimensions, the radius of its corners,
+    ///its color and the contour width
+    pub fn draw_empty_rectangle(canva: &mut Layer, base: &mut Layer, center: (i32, i32), size: (i32, i32), radius: u32, color: &Color, width: i32) {
+        let pos = (center.0-size.0/2, center.1-size.1/2);
+        let size = (size.0 as u32, size.1 as u32);
         let mut new_canva = RgbaImage::new(base.layer.width() as u32, base.layer.height() as u32);
-        let mut pos = (center.0-size.0/2, center.1-size.1/2);
-        for _ in 0..width{
-            let rect = Rect::at(pos.0, pos.1).of_size(size.0 as u32, size.1 as u32);
-            drawing::draw_hollow_rect_mut(&mut new_canva, rect, color.color);
-            pos.0 += 1;
-            pos.1 += 1;
-            size.0-=2;
-            size.1-=2;
-            if size.0 <=0 || size.1 <=0 {
-                break;
-            }
-        }
+        path::stroke_rectangle(&mut new_canva, pos, size, radius, width, color.color);
         canva.layer = DynamicImage::ImageRgba8(new_canva);
-        canva.layer_type = LayerType::Shape(((pos_2.0, pos_2.1),(size_2.0, size_2.1)));
+        canva.layer_type = LayerType::Shape(((pos.0 as u32, pos.1 as u32), size));
+        canva.annotation = Some(Annotation::new(AnnotationKind::Rectangle { pos, size, radius, filled: false, thickness: width }, *color));
     }
     ///Creates an arrow pointing upward, given its center, size and color
     pub fn draw_filled_up_arrow(canva: &mut Layer, base: &mut Layer, center: (i32, i32), size: (i32, i32), color: &Color) {
@@ -153,9 +426,10 @@ impl Image {
         let mut new_canva = RgbaImage::new(base.layer.width() as u32, base.layer.height() as u32);
         let arrow = Arrow::up_from_size(center, size);
         let poly = Polygon::from(arrow.vertices);
-        drawing::draw_polygon_mut(&mut new_canva, &poly.vertices, color.color);
+        path::fill_polygon(&mut new_canva, &poly.points(), color.color);
         canva.layer = DynamicImage::ImageRgba8(new_canva);
         canva.layer_type = LayerType::Shape(((pos.0 as u32, pos.1 as u32),(size.0 as u32, size.1 as u32)));
+        canva.annotation = Some(Annotation::new(AnnotationKind::Arrow { pos, size: (size.0 as u32, size.1 as u32), pointing: Pointing::Up }, *color));
     }
     ///Creates an arrow pointing right, given its center, size and color
     pub fn draw_filled_right_arrow(canva: &mut Layer, base: &mut Layer, center: (i32, i32), size: (i32, i32), color: &Color) {
@@ -163,9 +437,10 @@ impl Image {
         let mut new_canva = RgbaImage::new(base.layer.width() as u32, base.layer.height() as u32);
         let arrow = Arrow::right_from_size(center, size);
         let poly = Polygon::from(arrow.vertices);
-        drawing::draw_polygon_mut(&mut new_canva, &poly.vertices, color.color);
+        path::fill_polygon(&mut new_canva, &poly.points(), color.color);
         canva.layer = DynamicImage::ImageRgba8(new_canva);
         canva.layer_type = LayerType::Shape(((pos.0 as u32, pos.1 as u32),(size.0 as u32, size.1 as u32)));
+        canva.annotation = Some(Annotation::new(AnnotationKind::Arrow { pos, size: (size.0 as u32, size.1 as u32), pointing: Pointing::Right }, *color));
     }
     ///Creates an arrow pointing left, given its center, size and color
     pub fn draw_filled_left_arrow(canva: &mut Layer, base: &mut Layer, center: (i32, i32), size: (i32, i32), color: &Color) {
@@ -173,9 +448,10 @@ impl Image {
         let mut new_canva = RgbaImage::new(base.layer.width() as u32, base.layer.height() as u32);
         let arrow = Arrow::left_from_size(center, size);
         let poly = Polygon::from(arrow.vertices);
-        drawing::draw_polygon_mut(&mut new_canva, &poly.vertices, color.color);
+        path::fill_polygon(&mut new_canva, &poly.points(), color.color);
         canva.layer = DynamicImage::ImageRgba8(new_canva);
         canva.layer_type = LayerType::Shape(((pos.0 as u32, pos.1 as u32),(size.0 as u32, size.1 as u32)));
+        canva.annotation = Some(Annotation::new(AnnotationKind::Arrow { pos, size: (size.0 as u32, size.1 as u32), pointing: Pointing::Left }, *color));
     }
     ///Creates an arrow pointing downward, given its center, size and color
     pub fn draw_filled_down_arrow(canva: &mut Layer, base: &mut Layer, center: (i32, i32), size: (i32, i32), color: &Color) {
@@ -183,203 +459,72 @@ impl Image {
         let mut new_canva = RgbaImage::new(base.layer.width() as u32, base.layer.height() as u32);
         let arrow = Arrow::down_from_size(center, size);
         let poly = Polygon::from(arrow.vertices);
-        drawing::draw_polygon_mut(&mut new_canva, &poly.vertices, color.color);
+        path::fill_polygon(&mut new_canva, &poly.points(), color.color);
         canva.layer = DynamicImage::ImageRgba8(new_canva);
         canva.layer_type = LayerType::Shape(((pos.0 as u32, pos.1 as u32),(size.0 as u32, size.1 as u32)));
-    }
-    ///Puts a text in the image given the text to write, its color, the position of the upper-left corner,
-    ///the font and the font size 
-    pub fn put_text<'a>(&mut self, start: (i32, i32), color: &Color, text: &str, font_size: f32, font: &'a rusttype::Font<'a>) {
-        let mut layer = self.layers[0].clone();
-        drawing::draw_text_mut(&mut layer, color.color, start.0, start.1, rusttype::Scale::uniform(font_size), font, text);
-        self.layers.push_front(layer);
-    }
-    ///Initializes a Layer for free-hand drawing. Return an empty layer on which
-    ///it is possible to draw
-    pub fn free_hand_draw_init(&self) -> Layer {
-        let layer = self.layers[0].clone();
-        let layer = Layer::new(layer,LayerType::FreeHandDrawing);
-        layer
-    }
-    ///Finalizes the free-hand drawing layer and puts it with the others. Takes as parameter the previously
-    ///defines Layer used for drawing
-    pub fn free_hand_draw_set(&mut self, layer: Layer, _last: (i32, i32), _size: i32, _color: &Color ) {
-        self.layers.push_front(layer.layer);
-    }
-    ///Draws a point on a previously deifned Layer (returned by free_hand_draw_init) given the point position
-    ///and its color. Takes a mutable reference to such Layer
-    pub fn draw_point(layer: &mut Layer, prev: Option<((i32, i32), (i32, i32), (i32, i32))>, current: (i32, i32), mut size: i32, color: &Color) -> ((i32, i32), (i32, i32), (i32, i32)) {
-        
-        let c1 = current;
-        if size%2==0{
-            size = size + 1;
-        }
-
-        match prev {
-            Some((_, _, c0)) => {
-
-                let d = (c1.0-c0.0, c1.1-c0.1);
-
-                let xc0 = c0.0 as f32;
-                let yc0 = c0.1 as f32;
-                let xc1 = c1.0 as f32;
-                let yc1 = c1.1 as f32;
-
-                if size == 1 {
-                    drawing::draw_antialiased_line_segment_mut(
-                        &mut layer.layer, 
-                        c0, 
-                        c1, 
-                        color.color, 
-                        |a,b,c| {
-                            interpolate(a, b, c)
-                        }
-                    );
-                    return (c1,c1,c1);
-                }
-
-                let m1 = (yc1-yc0)/(xc1-xc0);
-
-                if m1.is_nan() || m1.is_infinite() {
-                    
-                    let l1 = (c1.0 - size/2, c1.1);
-                    let r1 = (c1.0 + size/2, c1.1);
-                    let l0 = (l1.0-d.0, l1.1-d.1);
-                    let r0 = (r1.0-d.0, r1.1-d.1);
-
-                    let points = Vec::from(
-                        [
-                            Point::new(l0.0, l0.1),
-                            Point::new(l1.0, l1.1),
-                            Point::new(r1.0, r1.1),
-                            Point::new(r0.0, r0.1)
-                        ]
-                    );
-
-                    drawing::draw_polygon_mut(&mut layer.layer, &points, color.color);
-                    drawing::draw_filled_circle_mut(&mut layer.layer, c1, size/2, color.color);
-
-                    return (l1,r1,c1);
-
-                } else if m1==0.0 {
-                    
-                    let l1 = (c1.0, c1.1 - size/2);
-                    let r1 = (c1.0, c1.1 + size/2);
-                    let l0 = (l1.0-d.0, l1.1-d.1);
-                    let r0 = (r1.0-d.0, r1.1-d.1);
-
-                    let points = Vec::from(
-                        [
-                            Point::new(l0.0, l0.1),
-                            Point::new(l1.0, l1.1),
-                            Point::new(r1.0, r1.1),
-                            Point::new(r0.0, r0.1)
-                        ]
-                    );
-
-                    drawing::draw_polygon_mut(&mut layer.layer, &points, color.color);
-                    drawing::draw_filled_circle_mut(&mut layer.layer, c1, size/2, color.color);
-
-                    return (l1,r1,c1);
-
-                } else {
-
-                    let size = size as f32;
-
-                    let m2 = -1.0/m1;
-                    let q2 = yc1-m2*xc1;
-                    let r = size/2.0;
-
-                    let m22 = f32::powi(m2,2);
-                    let r2 = f32::powi(r as f32,2);
-                    let xc12 = f32::powi(xc1,2);
-                    let yc12 = f32::powi(yc1,2);
-                    let q22 = f32::powi(q2, 2);
-
-                    let xl1 = -(q2 - (q2 + m2*xc1 + m2*f32::sqrt(m22*r2 - m22*xc12 - 2.0*m2*q2*xc1 + 2.0*m2*xc1*yc1 - q22 + 2.0*q2*yc1 + r2 - yc12) + m22*yc1)/(m22 + 1.0))/m2;
-                    let yl1 = (q2 + m2*xc1 + m2*f32::sqrt(m22*r2 - m22*xc12 - 2.0*m2*q2*xc1 + 2.0*m2*xc1*yc1 - q22 + 2.0*q2*yc1 + r2 - yc12) + m22*yc1)/(m22 + 1.0);
-                    let xr1 = -(q2 - (q2 + m2*xc1 - m2*f32::sqrt(m22*r2 - m22*xc12 - 2.0*m2*q2*xc1 + 2.0*m2*xc1*yc1 - q22 + 2.0*q2*yc1 + r2 - yc12) + m22*yc1)/(m22 + 1.0))/m2;
-                    let yr1 = (q2 + m2*xc1 - m2*f32::sqrt(m22*r2 - m22*xc12 - 2.0*m2*q2*xc1 + 2.0*m2*xc1*yc1 - q22 + 2.0*q2*yc1 + r2 - yc12) + m22*yc1)/(m22 + 1.0);
-
-                    let l1 = (xl1 as i32, yl1 as i32);
-                    let r1 = (xr1 as i32, yr1 as i32);
-                    let l0 = (l1.0-d.0, l1.1-d.1);
-                    let r0 = (r1.0-d.0, r1.1-d.1);
-
-                    let points = Vec::from(
-                        [
-                            Point::new(l0.0, l0.1),
-                            Point::new(l1.0, l1.1),
-                            Point::new(r1.0, r1.1),
-                            Point::new(r0.0, r0.1)
-                        ]
-                    );
-
-                    drawing::draw_polygon_mut(&mut layer.layer, &points, color.color);
-                    drawing::draw_antialiased_line_segment_mut(
-                        &mut layer.layer, 
-                        l0, 
-                        l1, 
-                        color.color, 
-                        |a,b,c| {
-                            interpolate(a, b, c)
-                        }
-                    );
-                    drawing::draw_antialiased_line_segment_mut(
-                        &mut layer.layer, 
-                        r0, 
-                        r1, 
-                        color.color, 
-                        |a,b,c| {
-                            interpolate(a, b, c)
-                        }
-                    );
-                    drawing::draw_filled_circle_mut(&mut layer.layer, c1, (size as i32)/2, color.color);
-
-                    return (l1,r1,c1);
-
-                }
-            },
-            None => {
-                let l0 = (current.0-size/2, current.1);
-                let r0 = (current.0+size/2, current.1);
-
-                imageproc::drawing::draw_filled_circle_mut(&mut layer.layer, current, size/2, color.color);
-
-                return (l0, r0, current);
-            }
+        canva.annotation = Some(Annotation::new(AnnotationKind::Arrow { pos, size: (size.0 as u32, size.1 as u32), pointing: Pointing::Down }, *color));
+    }
+    ///Returns a text annotation given the text to write, its color, the position of the upper-left corner of its box,
+    ///the font (along with its name), the font size and the style
+    pub fn text(start: (i32, i32), color: &Color, text: &str, font_size: f32, font_name: &str, font: &rusttype::Font<'static>, style: TextStyle) -> Annotation {
+        let font = TextFont { name: font_name.to_string(), font: font.clone() };
+        Annotation::new(AnnotationKind::Text { pos: start, text: text.to_string(), font, font_size, style }, *color)
+    }
+    ///Returns the image with the given annotation drawn over it, without adding it
+    pub fn preview(&self, annotation: &Annotation) -> DynamicImage {
+        let mut annotations = self.annotations().to_vec();
+        annotations.push(annotation.clone());
+        render(self.background(), &annotations)
+    }
+    ///Places a numbered step marker with given center, diameter and color; it takes the number following the markers already placed
+    pub fn put_marker(&mut self, center: (i32, i32), size: u32, color: &Color, font_name: &str, font: &rusttype::Font<'static>) {
+        let font = TextFont { name: font_name.to_string(), font: font.clone() };
+        let pos = (center.0 - size as i32 / 2, center.1 - size as i32 / 2);
+        self.push_annotation(Annotation::new(AnnotationKind::Marker { pos, size, number: 0, font }, *color));
+    }
+    ///Initializes two layers for free-hand drawing: the image and a transparent canvas on which it is possible to draw,
+    ///blended over the image when shown
+    pub fn free_hand_draw_init(&self) -> (Layer, Layer) {
+        self.highlight_init()
+    }
+    ///Finalizes the free-hand drawing, adding the line drawn on the given Layer as an annotation
+    ///with the given thickness and color
+    pub fn free_hand_draw_set(&mut self, layer: Layer, size: i32, color: &Color) {
+        if !layer.points.is_empty() {
+            self.push_annotation(Annotation::new(AnnotationKind::Stroke { points: layer.points, thickness: size }, *color));
         }
     }
+    ///Draws a point on a previously deifned Layer (returned by free_hand_draw_init) given the point position, the line
+    ///thickness and its color, joined to the previous point if any. Returns the point, to be passed as the previous one next time
+    pub fn draw_point(layer: &mut Layer, prev: Option<(i32, i32)>, current: (i32, i32), size: i32, color: &Color) -> (i32, i32) {
+        layer.points.push(current);
+        with_rgba8(&mut layer.layer, |image| line::paint_segment(image, prev.unwrap_or(current), current, size, color.color));
+        current
+    }
     ///Initializes a Layer for erasing. Return an empty layer on which
-    ///it is possible to use the rubber
-    pub fn rubber_init(&self, last_crop_data: Option<((u32, u32),(u32, u32))>) -> (Layer, Layer) {
-        let layer = self.layers[0].clone();
+    ///it is possible to use the rubber, along with the background it uncovers
+    pub fn rubber_init(&self) -> (Layer, Layer) {
+        let layer = self.show();
         let layer = Layer::new(layer,LayerType::BaseImage);
-        let mut base = self.base.clone();
-        let base = match last_crop_data {
-            Some(last_crop_data) => {
-                base.crop(last_crop_data.0.0, last_crop_data.0.1, last_crop_data.1.0, last_crop_data.1.1)
-            },
-            None => {
-                base
-            }
-        };
+        let base = uncovered(self.background(), self.annotations());
         let base = Layer::new(base,LayerType::FreeHandDrawing);
         (base, layer)
     }
-    ///Sets the rubber modification, finalizing them
-    pub fn rubber_set(&mut self, layer: Layer, base: &Layer, _last: (i32, i32), _size: i32) {
-        let layer = layer.show_rubber(base);
-        self.layers.push_front(layer);
+    ///Sets the rubber modification, adding the erased line as an annotation with the given thickness
+    pub fn rubber_set(&mut self, layer: Layer, _base: &Layer, size: i32) {
+        if !layer.points.is_empty() {
+            self.push_annotation(Annotation::new(AnnotationKind::Eraser { points: layer.points, thickness: size }, Color::new(0, 0, 0, 0.0)));
+        }
     }
     ///Erases part of the drawings
-    pub fn rubber(layer: &mut Layer, prev: Option<((i32, i32),(i32, i32),(i32, i32))>, current: (i32, i32), size: i32) -> ((i32, i32), (i32, i32), (i32, i32)) {
-        let color = Color::new(0, 0, 0, 0.0);
-        Image::draw_point(layer, prev, current, size, &color)
+    pub fn rubber(layer: &mut Layer, prev: Option<(i32, i32)>, current: (i32, i32), size: i32) -> (i32, i32) {
+        layer.points.push(current);
+        with_rgba8(&mut layer.layer, |image| line::erase_segment(image, prev.unwrap_or(current), current, size));
+        current
     }
     ///Initilizes a layer for higliting
     pub fn highlight_init(&self) -> (Layer, Layer) {
-        let base = self.layers[0].clone();
+        let base = self.show();
         let base = Layer::new(base,LayerType::FreeHandDrawing);
         let width = base.layer.width();
         let height = base.layer.height();
@@ -387,23 +532,71 @@ impl Image {
         let canva = Layer::new(DynamicImage::ImageRgba8(canva), LayerType::BaseImage);
         (base, canva)
     }
-    ///Sets the higlight layer, finalizing the modifications
-    pub fn highlight_set(&mut self, layer: Layer, base: &Layer, _last: (i32, i32), _size: i32, _color: &Color) {
-        //imageproc::drawing::draw_filled_circle_mut(&mut layer.layer, last, size/2, color.color);
-        let layer = layer.show_higlight(base);
-        self.layers.push_front(layer);
+    ///Sets the higlight layer, adding the highlighted line as an annotation with the given thickness and color
+    pub fn highlight_set(&mut self, layer: Layer, _base: &Layer, size: i32, color: &Color) {
+        if !layer.points.is_empty() {
+            self.push_annotation(Annotation::new(AnnotationKind::Highlight { points: layer.points, thickness: size }, *color));
+        }
     }
     ///Higlights the layer
-    pub fn highlight(layer: &mut Layer, prev: Option<((i32, i32),(i32, i32),(i32, i32))>, current: (i32, i32), size: i32, color: &Color) -> ((i32, i32), (i32, i32), (i32, i32)) {
+    pub fn highlight(layer: &mut Layer, prev: Option<(i32, i32)>, current: (i32, i32), size: i32, color: &Color) -> (i32, i32) {
         Image::draw_point(layer, prev, current, size, color)
     }
 
-    ///Remove the most recent created layer
+    ///Initializes two layers for drawing straight lines: the image and a transparent canvas
+    pub fn line_init(&self) -> (Layer, Layer) {
+        self.highlight_init()
+    }
+    ///Draws on the canvas the straight line from start to end with the given thickness, arrow heads and color
+    pub fn draw_line(canva: &mut Layer, start: (i32, i32), end: (i32, i32), thickness: i32, head: ArrowHead, color: &Color) {
+        let mut new_canva = RgbaImage::new(canva.layer.width(), canva.layer.height());
+        line::draw_line(&mut new_canva, start, end, thickness, head, color.color);
+        canva.layer = DynamicImage::ImageRgba8(new_canva);
+        canva.annotation = Some(Annotation::new(AnnotationKind::Line { start, end, thickness, head }, *color));
+    }
+    ///Finalizes the line drawing, adding the line drawn on the given layer as an annotation
+    pub fn line_set(&mut self, layer: Layer) {
+        if let Some(annotation) = layer.annotation {
+            self.push_annotation(annotation);
+        }
+    }
+
+    ///Undoes the most recent edit, which can be redone until a new edit is made
     pub fn undo(&mut self) -> DynamicImage {
-        if self.layers.len() > 1 {
-            self.layers.pop_front();
+        if self.can_undo() {
+            self.set_position(self.position + 1);
+        }
+        self.show()
+    }
+    ///Redoes the most recently undone edit
+    pub fn redo(&mut self) -> DynamicImage {
+        if self.can_redo() {
+            self.set_position(self.position - 1);
         }
-        self.layers[0].clone()
+        self.show()
+    }
+    ///Returns true if there is an edit to undo
+    pub fn can_undo(&self) -> bool {
+        self.position + 1 < self.history.len()
+    }
+    ///Returns true if there is an undone edit to redo
+    pub fn can_redo(&self) -> bool {
+        self.position > 0
+    }
+    ///Returns the names of the edits in the history, from the oldest one, including the undone ones
+    pub fn steps(&self) -> Vec<&str> {
+        self.history.iter().rev().map(|d| d.step.as_str()).collect()
+    }
+    ///Returns the index in steps of the current edit
+    pub fn current_step(&self) -> usize {
+        self.history.len() - 1 - self.position
+    }
+    ///Brings the image back (or forward) to the state right after the given step
+    pub fn go_to_step(&mut self, step: usize) -> DynamicImage {
+        if step < self.history.len() {
+            self.set_position(self.history.len() - 1 - step);
+        }
+        self.show()
     }
     ///Saves the image given the extension and the name one want to give it. The name includes also 
     ///the path.
@@ -437,12 +630,12 @@ impl Image {
         }
         let mut path = String::from(location);
         path.push_str(n.as_str());    let _file = File::create(&path)?;
-        let image  = self.layers[0].clone();    image.save(path)?;
+        let image  = self.show();    image.save(path)?;
         Ok(())
     }
-    ///Returns the current image
+    ///Returns the current image, with all the annotations drawn over it
     pub fn show(&self) -> DynamicImage {
-        self.layers[0].clone()
+        render(self.background(), &self.current().annotations)
     }
     ///Copies the image to the clipboard
     ///An error is returned if the operation is not successfull
@@ -458,7 +651,7 @@ impl Image {
     }
     ///Initilizes two layers for drawing shapes
     pub fn shape_init(&self, center: (u32, u32), size: (u32, u32)) -> (Layer, Layer) {
-        let base = self.layers[0].clone();
+        let base = self.show();
         let width = base.width();
         let height = base.height();
         let canva = RgbaImage::new(width,height);
@@ -466,39 +659,21 @@ impl Image {
 
         (Layer::new(base,LayerType::BaseImage), Layer::new(DynamicImage::ImageRgba8(canva),LayerType::Shape((pos,size))))
     }
-    ///Finalizes the shape drawings
-    pub fn shape_set(&mut self, base: Layer, shape_layer: Layer) {
-        let image = shape_layer.draw_shape(&base);
-        self.layers.push_front(image);
+    ///Finalizes the shape drawings, adding the shape drawn on the given Layer as an annotation
+    pub fn shape_set(&mut self, _base: Layer, shape_layer: Layer) {
+        if let Some(annotation) = shape_layer.annotation {
+            self.push_annotation(annotation);
+        }
     }
 
 }
 
-pub fn get_image(filepath: &str, _ix: u32, _iy: u32, _iw: u32, _ih: u32) -> egui::ImageData {
-    let fp = std::path::Path::new(filepath);
-    let color_image = load_image_from_path(&fp).unwrap();
-    std::fs::remove_file(".tmp.png").unwrap();
-    let img = egui::ImageData::from(color_image);
-    img
-}
-
 pub fn get_image_from_memory(di: DynamicImage, _ix: u32, _iy: u32, _iw: u32, _ih: u32) -> egui::ImageData {
     let color_image = load_image_from_memory(di).unwrap();
     let img = egui::ImageData::from(color_image);
     img
 }
 
-pub fn load_image_from_path(path: &std::path::Path) -> Result<egui::ColorImage, image::ImageError> {
-    let image = image::io::Reader::open(path)?.decode()?;
-    let size = [image.width() as _, image.height() as _];
-    let image_buffer = image.to_rgba8();
-    let pixels = image_buffer.as_flat_samples();
-    Ok(egui::ColorImage::from_rgba_unmultiplied(
-        size,
-        pixels.as_slice(),
-    ))
-}
-
 pub fn load_image_from_memory(image_data: DynamicImage) -> Result<egui::ColorImage, image::ImageError> {
     //let image = image::load_from_memory(&image_data)?;
     let size = [image_data.width() as _, image_data.height() as _];
@@ -508,4 +683,149 @@ pub fn load_image_from_memory(image_data: DynamicImage) -> Result<egui::ColorIma
         size,
         pixels.as_slice(),
     ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+
+    ///The memory limit of the history is shared by all the images, so the tests using it run one at a time
+    static HISTORY_LIMIT: Mutex<()> = Mutex::new(());
+
+    fn history_limit(bytes: usize) -> MutexGuard<'static, ()> {
+        let guard = HISTORY_LIMIT.lock().unwrap_or_else(|e| e.into_inner());
+        set_history_memory_limit(bytes);
+        guard
+    }
+
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| image::Rgba([(x * 4) as u8, (y * 4) as u8, 128, 255])))
+    }
+
+    fn rectangle(x: i32, y: i32) -> Annotation {
+        Annotation::new(
+            AnnotationKind::Rectangle { pos: (x, y), size: (10, 6), radius: 0, filled: true, thickness: 1 },
+            Color::new(255, 0, 0, 1.0)
+        )
+    }
+
+    fn same_pixels(a: &DynamicImage, b: &DynamicImage) -> bool {
+        a.width() == b.width() && a.height() == b.height() && a.to_rgba8().as_raw() == b.to_rgba8().as_raw()
+    }
+
+    #[test]
+    fn evicted_history_renders_the_steps_left() {
+        let _guard = history_limit(3 * 64 * 64 * 4);
+        let mut image = Image::from_dynamic_image(gradient(64, 64));
+        let mut shown = vec![image.show()];
+        for i in 0..4 {
+            image.push_annotation(rectangle(i * 12, i * 8));
+            shown.push(image.show());
+            image.rotate90cv();
+            shown.push(image.show());
+        }
+        image.flip_horizontally();
+        shown.push(image.show());
+        set_history_memory_limit(512 * 1024 * 1024);
+
+        let kept = image.steps().len();
+        assert!(kept > 1 && kept < shown.len());
+        let first = shown.len() - kept;
+        for step in (0..kept).rev() {
+            assert!(same_pixels(&image.go_to_step(step), &shown[first + step]));
+        }
+        assert!(!image.can_undo());
+        for step in 1..kept {
+            assert!(same_pixels(&image.redo(), &shown[first + step]));
+        }
+        assert!(!image.can_redo());
+        for step in (0..kept - 1).rev() {
+            assert!(same_pixels(&image.undo(), &shown[first + step]));
+        }
+    }
+
+    #[test]
+    fn erasing_a_redaction_does_not_reveal_the_content() {
+        let _guard = history_limit(512 * 1024 * 1024);
+        let original = gradient(64, 64);
+        let eraser = Annotation::new(
+            AnnotationKind::Eraser { points: vec![(0, 32), (63, 32)], thickness: 80 },
+            Color::new(0, 0, 0, 0.0)
+        );
+        for style in [RedactionStyle::Pixelate, RedactionStyle::Blur, RedactionStyle::Fill] {
+            let mut image = Image::from_dynamic_image(original.clone());
+            image.push_annotation(Annotation::new(
+                AnnotationKind::Redaction { pos: (16, 16), size: (32, 32), style },
+                Color::new(0, 0, 0, 1.0)
+            ));
+            let redacted = image.show();
+            image.push_annotation(rectangle(20, 20));
+            image.push_annotation(eraser.clone());
+
+            //the drawing over the redaction is erased, the redaction is not
+            let erased = image.show();
+            assert!(same_pixels(&erased, &redacted));
+            assert!(!same_pixels(&erased.crop_imm(16, 16, 32, 32), &original.crop_imm(16, 16, 32, 32)));
+            let (base, _) = image.rubber_init();
+            assert!(same_pixels(&base.layer, &redacted));
+        }
+    }
+
+    fn font() -> rusttype::Font<'static> {
+        rusttype::Font::try_from_bytes(include_bytes!("../fonts/JetBrainsMono-Regular.ttf")).unwrap()
+    }
+
+    fn marker_numbers(image: &Image) -> Vec<u32> {
+        image.annotations().iter().filter_map(|a| match a.kind {
+            AnnotationKind::Marker { number, .. } => Some(number),
+            _ => None
+        }).collect()
+    }
+
+    #[test]
+    fn deleted_marker_closes_the_gap() {
+        let _guard = history_limit(512 * 1024 * 1024);
+        let (font, color) = (font(), Color::new(255, 0, 0, 1.0));
+        let mut image = Image::from_dynamic_image(gradient(64, 64));
+        image.put_marker((10, 10), 12, &color, "Regular", &font);
+        image.push_annotation(rectangle(30, 30));
+        image.put_marker((30, 10), 12, &color, "Regular", &font);
+        image.put_marker((50, 10), 12, &color, "Regular", &font);
+        assert_eq!(marker_numbers(&image), vec![1, 2, 3]);
+
+        image.remove_annotation(0);
+        assert_eq!(marker_numbers(&image), vec![1, 2]);
+        image.undo();
+        assert_eq!(marker_numbers(&image), vec![1, 2, 3]);
+        image.redo();
+        assert_eq!(marker_numbers(&image), vec![1, 2]);
+    }
+
+    #[test]
+    fn undone_marker_number_is_taken_again() {
+        let _guard = history_limit(512 * 1024 * 1024);
+        let (font, color) = (font(), Color::new(255, 0, 0, 1.0));
+        let mut image = Image::from_dynamic_image(gradient(64, 64));
+        image.put_marker((10, 10), 12, &color, "Regular", &font);
+        image.put_marker((30, 10), 12, &color, "Regular", &font);
+        image.undo();
+        assert_eq!(marker_numbers(&image), vec![1]);
+        image.put_marker((50, 10), 12, &color, "Regular", &font);
+        assert_eq!(marker_numbers(&image), vec![1, 2]);
+        assert!(!image.can_redo());
+    }
+
+    #[test]
+    fn undecodable_background_is_not_reached() {
+        let _guard = history_limit(512 * 1024 * 1024);
+        let mut image = Image::from_dynamic_image(gradient(64, 64));
+        image.rotate90cv();
+        let rotated = image.show();
+        let key = image.history.back().unwrap().background;
+        image.backgrounds.insert(key, Background::Compressed(Arc::new(vec![1, 2, 3])));
+
+        assert!(same_pixels(&image.undo(), &rotated));
+        assert_eq!(image.current_step(), 1);
+    }
 }
\ No newline at end of file
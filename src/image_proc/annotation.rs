@@ -0,0 +1,735 @@
+use std::fmt::{Debug, Display, Formatter};
+use image::{DynamicImage, Rgba, RgbaImage};
+use image::imageops::{replace, FilterType};
+use imageproc::filter::gaussian_blur_f32;
+use rusttype::{Font, Scale};
+
+use super::blend::{blend, composite, with_rgba8};
+use super::colors::Color;
+use super::layer::{Layer, LayerType};
+use super::line::{draw_disc, draw_line, head_size};
+use super::path::{Join, Mask};
+use super::text::{draw_text, fit_font_size, text_size};
+use super::Image;
+
+///Direction an arrow points to
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pointing{
+    Up,
+    Down,
+    Left,
+    Right
+}
+
+///Heads drawn at the ends of a straight line
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ArrowHead {
+    ///Plain line
+    None,
+    ///Two strokes at the end
+    Open,
+    ///Filled triangle at the end
+    Filled,
+    ///Filled triangles at both ends
+    Double
+}
+
+impl Display for ArrowHead {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArrowHead::None => write!(f, "None"),
+            ArrowHead::Open => write!(f, "Open"),
+            ArrowHead::Filled => write!(f, "Filled"),
+            ArrowHead::Double => write!(f, "Double")
+        }
+    }
+}
+
+///Smallest side of the blocks of a pixelated area, large enough to make text unreadable
+const MIN_PIXEL_BLOCK: u32 = 12;
+
+///Way an area is made unreadable by a redaction
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RedactionStyle {
+    ///Mosaic of blocks, each filled with the mean color of its pixels
+    Pixelate,
+    ///Gaussian blur. It is only cosmetic: blurred text can often be recovered,
+    ///so secrets should be hidden with Pixelate or Fill
+    Blur,
+    ///Solid fill with the color of the annotation
+    Fill
+}
+
+impl Display for RedactionStyle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RedactionStyle::Pixelate => write!(f, "Pixelate"),
+            RedactionStyle::Blur => write!(f, "Blur"),
+            RedactionStyle::Fill => write!(f, "Fill")
+        }
+    }
+}
+
+///Font of a text annotation, kept along with its name
+#[derive(Clone)]
+pub struct TextFont {
+    pub name: String,
+    pub font: Font<'static>
+}
+
+impl Debug for TextFont {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TextFont({})", self.name)
+    }
+}
+
+impl PartialEq for TextFont {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+///Horizontal alignment of the lines of a text
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right
+}
+
+impl Display for TextAlign {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextAlign::Left => write!(f, "Left"),
+            TextAlign::Center => write!(f, "Center"),
+            TextAlign::Right => write!(f, "Right")
+        }
+    }
+}
+
+///Layout and decorations of a text annotation
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextStyle {
+    pub align: TextAlign,
+    ///Width in pixels beyond which lines are wrapped between words, 0 for no wrapping
+    pub max_width: u32,
+    ///Color of the box behind the text, whose alpha is the box opacity; None for no box
+    pub background: Option<Color>,
+    ///Space in pixels between the text and the sides of its box
+    pub padding: u32,
+    ///Color and width in pixels of the outline around the letters; None for no outline
+    pub outline: Option<(Color, u32)>
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            align: TextAlign::Left,
+            max_width: 0,
+            background: None,
+            padding: 0,
+            outline: None
+        }
+    }
+}
+
+///Kind of an annotation, along with the geometry needed to draw it.
+///Positions and sizes are in pixels of the image the annotation is placed on
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnnotationKind {
+    ///Free-hand line through the given points
+    Stroke { points: Vec<(i32, i32)>, thickness: i32 },
+    ///Free-hand highlighter line, blended over what is below it
+    Highlight { points: Vec<(i32, i32)>, thickness: i32 },
+    ///Free-hand line uncovering the background, erasing the annotations placed before it
+    Eraser { points: Vec<(i32, i32)>, thickness: i32 },
+    ///Rectangle with the given upper-left corner, size and corner radius; the thickness is ignored if filled
+    Rectangle { pos: (i32, i32), size: (u32, u32), radius: u32, filled: bool, thickness: i32 },
+    ///Ellipse inscribed in the rectangle with the given upper-left corner and size
+    Ellipse { pos: (i32, i32), size: (u32, u32), filled: bool, thickness: i32 },
+    ///Filled arrow inscribed in the rectangle with the given upper-left corner and size
+    Arrow { pos: (i32, i32), size: (u32, u32), pointing: Pointing },
+    ///Text, possibly on several lines, whose box has the given upper-left corner
+    Text { pos: (i32, i32), text: String, font: TextFont, font_size: f32, style: TextStyle },
+    ///Numbered circle with the given upper-left corner and diameter. Markers are numbered 1, 2, 3... in drawing order,
+    ///the number is updated by the image whenever the annotations change
+    Marker { pos: (i32, i32), size: u32, number: u32, font: TextFont },
+    ///Enlarged copy of the source rectangle, placed in the rectangle with the given upper-left corner and size,
+    ///framed by borders of the given thickness and joined to the source by a connector line.
+    ///Its area is the one of the copy, so selecting it moves and resizes the copy while the source stays in place
+    Magnifier { source_pos: (i32, i32), source_size: (u32, u32), pos: (i32, i32), size: (u32, u32), thickness: i32 },
+    ///Rounded rectangle with the given upper-left corner and size, holding the text sized to fill it.
+    ///If there is a tail, it is a speech bubble pointing to the tail tip, otherwise a label.
+    ///Its area is the one of the rectangle, so selecting it moves and resizes the rectangle while the tail tip stays in place
+    Bubble { pos: (i32, i32), size: (u32, u32), tail: Option<(i32, i32)>, text: String, font: TextFont },
+    ///Anti-aliased straight line from start to end at any angle, with optional arrow heads
+    Line { start: (i32, i32), end: (i32, i32), thickness: i32, head: ArrowHead },
+    ///Rectangle with the given upper-left corner and size whose content is made unreadable
+    Redaction { pos: (i32, i32), size: (u32, u32), style: RedactionStyle }
+}
+
+///Geometric transformation applied to the whole image, that annotations have to follow
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    ///Horizontal flip of an image with the given width
+    FlipH(u32),
+    ///Vertical flip of an image with the given height
+    FlipV(u32),
+    ///Clockwise rotation of an image with the given height
+    Rotate90(u32),
+    ///Counter-clockwise rotation of an image with the given width
+    Rotate270(u32)
+}
+
+impl Transform {
+    fn point(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        match *self {
+            Transform::FlipH(w) => (w as i32 - 1 - x, y),
+            Transform::FlipV(h) => (x, h as i32 - 1 - y),
+            Transform::Rotate90(h) => (h as i32 - 1 - y, x),
+            Transform::Rotate270(w) => (y, w as i32 - 1 - x)
+        }
+    }
+    fn rect(&self, (x, y): (i32, i32), (w, h): (u32, u32)) -> ((i32, i32), (u32, u32)) {
+        let (w, h) = (w as i32, h as i32);
+        match *self {
+            Transform::FlipH(iw) => ((iw as i32 - x - w, y), (w as u32, h as u32)),
+            Transform::FlipV(ih) => ((x, ih as i32 - y - h), (w as u32, h as u32)),
+            Transform::Rotate90(ih) => ((ih as i32 - y - h, x), (h as u32, w as u32)),
+            Transform::Rotate270(iw) => ((y, iw as i32 - x - w), (h as u32, w as u32))
+        }
+    }
+    fn pointing(&self, pointing: Pointing) -> Pointing {
+        match (*self, pointing) {
+            (Transform::FlipH(_), Pointing::Left) => Pointing::Right,
+            (Transform::FlipH(_), Pointing::Right) => Pointing::Left,
+            (Transform::FlipV(_), Pointing::Up) => Pointing::Down,
+            (Transform::FlipV(_), Pointing::Down) => Pointing::Up,
+            (Transform::Rotate90(_), Pointing::Up) => Pointing::Right,
+            (Transform::Rotate90(_), Pointing::Right) => Pointing::Down,
+            (Transform::Rotate90(_), Pointing::Down) => Pointing::Left,
+            (Transform::Rotate90(_), Pointing::Left) => Pointing::Up,
+            (Transform::Rotate270(_), Pointing::Up) => Pointing::Left,
+            (Transform::Rotate270(_), Pointing::Left) => Pointing::Down,
+            (Transform::Rotate270(_), Pointing::Down) => Pointing::Right,
+            (Transform::Rotate270(_), Pointing::Right) => Pointing::Up,
+            (_, p) => p
+        }
+    }
+}
+
+///Object placed over an image, kept apart from its pixels so that it can be edited until the image is exported
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub kind: AnnotationKind,
+    pub color: Color
+}
+
+impl Annotation {
+    pub fn new(kind: AnnotationKind, color: Color) -> Self {
+        Self {
+            kind,
+            color
+        }
+    }
+    ///Returns the name of the annotation, as shown in the history of the edits
+    pub fn name(&self) -> &'static str {
+        match self.kind {
+            AnnotationKind::Stroke { .. } => "Drawing",
+            AnnotationKind::Highlight { .. } => "Highlight",
+            AnnotationKind::Eraser { .. } => "Eraser",
+            AnnotationKind::Rectangle { .. } => "Rectangle",
+            AnnotationKind::Ellipse { .. } => "Ellipse",
+            AnnotationKind::Arrow { .. } => "Arrow",
+            AnnotationKind::Text { .. } => "Text",
+            AnnotationKind::Redaction { .. } => "Redaction",
+            AnnotationKind::Line { head: ArrowHead::None, .. } => "Line",
+            AnnotationKind::Line { .. } => "Arrow",
+            AnnotationKind::Marker { .. } => "Step marker",
+            AnnotationKind::Magnifier { .. } => "Magnifier",
+            AnnotationKind::Bubble { tail: None, .. } => "Label",
+            AnnotationKind::Bubble { .. } => "Speech bubble"
+        }
+    }
+    ///Returns the approximate number of bytes used to store the annotation
+    pub fn memory_size(&self) -> usize {
+        let data = match &self.kind {
+            AnnotationKind::Stroke { points, .. } |
+            AnnotationKind::Highlight { points, .. } |
+            AnnotationKind::Eraser { points, .. } => points.len() * std::mem::size_of::<(i32, i32)>(),
+            AnnotationKind::Text { text, font, .. } |
+            AnnotationKind::Bubble { text, font, .. } => text.len() + font.name.len(),
+            AnnotationKind::Marker { font, .. } => font.name.len(),
+            _ => 0
+        };
+        std::mem::size_of::<Annotation>() + data
+    }
+    ///Returns the upper-left corner and the size of the smallest rectangle containing the annotation
+    pub fn bounds(&self) -> ((i32, i32), (u32, u32)) {
+        match &self.kind {
+            AnnotationKind::Stroke { points, thickness } |
+            AnnotationKind::Highlight { points, thickness } |
+            AnnotationKind::Eraser { points, thickness } => {
+                let r = thickness / 2;
+                let min_x = points.iter().map(|p| p.0).min().unwrap_or(0) - r;
+                let min_y = points.iter().map(|p| p.1).min().unwrap_or(0) - r;
+                let max_x = points.iter().map(|p| p.0).max().unwrap_or(0) + r;
+                let max_y = points.iter().map(|p| p.1).max().unwrap_or(0) + r;
+                ((min_x, min_y), ((max_x - min_x) as u32 + 1, (max_y - min_y) as u32 + 1))
+            }
+            AnnotationKind::Rectangle { pos, size, .. } |
+            AnnotationKind::Ellipse { pos, size, .. } |
+            AnnotationKind::Arrow { pos, size, .. } |
+            AnnotationKind::Redaction { pos, size, .. } |
+            AnnotationKind::Magnifier { pos, size, .. } |
+            AnnotationKind::Bubble { pos, size, .. } => (*pos, *size),
+            AnnotationKind::Marker { pos, size, .. } => (*pos, (*size, *size)),
+            AnnotationKind::Text { pos, text, font, font_size, style } => {
+                let (w, h) = text_size(text, &font.font, *font_size, style);
+                (*pos, (w.max(1), h.max(1)))
+            }
+            AnnotationKind::Line { start, end, thickness, head } => {
+                let r = match head {
+                    ArrowHead::None => thickness / 2 + 1,
+                    _ => head_size(*thickness).1.ceil() as i32 + 1
+                };
+                let (min_x, min_y) = (start.0.min(end.0) - r, start.1.min(end.1) - r);
+                let (max_x, max_y) = (start.0.max(end.0) + r, start.1.max(end.1) + r);
+                ((min_x, min_y), ((max_x - min_x) as u32 + 1, (max_y - min_y) as u32 + 1))
+            }
+        }
+    }
+    ///Moves the annotation by the given offset
+    pub fn translate(&mut self, dx: i32, dy: i32) {
+        match &mut self.kind {
+            AnnotationKind::Stroke { points, .. } |
+            AnnotationKind::Highlight { points, .. } |
+            AnnotationKind::Eraser { points, .. } => {
+                for p in points.iter_mut() {
+                    *p = (p.0 + dx, p.1 + dy);
+                }
+            }
+            AnnotationKind::Rectangle { pos, .. } |
+            AnnotationKind::Ellipse { pos, .. } |
+            AnnotationKind::Arrow { pos, .. } |
+            AnnotationKind::Text { pos, .. } |
+            AnnotationKind::Redaction { pos, .. } |
+            AnnotationKind::Marker { pos, .. } => {
+                *pos = (pos.0 + dx, pos.1 + dy);
+            }
+            AnnotationKind::Line { start, end, .. } => {
+                *start = (start.0 + dx, start.1 + dy);
+                *end = (end.0 + dx, end.1 + dy);
+            }
+            AnnotationKind::Magnifier { source_pos, pos, .. } => {
+                *source_pos = (source_pos.0 + dx, source_pos.1 + dy);
+                *pos = (pos.0 + dx, pos.1 + dy);
+            }
+            AnnotationKind::Bubble { pos, tail, .. } => {
+                *pos = (pos.0 + dx, pos.1 + dy);
+                *tail = tail.map(|t| (t.0 + dx, t.1 + dy));
+            }
+        }
+    }
+    ///Moves and scales the annotation so that the area `from` (upper-left corner and size) is mapped onto the area `to`
+    pub fn fit(&mut self, from: ((i32, i32), (u32, u32)), to: ((i32, i32), (u32, u32))) {
+        let sx = to.1.0 as f32 / u32::max(from.1.0, 1) as f32;
+        let sy = to.1.1 as f32 / u32::max(from.1.1, 1) as f32;
+        let map = |(x, y): (i32, i32)| {
+            (to.0.0 + ((x - from.0.0) as f32 * sx).round() as i32, to.0.1 + ((y - from.0.1) as f32 * sy).round() as i32)
+        };
+        match &mut self.kind {
+            AnnotationKind::Stroke { points, .. } |
+            AnnotationKind::Highlight { points, .. } |
+            AnnotationKind::Eraser { points, .. } => {
+                for p in points.iter_mut() {
+                    *p = map(*p);
+                }
+            }
+            AnnotationKind::Rectangle { pos, size, .. } |
+            AnnotationKind::Ellipse { pos, size, .. } |
+            AnnotationKind::Arrow { pos, size, .. } |
+            AnnotationKind::Redaction { pos, size, .. } |
+            AnnotationKind::Magnifier { pos, size, .. } |
+            AnnotationKind::Bubble { pos, size, .. } => {
+                let end = map((pos.0 + size.0 as i32, pos.1 + size.1 as i32));
+                *pos = map(*pos);
+                *size = (i32::max(end.0 - pos.0, 1) as u32, i32::max(end.1 - pos.1, 1) as u32);
+            }
+            AnnotationKind::Text { pos, font_size, style, .. } => {
+                *pos = map(*pos);
+                *font_size *= sy;
+                style.max_width = (style.max_width as f32 * sx).round() as u32;
+            }
+            AnnotationKind::Marker { pos, size, .. } => {
+                //markers stay round, fitting the smaller side of the area
+                let end = map((pos.0 + *size as i32, pos.1 + *size as i32));
+                *pos = map(*pos);
+                *size = i32::max(i32::min(end.0 - pos.0, end.1 - pos.1), 1) as u32;
+            }
+            AnnotationKind::Line { start, end, .. } => {
+                *start = map(*start);
+                *end = map(*end);
+            }
+        }
+    }
+    ///Returns false if the annotation cannot follow flips and rotations of the image (text cannot be flipped or rotated)
+    pub fn can_transform(&self) -> bool {
+        !matches!(self.kind, AnnotationKind::Text { .. })
+    }
+    ///Applies to the annotation the transformation applied to the image. Text annotations are left unchanged
+    pub fn transform(&mut self, t: Transform) {
+        match &mut self.kind {
+            AnnotationKind::Stroke { points, .. } |
+            AnnotationKind::Highlight { points, .. } |
+            AnnotationKind::Eraser { points, .. } => {
+                for p in points.iter_mut() {
+                    *p = t.point(*p);
+                }
+            }
+            AnnotationKind::Rectangle { pos, size, .. } |
+            AnnotationKind::Ellipse { pos, size, .. } |
+            AnnotationKind::Redaction { pos, size, .. } => {
+                (*pos, *size) = t.rect(*pos, *size);
+            }
+            AnnotationKind::Arrow { pos, size, pointing } => {
+                (*pos, *size) = t.rect(*pos, *size);
+                *pointing = t.pointing(*pointing);
+            }
+            AnnotationKind::Line { start, end, .. } => {
+                *start = t.point(*start);
+                *end = t.point(*end);
+            }
+            AnnotationKind::Marker { pos, size, .. } => {
+                (*pos, _) = t.rect(*pos, (*size, *size));
+            }
+            AnnotationKind::Magnifier { source_pos, source_size, pos, size, .. } => {
+                (*source_pos, *source_size) = t.rect(*source_pos, *source_size);
+                (*pos, *size) = t.rect(*pos, *size);
+            }
+            AnnotationKind::Bubble { pos, size, tail, .. } => {
+                (*pos, *size) = t.rect(*pos, *size);
+                *tail = tail.map(|p| t.point(p));
+            }
+            AnnotationKind::Text { .. } => {}
+        }
+    }
+    ///Draws the annotation over the image. The background is what the eraser uncovers: the image without
+    ///any annotation but the redactions; canvas is a layer of the same size, used by the shapes for their size only
+    pub fn draw(&self, image: &mut DynamicImage, background: &DynamicImage, canvas: &mut Layer) {
+        let color = &self.color;
+        match &self.kind {
+            //the line is drawn on a transparent layer first, so that its overlapping parts are blended only once
+            AnnotationKind::Stroke { points, thickness } | AnnotationKind::Highlight { points, thickness } => {
+                let mut layer = Layer::new(DynamicImage::ImageRgba8(RgbaImage::new(image.width(), image.height())), LayerType::BaseImage);
+                replay(points, |prev, p| Image::draw_point(&mut layer, prev, p, *thickness, color));
+                composite(image, &layer.layer);
+            }
+            AnnotationKind::Eraser { points, thickness } => {
+                let mut layer = Layer::new(std::mem::replace(image, DynamicImage::new_rgba8(0, 0)), LayerType::BaseImage);
+                replay(points, |prev, p| Image::rubber(&mut layer, prev, p, *thickness));
+                let mut erased = background.clone();
+                composite(&mut erased, &layer.layer);
+                *image = erased;
+            }
+            AnnotationKind::Rectangle { pos, size, radius, filled, thickness } => {
+                let mut shape = Layer::new(DynamicImage::new_rgba8(0, 0), LayerType::BaseImage);
+                let center = (pos.0 + size.0 as i32 / 2, pos.1 + size.1 as i32 / 2);
+                let size = (size.0 as i32, size.1 as i32);
+                if *filled {
+                    Image::draw_filled_rectangle(&mut shape, canvas, center, size, *radius, color);
+                } else {
+                    Image::draw_empty_rectangle(&mut shape, canvas, center, size, *radius, color, *thickness);
+                }
+                composite(image, &shape.layer);
+            }
+            AnnotationKind::Ellipse { pos, size, filled, thickness } => {
+                let mut shape = Layer::new(DynamicImage::new_rgba8(0, 0), LayerType::BaseImage);
+                let center = (pos.0 + size.0 as i32 / 2, pos.1 + size.1 as i32 / 2);
+                let size = (size.0 as i32, size.1 as i32);
+                if *filled {
+                    Image::draw_filled_ellipse(&mut shape, canvas, center, size, color);
+                } else {
+                    Image::draw_empty_ellipse(&mut shape, canvas, center, size, color, *thickness);
+                }
+                composite(image, &shape.layer);
+            }
+            AnnotationKind::Arrow { pos, size, pointing } => {
+                let mut shape = Layer::new(DynamicImage::new_rgba8(0, 0), LayerType::BaseImage);
+                let center = (pos.0 + size.0 as i32 / 2, pos.1 + size.1 as i32 / 2);
+                let size = (size.0 as i32, size.1 as i32);
+                match pointing {
+                    Pointing::Up => Image::draw_filled_up_arrow(&mut shape, canvas, center, size, color),
+                    Pointing::Down => Image::draw_filled_down_arrow(&mut shape, canvas, center, size, color),
+                    Pointing::Left => Image::draw_filled_left_arrow(&mut shape, canvas, center, size, color),
+                    Pointing::Right => Image::draw_filled_right_arrow(&mut shape, canvas, center, size, color)
+                }
+                composite(image, &shape.layer);
+            }
+            AnnotationKind::Text { pos, text, font, font_size, style } => {
+                with_rgba8(image, |rgba| draw_text(rgba, *pos, text, &font.font, *font_size, style, color.color));
+            }
+            AnnotationKind::Redaction { pos, size, style } => {
+                if let Some(((x, y), patch)) = redact(image, *pos, *size, *style, color) {
+                    replace(image, &patch, x as i64, y as i64);
+                }
+            }
+            AnnotationKind::Line { start, end, thickness, head } => {
+                with_rgba8(image, |rgba| draw_line(rgba, *start, *end, *thickness, *head, color.color));
+            }
+            AnnotationKind::Marker { pos, size, number, font } => {
+                with_rgba8(image, |rgba| draw_marker(rgba, *pos, *size, *number, &font.font, color.color));
+            }
+            AnnotationKind::Magnifier { source_pos, source_size, pos, size, thickness } => {
+                with_rgba8(image, |rgba| draw_magnifier(rgba, (*source_pos, *source_size), (*pos, *size), *thickness, color.color));
+            }
+            AnnotationKind::Bubble { pos, size, tail, text, font } => {
+                with_rgba8(image, |rgba| draw_bubble(rgba, *pos, *size, *tail, text, &font.font, color.color));
+            }
+        }
+    }
+}
+
+///Draws a disc of the given color with the number centered on it, in white or black depending on which one
+///stands out more from the color
+fn draw_marker(image: &mut RgbaImage, pos: (i32, i32), size: u32, number: u32, font: &Font<'static>, color: Rgba<u8>) {
+    let radius = size as f32 / 2.0;
+    let center = (pos.0 as f32 + radius, pos.1 as f32 + radius);
+    draw_disc(image, center, radius, color);
+
+    let text = number.to_string();
+    //the digits take at most 60% of the height and 70% of the width of the disc
+    let mut scale = Scale::uniform(size as f32 * 0.6);
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (i32::MAX, i32::MAX, i32::MIN, i32::MIN);
+    for _ in 0..2 {
+        (min_x, min_y, max_x, max_y) = (i32::MAX, i32::MAX, i32::MIN, i32::MIN);
+        for glyph in font.layout(&text, scale, rusttype::point(0.0, font.v_metrics(scale).ascent)) {
+            if let Some(bb) = glyph.pixel_bounding_box() {
+                (min_x, min_y) = (min_x.min(bb.min.x), min_y.min(bb.min.y));
+                (max_x, max_y) = (max_x.max(bb.max.x), max_y.max(bb.max.y));
+            }
+        }
+        let width = (max_x - min_x) as f32;
+        if width <= size as f32 * 0.7 {
+            break;
+        }
+        scale = Scale::uniform(scale.y * size as f32 * 0.7 / width);
+    }
+    if min_x > max_x {
+        return;
+    }
+
+    let x = (center.0 - (min_x + max_x) as f32 / 2.0).round() as i32;
+    let y = (center.1 - (min_y + max_y) as f32 / 2.0).round() as i32;
+    //the digits are as opaque as the disc
+    let mut digits = contrast_color(color);
+    digits.0[3] = color.0[3];
+    for glyph in font.layout(&text, scale, rusttype::point(x as f32, y as f32 + font.v_metrics(scale).ascent)) {
+        if let Some(bb) = glyph.pixel_bounding_box() {
+            glyph.draw(|gx, gy, v| {
+                let (px, py) = (bb.min.x + gx as i32, bb.min.y + gy as i32);
+                if px >= 0 && py >= 0 && px < image.width() as i32 && py < image.height() as i32 {
+                    let pixel = image.get_pixel_mut(px as u32, py as u32);
+                    *pixel = blend(*pixel, digits, v);
+                }
+            });
+        }
+    }
+}
+
+///Returns white or black, depending on which one stands out more over the given color
+fn contrast_color(color: Rgba<u8>) -> Rgba<u8> {
+    let luminance = 0.299 * color.0[0] as f32 + 0.587 * color.0[1] as f32 + 0.114 * color.0[2] as f32;
+    if luminance > 160.0 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) }
+}
+
+///Returns the corners of the tail of a bubble with the given upper-left corner, size and corner radius pointing to the tip,
+///or None if the tip is inside the bubble. The base of the tail lies just inside the side facing the tip
+fn bubble_tail(pos: (i32, i32), size: (u32, u32), radius: f32, tip: (i32, i32)) -> Option<[(f32, f32); 3]> {
+    let (x, y, w, h) = (pos.0 as f32, pos.1 as f32, size.0 as f32, size.1 as f32);
+    let (tx, ty) = (tip.0 as f32, tip.1 as f32);
+    if tx >= x && tx <= x + w && ty >= y && ty <= y + h {
+        return None;
+    }
+    let (dx, dy) = ((tx - x - w / 2.0) / w.max(1.0), (ty - y - h / 2.0) / h.max(1.0));
+    //the base slides along the side, as close as possible to the tip without reaching the rounded corners
+    let base = |length: f32, start: f32, tip: f32| {
+        let half = (f32::min(length, f32::min(w, h)) / 3.0).clamp(5.0, 30.0).min((length / 2.0 - radius).max(1.0));
+        let center = tip.clamp(start + radius + half, (start + length - radius - half).max(start + radius + half));
+        (center - half, center + half)
+    };
+    let inset = 2.0;
+    Some(if dx.abs() >= dy.abs() {
+        let side = if dx < 0.0 { x + inset } else { x + w - inset };
+        let (a, b) = base(h, y, ty);
+        [(side, a), (side, b), (tx, ty)]
+    } else {
+        let side = if dy < 0.0 { y + inset } else { y + h - inset };
+        let (a, b) = base(w, x, tx);
+        [(a, side), (b, side), (tx, ty)]
+    })
+}
+
+///Returns the corner radius of a bubble of the given size
+fn bubble_radius(size: (u32, u32)) -> f32 {
+    f32::min(u32::min(size.0, size.1) as f32 / 4.0, 20.0)
+}
+
+///Draws a rounded rectangle of the given color, with a tail pointing to the tip if any, and the text centered in it,
+///in the largest font size that fits, in white or black depending on which one stands out more
+fn draw_bubble(image: &mut RgbaImage, pos: (i32, i32), size: (u32, u32), tail: Option<(i32, i32)>, text: &str, font: &Font<'static>, color: Rgba<u8>) {
+    let radius = bubble_radius(size);
+    let (x, y, w, h) = (pos.0 as f32, pos.1 as f32, size.0 as f32, size.1 as f32);
+    let tail = tail.and_then(|t| bubble_tail(pos, size, radius, t));
+    let mut points = vec![(x, y), (x + w, y + h)];
+    if let Some(corners) = tail {
+        points.extend_from_slice(&corners);
+    }
+    let mut mask = Mask::new(&points, 2.0, image);
+    mask.add_rounded_rect((x, y), (w, h), radius);
+    if let Some(corners) = tail {
+        mask.add_convex(&corners);
+    }
+    mask.apply(image, color);
+
+    //the text keeps clear of the rounded corners
+    let padding = (radius / 2.0).ceil() as u32 + 4;
+    let inner = (size.0.saturating_sub(2 * padding), size.1.saturating_sub(2 * padding));
+    let style = TextStyle { align: TextAlign::Center, max_width: inner.0, ..TextStyle::default() };
+    let font_size = fit_font_size(text, font, inner, &style);
+    let (tw, th) = text_size(text, font, font_size, &style);
+    let text_pos = (pos.0 + (size.0 as i32 - tw as i32) / 2, pos.1 + (size.1 as i32 - th as i32) / 2);
+    draw_text(image, text_pos, text, font, font_size, &style, contrast_color(color));
+}
+
+///Returns the upper-left corner of an enlarged copy of the given size of the source rectangle, placed beside it.
+///The right, left, lower and upper sides are tried in this order, taking the first one where the copy fits in the image
+pub fn magnifier_position(source: ((i32, i32), (u32, u32)), size: (u32, u32), image_size: (u32, u32)) -> (i32, i32) {
+    let ((sx, sy), (sw, sh)) = source;
+    let (sw, sh, w, h) = (sw as i32, sh as i32, size.0 as i32, size.1 as i32);
+    let (iw, ih) = (image_size.0 as i32, image_size.1 as i32);
+    let gap = i32::max(20, i32::min(sw, sh) / 2);
+    let (center_x, center_y) = (sx + sw / 2 - w / 2, sy + sh / 2 - h / 2);
+    let candidates = [
+        (sx + sw + gap, center_y),
+        (sx - gap - w, center_y),
+        (center_x, sy + sh + gap),
+        (center_x, sy - gap - h)
+    ];
+    let clamp = |(x, y): (i32, i32)| (x.clamp(0, i32::max(iw - w, 0)), y.clamp(0, i32::max(ih - h, 0)));
+    for (x, y) in candidates {
+        //the copy can slide along the side it is placed on, but not onto the source
+        let (cx, cy) = clamp((x, y));
+        let overlaps = cx < sx + sw && cx + w > sx && cy < sy + sh && cy + h > sy;
+        if x >= 0 && y + h > 0 && x + w <= iw && y < ih && !overlaps && (cx == x || cy == y) {
+            return (cx, cy);
+        }
+    }
+    clamp(candidates[0])
+}
+
+///Draws over the image the enlarged copy of the source rectangle in the target rectangle, both framed by a border,
+///along with a connector line between them. Each rectangle is given by its upper-left corner and size
+fn draw_magnifier(image: &mut RgbaImage, source: ((i32, i32), (u32, u32)), target: ((i32, i32), (u32, u32)), thickness: i32, color: Rgba<u8>) {
+    let ((sx, sy), (sw, sh)) = source;
+    let ((tx, ty), (tw, th)) = target;
+    let x0 = sx.clamp(0, image.width() as i32);
+    let y0 = sy.clamp(0, image.height() as i32);
+    let x1 = (sx + sw as i32).clamp(x0, image.width() as i32);
+    let y1 = (sy + sh as i32).clamp(y0, image.height() as i32);
+    if x1 == x0 || y1 == y0 || tw == 0 || th == 0 {
+        return;
+    }
+    //the part of the source outside the image is left out, the copy shrinks accordingly
+    let zoom = (tw as f32 / sw.max(1) as f32, th as f32 / sh.max(1) as f32);
+    let copy_x = tx + ((x0 - sx) as f32 * zoom.0).round() as i32;
+    let copy_y = ty + ((y0 - sy) as f32 * zoom.1).round() as i32;
+    let copy_w = u32::max(((x1 - x0) as f32 * zoom.0).round() as u32, 1);
+    let copy_h = u32::max(((y1 - y0) as f32 * zoom.1).round() as u32, 1);
+    let copy = image::imageops::resize(&image::imageops::crop_imm(image, x0 as u32, y0 as u32, (x1 - x0) as u32, (y1 - y0) as u32).to_image(),
+                                       copy_w, copy_h, FilterType::Lanczos3);
+
+    //the connector goes between the borders of the rectangles, along the line joining their centers
+    let (scx, scy) = (sx as f32 + sw as f32 / 2.0, sy as f32 + sh as f32 / 2.0);
+    let (tcx, tcy) = (tx as f32 + tw as f32 / 2.0, ty as f32 + th as f32 / 2.0);
+    let (dx, dy) = (tcx - scx, tcy - scy);
+    let exit = |w: u32, h: u32| f32::min(w as f32 / 2.0 / dx.abs().max(f32::EPSILON), h as f32 / 2.0 / dy.abs().max(f32::EPSILON));
+    let (ts, tt) = (exit(sw, sh), exit(tw, th));
+
+    replace(image, &copy, copy_x as i64, copy_y as i64);
+    //the borders and the connector are drawn together, so that they are blended only once where they meet
+    let width = thickness.max(1) as f32;
+    let corners = |((x, y), (w, h)): ((i32, i32), (u32, u32))| {
+        let (x, y, w, h) = (x as f32, y as f32, w as f32, h as f32);
+        [(x, y), (x + w, y), (x + w, y + h), (x, y + h)]
+    };
+    let (source, target) = (corners(source), corners(target));
+    let mut mask = Mask::new(&[source[0], source[2], target[0], target[2]], width + 1.0, image);
+    mask.add_stroke(&source, true, width, Join::Miter);
+    mask.add_stroke(&target, true, width, Join::Miter);
+    if ts + tt < 1.0 {
+        mask.add_segment((scx + dx * ts, scy + dy * ts), (tcx - dx * tt, tcy - dy * tt), width);
+    }
+    mask.apply(image, color);
+}
+
+///Returns the part of the image inside the area with the given upper-left corner and size, made unreadable
+///with the given style, along with its position. The area is limited to the image, None is returned if nothing is left.
+///Each block of a pixelated area only keeps the mean of its pixels, so the original content cannot be recovered
+pub fn redact(image: &DynamicImage, pos: (i32, i32), size: (u32, u32), style: RedactionStyle, color: &Color) -> Option<((u32, u32), RgbaImage)> {
+    let x0 = pos.0.clamp(0, image.width() as i32);
+    let y0 = pos.1.clamp(0, image.height() as i32);
+    let x1 = (pos.0 + size.0 as i32).clamp(x0, image.width() as i32);
+    let y1 = (pos.1 + size.1 as i32).clamp(y0, image.height() as i32);
+    if x1 == x0 || y1 == y0 {
+        return None;
+    }
+    let (w, h) = ((x1 - x0) as u32, (y1 - y0) as u32);
+    let mut patch = image.crop_imm(x0 as u32, y0 as u32, w, h).to_rgba8();
+
+    match style {
+        RedactionStyle::Pixelate => {
+            let block = u32::max(MIN_PIXEL_BLOCK, u32::min(size.0, size.1) / 8);
+            for by in (0..h).step_by(block as usize) {
+                for bx in (0..w).step_by(block as usize) {
+                    let (bw, bh) = (u32::min(block, w - bx), u32::min(block, h - by));
+                    let mut sum = [0u64; 4];
+                    for y in by..by + bh {
+                        for x in bx..bx + bw {
+                            let p = patch.get_pixel(x, y).0;
+                            for c in 0..4 {
+                                sum[c] += p[c] as u64;
+                            }
+                        }
+                    }
+                    let n = (bw * bh) as u64;
+                    let mean = Rgba([(sum[0] / n) as u8, (sum[1] / n) as u8, (sum[2] / n) as u8, (sum[3] / n) as u8]);
+                    for y in by..by + bh {
+                        for x in bx..bx + bw {
+                            patch.put_pixel(x, y, mean);
+                        }
+                    }
+                }
+            }
+        }
+        RedactionStyle::Blur => {
+            let sigma = f32::max(4.0, u32::min(size.0, size.1) as f32 / 10.0);
+            patch = gaussian_blur_f32(&patch, sigma);
+        }
+        //the fill is always opaque, otherwise the content would show through
+        RedactionStyle::Fill => {
+            patch = RgbaImage::from_pixel(w, h, Rgba([color.color.0[0], color.color.0[1], color.color.0[2], 255]));
+        }
+    }
+
+    Some(((x0 as u32, y0 as u32), patch))
+}
+
+///Draws a free-hand line through the given points, the same way it was drawn while the user was dragging:
+///the function draws each point, joined to the previous one if any, and returns it
+fn replay<F: FnMut(Option<(i32, i32)>, (i32, i32)) -> (i32, i32)>(points: &[(i32, i32)], mut step: F) {
+    let mut prev = None;
+    for p in points {
+        prev = Some(step(prev, *p));
+    }
+}
@@ -0,0 +1,123 @@
+use std::borrow::Cow;
+use image::{DynamicImage, Rgba, RgbaImage};
+
+///Blends the color over the pixel with source-over compositing, the alpha of the color being scaled by the coverage.
+///Both colors are not premultiplied, and neither is the result
+pub fn blend(dst: Rgba<u8>, src: Rgba<u8>, coverage: f32) -> Rgba<u8> {
+    let sa = src.0[3] as f32 / 255.0 * coverage;
+    let da = dst.0[3] as f32 / 255.0;
+    let oa = sa + da * (1.0 - sa);
+    if oa <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    let channel = |i: usize| ((src.0[i] as f32 * sa + dst.0[i] as f32 * da * (1.0 - sa)) / oa).round() as u8;
+    Rgba([channel(0), channel(1), channel(2), (oa * 255.0).round() as u8])
+}
+
+///Calls the function on the RGBA buffer of the image, converting the image to RGBA first if needed
+pub fn with_rgba8<F: FnOnce(&mut RgbaImage)>(image: &mut DynamicImage, f: F) {
+    match image.as_mut_rgba8() {
+        Some(rgba) => f(rgba),
+        None => {
+            let mut rgba = image.to_rgba8();
+            f(&mut rgba);
+            *image = DynamicImage::ImageRgba8(rgba);
+        }
+    }
+}
+
+///Blends every pixel of the top image over the pixel in the same position of the bottom one.
+///The parts of the top image outside the bottom one are ignored
+pub fn composite(bottom: &mut DynamicImage, top: &DynamicImage) {
+    let top = match top.as_rgba8() {
+        Some(rgba) => Cow::Borrowed(rgba),
+        None => Cow::Owned(top.to_rgba8())
+    };
+    with_rgba8(bottom, |bottom| {
+        let width = u32::min(bottom.width(), top.width());
+        let height = u32::min(bottom.height(), top.height());
+        for y in 0..height {
+            for x in 0..width {
+                let src = *top.get_pixel(x, y);
+                if src.0[3] > 0 {
+                    let pixel = bottom.get_pixel_mut(x, y);
+                    *pixel = blend(*pixel, src, 1.0);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_proc::annotation::{Annotation, AnnotationKind};
+    use crate::image_proc::colors::Color;
+    use crate::image_proc::layer::{Layer, LayerType};
+
+    fn render(background: Rgba<u8>, annotation: Annotation) -> RgbaImage {
+        let background = DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 40, background));
+        let mut image = background.clone();
+        let mut canvas = Layer::new(background.clone(), LayerType::BaseImage);
+        annotation.draw(&mut image, &background, &mut canvas);
+        image.to_rgba8()
+    }
+
+    #[test]
+    fn opaque_color_replaces_pixel() {
+        assert_eq!(blend(Rgba([10, 20, 30, 255]), Rgba([200, 100, 50, 255]), 1.0), Rgba([200, 100, 50, 255]));
+    }
+
+    #[test]
+    fn no_coverage_keeps_pixel() {
+        assert_eq!(blend(Rgba([10, 20, 30, 255]), Rgba([200, 100, 50, 255]), 0.0), Rgba([10, 20, 30, 255]));
+        assert_eq!(blend(Rgba([10, 20, 30, 255]), Rgba([200, 100, 50, 0]), 1.0), Rgba([10, 20, 30, 255]));
+    }
+
+    #[test]
+    fn translucent_color_over_opaque_pixel() {
+        assert_eq!(blend(Rgba([0, 0, 255, 255]), Rgba([255, 0, 0, 128]), 1.0), Rgba([128, 0, 127, 255]));
+        assert_eq!(blend(Rgba([255, 255, 255, 255]), Rgba([0, 0, 0, 64]), 1.0), Rgba([191, 191, 191, 255]));
+    }
+
+    #[test]
+    fn coverage_scales_alpha() {
+        assert_eq!(blend(Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255]), 0.5), Rgba([128, 128, 128, 255]));
+    }
+
+    #[test]
+    fn translucent_color_over_transparent_pixel() {
+        assert_eq!(blend(Rgba([0, 0, 0, 0]), Rgba([255, 0, 0, 128]), 1.0), Rgba([255, 0, 0, 128]));
+        assert_eq!(blend(Rgba([0, 0, 255, 128]), Rgba([255, 0, 0, 128]), 1.0), Rgba([170, 0, 85, 192]));
+    }
+
+    #[test]
+    fn composite_blends_only_visible_pixels() {
+        let mut bottom = DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 1, Rgba([0, 0, 255, 255])));
+        let mut top = RgbaImage::new(3, 1);
+        top.put_pixel(1, 0, Rgba([255, 0, 0, 128]));
+        top.put_pixel(2, 0, Rgba([255, 0, 0, 255]));
+        composite(&mut bottom, &DynamicImage::ImageRgba8(top));
+        let bottom = bottom.to_rgba8();
+        assert_eq!(*bottom.get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+        assert_eq!(*bottom.get_pixel(1, 0), Rgba([128, 0, 127, 255]));
+    }
+
+    #[test]
+    fn translucent_rectangle_is_blended() {
+        let red = Color { color: Rgba([255, 0, 0, 128]) };
+        let rectangle = Annotation::new(AnnotationKind::Rectangle { pos: (10, 10), size: (20, 20), radius: 0, filled: true, thickness: 0 }, red);
+        let image = render(Rgba([255, 255, 255, 255]), rectangle);
+        assert_eq!(*image.get_pixel(20, 20), Rgba([255, 127, 127, 255]));
+        assert_eq!(*image.get_pixel(2, 2), Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn translucent_stroke_is_blended() {
+        let blue = Color { color: Rgba([0, 0, 255, 128]) };
+        let stroke = Annotation::new(AnnotationKind::Stroke { points: vec![(5, 20), (35, 20)], thickness: 9 }, blue);
+        let image = render(Rgba([255, 255, 255, 255]), stroke);
+        assert_eq!(*image.get_pixel(20, 20), Rgba([127, 127, 255, 255]));
+        assert_eq!(*image.get_pixel(20, 2), Rgba([255, 255, 255, 255]));
+    }
+}
\ No newline at end of file
@@ -52,11 +52,8 @@ impl BlurArea {
 
         blur
     }
-    pub fn save(&self) -> DynamicImage {
-        let mut blur = self.blur.clone();
-        let image = self.image.clone().crop(self.crop_position.0, self.crop_position.1, self.crop_size.0, self.crop_size.1);
-        image::imageops::overlay(&mut blur, &image, self.crop_position.0.into(), self.crop_position.1.into());
-        blur
+    pub fn show_background(&self) -> DynamicImage {
+        self.blur.clone()
     }
     pub fn resize(&mut self, new_crop_position: (u32, u32), new_crop_size: (u32, u32)) {
         self.crop_position = new_crop_position;
@@ -1,11 +1,17 @@
-use image::{DynamicImage, imageops::overlay, RgbaImage};
+use image::{DynamicImage, RgbaImage};
 
+use super::annotation::Annotation;
+use super::blend::composite;
 use super::colors::Color;
 
 #[derive(Clone)]
 pub struct Layer {
     pub layer: DynamicImage,
-    pub layer_type: LayerType
+    pub layer_type: LayerType,
+    //points drawn on the layer by free-hand drawing, highlighting or erasing
+    pub points: Vec<(i32,i32)>,
+    //shape drawn on the layer, if any
+    pub annotation: Option<Annotation>
 }
 
 #[derive(Clone, PartialEq, Eq)]
@@ -19,34 +25,35 @@ pub enum LayerType {
 impl Layer {
     pub fn new(layer: DynamicImage, layer_type: LayerType) -> Self {
         Self {
-            layer: layer,
-            layer_type: layer_type
+            layer,
+            layer_type,
+            points: Vec::new(),
+            annotation: None
         }
     }
-    pub fn show(&self) -> DynamicImage{
-        self.layer.clone()
-    }
-
-    pub fn show_rubber(&self, base: &Layer) -> DynamicImage {
+    ///Returns the image of the base layer with this layer composited over it
+    pub fn show_over(&self, base: &Layer) -> DynamicImage {
         let mut image = base.layer.clone();
-        overlay(&mut image, &self.layer, 0, 0);
+        composite(&mut image, &self.layer);
         image
     }
 
-    pub fn show_higlight(&self, base: &Layer) -> DynamicImage {
-        let mut image = base.layer.clone();
-        overlay(&mut image, &self.layer, 0, 0);
+    pub fn show_shape(&self, base: &Layer) -> DynamicImage {
+        let mut image = self.show_over(base);
+        let (pos, size) = self.get_pos_size().unwrap();
+
+        Layer::draw_handles(&mut image, pos, size);
+
         image
     }
 
-    pub fn show_shape(&self, base: &Layer) -> DynamicImage {
-        let mut image = base.layer.clone();
+    ///Draws over the image the frame and the corner handles of the area with the given upper-left corner and size
+    pub fn draw_handles(image: &mut DynamicImage, pos: (u32,u32), size: (u32,u32)) {
         let mut area = RgbaImage::new(image.width(), image.height());
-        let (pos, size) = self.get_pos_size().unwrap();
         let blue = Color::new(0, 255, 255, 1.0);
 
-        let width = self.layer.width();
-        let height = self.layer.height();
+        let width = image.width();
+        let height = image.height();
         let rect = imageproc::rect::Rect::at(pos.0 as i32, pos.1 as i32).of_size(size.0, size.1);
 
         let radius = (u32::min(width,height)/100) as i32;
@@ -57,16 +64,7 @@ impl Layer {
         imageproc::drawing::draw_filled_circle_mut(&mut area, (pos.0 as i32, (pos.1+size.1) as i32), radius, blue.color);
         imageproc::drawing::draw_filled_circle_mut(&mut area, ((pos.0+size.0) as i32, (pos.1+size.1) as i32), radius, blue.color);
 
-        overlay(&mut image, &self.layer, 0, 0);
-        overlay(&mut image, &area, 0, 0);
-
-        image
-    }
-
-    pub fn draw_shape(&self, base: &Layer) -> DynamicImage {
-        let mut image = base.layer.clone();
-        overlay(&mut image, &self.layer, 0, 0);
-        image
+        composite(image, &DynamicImage::ImageRgba8(area));
     }
 
     pub fn get_pos_size(&self) -> Option<((u32,u32),(u32,u32))> {
@@ -0,0 +1,89 @@
+use image::{Rgba, RgbaImage};
+
+use super::annotation::ArrowHead;
+use super::path::Mask;
+
+///Length and half-width of the arrow heads of a line with the given thickness
+pub fn head_size(thickness: i32) -> (f32, f32) {
+    let t = thickness.max(1) as f32;
+    (f32::max(t * 4.0, 14.0), f32::max(t * 2.5, 8.0))
+}
+
+///Draws an anti-aliased filled disc with the given center, radius and color
+pub fn draw_disc(image: &mut RgbaImage, center: (f32, f32), radius: f32, color: Rgba<u8>) {
+    let mut mask = Mask::new(&[center], radius + 1.0, image);
+    mask.add_segment(center, center, radius * 2.0);
+    mask.apply(image, color);
+}
+
+///Paints on a layer holding a single color the segment from a to b, with the given width and round ends,
+///as a piece of a free-hand line
+pub fn paint_segment(image: &mut RgbaImage, a: (i32, i32), b: (i32, i32), width: i32, color: Rgba<u8>) {
+    let (a, b) = ((a.0 as f32, a.1 as f32), (b.0 as f32, b.1 as f32));
+    let width = width.max(1) as f32;
+    let mut mask = Mask::new(&[a, b], width / 2.0 + 1.0, image);
+    mask.add_segment(a, b, width);
+    mask.paint(image, color);
+}
+
+///Erases from the image the segment from a to b, with the given width and round ends
+pub fn erase_segment(image: &mut RgbaImage, a: (i32, i32), b: (i32, i32), width: i32) {
+    let (a, b) = ((a.0 as f32, a.1 as f32), (b.0 as f32, b.1 as f32));
+    let width = width.max(1) as f32;
+    let mut mask = Mask::new(&[a, b], width / 2.0 + 1.0, image);
+    mask.add_segment(a, b, width);
+    mask.erase(image);
+}
+
+///Draws an anti-aliased straight line from start to end with the given thickness, color and arrow heads.
+///Filled heads point outwards at the end (and at the start too if double-ended); open heads are drawn as two strokes
+pub fn draw_line(image: &mut RgbaImage, start: (i32, i32), end: (i32, i32), thickness: i32, head: ArrowHead, color: Rgba<u8>) {
+    let a = (start.0 as f32, start.1 as f32);
+    let b = (end.0 as f32, end.1 as f32);
+    let width = thickness.max(1) as f32;
+    let (head_length, head_width) = head_size(thickness);
+    let mut mask = Mask::new(&[a, b], head_width.max(width) + 1.0, image);
+
+    let length = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+    if length < 1.0 || head == ArrowHead::None {
+        mask.add_segment(a, b, width);
+        mask.apply(image, color);
+        return;
+    }
+    let u = ((b.0 - a.0) / length, (b.1 - a.1) / length);
+    let n = (-u.1, u.0);
+    //heads never take more than the whole line (half of it each if double-ended)
+    let head_length = match head {
+        ArrowHead::Double => f32::min(head_length, length / 2.0),
+        _ => f32::min(head_length, length)
+    };
+    //returns the tip and the two back corners of a head pointing to tip, in direction d
+    let corners = |tip: (f32, f32), d: (f32, f32)| {
+        let back = (tip.0 - d.0 * head_length, tip.1 - d.1 * head_length);
+        [tip, (back.0 + n.0 * head_width, back.1 + n.1 * head_width), (back.0 - n.0 * head_width, back.1 - n.1 * head_width)]
+    };
+
+    match head {
+        ArrowHead::Open => {
+            let [tip, left, right] = corners(b, u);
+            mask.add_segment(a, b, width);
+            mask.add_segment(tip, left, width);
+            mask.add_segment(tip, right, width);
+        }
+        ArrowHead::Filled => {
+            //the shaft stops inside the head, so that its round end does not stick out of the tip
+            let shaft_end = (b.0 - u.0 * head_length / 2.0, b.1 - u.1 * head_length / 2.0);
+            mask.add_segment(a, shaft_end, width);
+            mask.add_convex(&corners(b, u));
+        }
+        ArrowHead::Double => {
+            let shaft_start = (a.0 + u.0 * head_length / 2.0, a.1 + u.1 * head_length / 2.0);
+            let shaft_end = (b.0 - u.0 * head_length / 2.0, b.1 - u.1 * head_length / 2.0);
+            mask.add_segment(shaft_start, shaft_end, width);
+            mask.add_convex(&corners(b, u));
+            mask.add_convex(&corners(a, (-u.0, -u.1)));
+        }
+        ArrowHead::None => {}
+    }
+    mask.apply(image, color);
+}
@@ -0,0 +1,431 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+use image::{Rgba, RgbaImage};
+
+use super::blend::blend;
+
+///Number of samples taken along each side of a pixel to estimate its coverage, so that a pixel has 64 samples
+const SAMPLES: u32 = 8;
+///Longest miter, as a multiple of half the stroke width, beyond which a join is beveled
+const MITER_LIMIT: f32 = 4.0;
+
+///Shape of the corners where the segments of a stroked path meet
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Join {
+    ///Arc around the corner; the ends of open paths are rounded too
+    Round,
+    ///Sharp corner, beveled when the turn is too tight; the ends of open paths are cut square at the end points
+    Miter
+}
+
+///Coverage of the pixels of a rectangular area, estimated from 8x8 samples per pixel.
+///Shapes are added through their signed distance and the mask holds their union: since each sample is either covered
+///or not, overlapping parts are blended only once and no seam shows where they touch.
+///Only the pixels crossed by the edge of a shape are sampled, the other ones are either fully covered or untouched
+pub struct Mask {
+    x0: i32,
+    y0: i32,
+    width: i32,
+    height: i32,
+    data: Vec<u64>
+}
+
+impl Mask {
+    ///Returns an empty mask covering the given points, enlarged by the given margin and limited to the image
+    pub fn new(points: &[(f32, f32)], margin: f32, image: &RgbaImage) -> Self {
+        let min_x = points.iter().map(|p| p.0).fold(f32::MAX, f32::min) - margin;
+        let min_y = points.iter().map(|p| p.1).fold(f32::MAX, f32::min) - margin;
+        let max_x = points.iter().map(|p| p.0).fold(f32::MIN, f32::max) + margin;
+        let max_y = points.iter().map(|p| p.1).fold(f32::MIN, f32::max) + margin;
+        let x0 = (min_x.floor() as i32).clamp(0, image.width() as i32);
+        let y0 = (min_y.floor() as i32).clamp(0, image.height() as i32);
+        let x1 = (max_x.ceil() as i32 + 1).clamp(x0, image.width() as i32);
+        let y1 = (max_y.ceil() as i32 + 1).clamp(y0, image.height() as i32);
+        Self {
+            x0,
+            y0,
+            width: x1 - x0,
+            height: y1 - y0,
+            data: vec![0; ((x1 - x0) * (y1 - y0)) as usize]
+        }
+    }
+    ///Adds the shape lying between min and max whose signed distance, negative inside, is given by the function.
+    ///The distance can be underestimated, but never overestimated
+    fn add<D: Fn((f32, f32)) -> f32>(&mut self, min: (f32, f32), max: (f32, f32), distance: D) {
+        let x0 = (min.0.floor() as i32 - 1 - self.x0).clamp(0, self.width);
+        let y0 = (min.1.floor() as i32 - 1 - self.y0).clamp(0, self.height);
+        let x1 = (max.0.ceil() as i32 + 1 - self.x0).clamp(x0, self.width);
+        let y1 = (max.1.ceil() as i32 + 1 - self.y0).clamp(y0, self.height);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let cell = &mut self.data[(y * self.width + x) as usize];
+                if *cell == u64::MAX {
+                    continue;
+                }
+                let (px, py) = ((self.x0 + x) as f32, (self.y0 + y) as f32);
+                let d = distance((px + 0.5, py + 0.5));
+                if d <= -FRAC_1_SQRT_2 {
+                    *cell = u64::MAX;
+                } else if d < FRAC_1_SQRT_2 {
+                    for i in 0..SAMPLES * SAMPLES {
+                        let sample = (px + ((i % SAMPLES) as f32 + 0.5) / SAMPLES as f32, py + ((i / SAMPLES) as f32 + 0.5) / SAMPLES as f32);
+                        if distance(sample) < 0.0 {
+                            *cell |= 1 << i;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    ///Adds a segment with the given width and round ends
+    pub fn add_segment(&mut self, a: (f32, f32), b: (f32, f32), width: f32) {
+        let radius = width / 2.0;
+        let min = (f32::min(a.0, b.0) - radius, f32::min(a.1, b.1) - radius);
+        let max = (f32::max(a.0, b.0) + radius, f32::max(a.1, b.1) + radius);
+        self.add(min, max, |p| segment_distance(p, a, b) - radius);
+    }
+    ///Adds a convex polygon, whose vertices are given in either order
+    pub fn add_convex(&mut self, vertices: &[(f32, f32)]) {
+        let (min, max) = bounds(vertices);
+        let area: f32 = (0..vertices.len()).map(|i| {
+            let (a, b) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+            a.0 * b.1 - b.0 * a.1
+        }).sum();
+        let orientation = if area >= 0.0 { 1.0 } else { -1.0 };
+        self.add(min, max, |p| {
+            //signed distance from the farthest edge line
+            let mut outside = f32::MIN;
+            for i in 0..vertices.len() {
+                let (a, b) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+                let (ex, ey) = (b.0 - a.0, b.1 - a.1);
+                let len = (ex * ex + ey * ey).sqrt().max(f32::EPSILON);
+                let d = orientation * ((p.0 - a.0) * ey - (p.1 - a.1) * ex) / len;
+                outside = f32::max(outside, d);
+            }
+            outside
+        });
+    }
+    ///Adds a polygon, convex or not, whose sides do not cross each other
+    pub fn add_polygon(&mut self, vertices: &[(f32, f32)]) {
+        let (min, max) = bounds(vertices);
+        self.add(min, max, |p| {
+            let mut inside = false;
+            let mut nearest = f32::MAX;
+            for i in 0..vertices.len() {
+                let (a, b) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+                if (a.1 > p.1) != (b.1 > p.1) && p.0 < a.0 + (p.1 - a.1) * (b.0 - a.0) / (b.1 - a.1) {
+                    inside = !inside;
+                }
+                nearest = f32::min(nearest, segment_distance(p, a, b));
+            }
+            if inside { -nearest } else { nearest }
+        });
+    }
+    ///Adds a rectangle with the given upper-left corner and size, whose corners are rounded with the given radius
+    pub fn add_rounded_rect(&mut self, pos: (f32, f32), size: (f32, f32), radius: f32) {
+        self.add(pos, (pos.0 + size.0, pos.1 + size.1), |p| rounded_rect_distance(p, pos, size, radius));
+    }
+    ///Adds the border of the given width lying inside the rectangle with the given upper-left corner, size and corner radius.
+    ///The inner contour of the border is rounded by what is left of the radius
+    pub fn add_rounded_rect_border(&mut self, pos: (f32, f32), size: (f32, f32), radius: f32, width: f32) {
+        let inner_pos = (pos.0 + width, pos.1 + width);
+        let inner_size = (size.0 - 2.0 * width, size.1 - 2.0 * width);
+        let inner_radius = f32::max(radius - width, 0.0);
+        self.add(pos, (pos.0 + size.0, pos.1 + size.1), |p| {
+            f32::max(rounded_rect_distance(p, pos, size, radius), -rounded_rect_distance(p, inner_pos, inner_size, inner_radius))
+        });
+    }
+    ///Adds a filled ellipse with the given center and radii
+    pub fn add_ellipse(&mut self, center: (f32, f32), radii: (f32, f32)) {
+        let min = (center.0 - radii.0, center.1 - radii.1);
+        let max = (center.0 + radii.0, center.1 + radii.1);
+        self.add(min, max, |p| ellipse_distance((p.0 - center.0, p.1 - center.1), radii));
+    }
+    ///Adds the contour of the ellipse with the given center and radii, stroked with the given width
+    pub fn add_ellipse_stroke(&mut self, center: (f32, f32), radii: (f32, f32), width: f32) {
+        let half = width / 2.0;
+        let min = (center.0 - radii.0 - half, center.1 - radii.1 - half);
+        let max = (center.0 + radii.0 + half, center.1 + radii.1 + half);
+        self.add(min, max, |p| ellipse_distance((p.0 - center.0, p.1 - center.1), radii).abs() - half);
+    }
+    ///Adds the path through the points, closed back to the first one if required, stroked with the given width and joins
+    pub fn add_stroke(&mut self, points: &[(f32, f32)], closed: bool, width: f32, join: Join) {
+        let n = points.len();
+        if n == 0 {
+            return;
+        }
+        let segments = if closed && n > 2 { n } else { n - 1 };
+        if join == Join::Round || segments == 0 {
+            for i in 0..segments.max(1) {
+                self.add_segment(points[i], points[(i + 1) % n], width);
+            }
+            return;
+        }
+        let half = width / 2.0;
+        for i in 0..segments {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            if let Some(u) = direction(a, b) {
+                let (nx, ny) = (-u.1 * half, u.0 * half);
+                self.add_convex(&[(a.0 + nx, a.1 + ny), (b.0 + nx, b.1 + ny), (b.0 - nx, b.1 - ny), (a.0 - nx, a.1 - ny)]);
+            }
+        }
+        let corners = if closed && n > 2 { 0..n } else { 1..n - 1 };
+        for i in corners {
+            self.add_miter(points[(i + n - 1) % n], points[i], points[(i + 1) % n], half);
+        }
+    }
+    ///Adds the miter filling the outer side of the corner in p, between the segments coming from prev and going to next
+    fn add_miter(&mut self, prev: (f32, f32), p: (f32, f32), next: (f32, f32), half: f32) {
+        let (u1, u2) = match (direction(prev, p), direction(p, next)) {
+            (Some(u1), Some(u2)) => (u1, u2),
+            _ => return
+        };
+        let cross = u1.0 * u2.1 - u1.1 * u2.0;
+        if cross.abs() < 1e-6 {
+            return;
+        }
+        //the outer side is the one opposite to the turn
+        let side = if cross > 0.0 { -half } else { half };
+        let (o1, o2) = ((-u1.1 * side, u1.0 * side), (-u2.1 * side, u2.0 * side));
+        let (a, b) = ((p.0 + o1.0, p.1 + o1.1), (p.0 + o2.0, p.1 + o2.1));
+        //the tip lies on the bisector of the outer offsets, at half / cos(half the angle between them)
+        let (mx, my) = (o1.0 + o2.0, o1.1 + o2.1);
+        let m2 = mx * mx + my * my;
+        let cos = m2.sqrt() / (2.0 * half);
+        if cos * MITER_LIMIT < 1.0 {
+            self.add_convex(&[p, a, b]);
+        } else {
+            let scale = 2.0 * half * half / m2;
+            self.add_convex(&[p, a, (p.0 + mx * scale, p.1 + my * scale), b]);
+        }
+    }
+    ///Returns the fraction of the samples of the pixel, at the given index, covered by the mask
+    fn coverage(&self, index: usize) -> f32 {
+        self.data[index].count_ones() as f32 / (SAMPLES * SAMPLES) as f32
+    }
+    ///Calls the function with every pixel of the image covered by the mask, along with its coverage
+    fn for_each<F: FnMut(&mut Rgba<u8>, f32)>(&self, image: &mut RgbaImage, mut f: F) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let c = self.coverage((y * self.width + x) as usize);
+                if c > 0.0 {
+                    f(image.get_pixel_mut((self.x0 + x) as u32, (self.y0 + y) as u32), c);
+                }
+            }
+        }
+    }
+    ///Blends the color over the image, weighted by the coverage of each pixel
+    pub fn apply(&self, image: &mut RgbaImage, color: Rgba<u8>) {
+        self.for_each(image, |pixel, c| *pixel = blend(*pixel, color, c));
+    }
+    ///Paints the color over a layer holding a single color, such as a free-hand line being drawn: each pixel takes the
+    ///color, with the highest alpha between its own and the one of the color weighted by the coverage, so that the
+    ///pieces of the line are not blended over each other
+    pub fn paint(&self, image: &mut RgbaImage, color: Rgba<u8>) {
+        self.for_each(image, |pixel, c| {
+            let alpha = (color.0[3] as f32 * c).round() as u8;
+            if alpha >= pixel.0[3] {
+                *pixel = Rgba([color.0[0], color.0[1], color.0[2], alpha]);
+            }
+        });
+    }
+    ///Makes the image transparent, weighted by the coverage of each pixel
+    pub fn erase(&self, image: &mut RgbaImage) {
+        self.for_each(image, |pixel, c| pixel.0[3] = (pixel.0[3] as f32 * (1.0 - c)).round() as u8);
+    }
+}
+
+///Returns the smallest and the largest coordinates of the points
+fn bounds(points: &[(f32, f32)]) -> ((f32, f32), (f32, f32)) {
+    let min = (points.iter().map(|p| p.0).fold(f32::MAX, f32::min), points.iter().map(|p| p.1).fold(f32::MAX, f32::min));
+    let max = (points.iter().map(|p| p.0).fold(f32::MIN, f32::max), points.iter().map(|p| p.1).fold(f32::MIN, f32::max));
+    (min, max)
+}
+
+///Returns the unit vector from a to b, or None if they are the same point
+fn direction(a: (f32, f32), b: (f32, f32)) -> Option<(f32, f32)> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len = (dx * dx + dy * dy).sqrt();
+    if len < f32::EPSILON { None } else { Some((dx / len, dy / len)) }
+}
+
+///Returns the distance of the point from the segment between a and b
+fn segment_distance(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 == 0.0 { 0.0 } else { (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len2).clamp(0.0, 1.0) };
+    let (cx, cy) = (a.0 + t * dx, a.1 + t * dy);
+    ((p.0 - cx).powi(2) + (p.1 - cy).powi(2)).sqrt()
+}
+
+///Returns the signed distance of the point from the rectangle with the given upper-left corner, size and corner radius,
+///negative inside. The radius is limited to half the shorter side
+fn rounded_rect_distance(p: (f32, f32), pos: (f32, f32), size: (f32, f32), radius: f32) -> f32 {
+    let radius = radius.clamp(0.0, f32::min(size.0, size.1) / 2.0);
+    let (half_w, half_h) = (size.0 / 2.0, size.1 / 2.0);
+    let (qx, qy) = ((p.0 - pos.0 - half_w).abs() - (half_w - radius), (p.1 - pos.1 - half_h).abs() - (half_h - radius));
+    (qx.max(0.0).powi(2) + qy.max(0.0).powi(2)).sqrt() + f32::min(f32::max(qx, qy), 0.0) - radius
+}
+
+///Returns the signed distance of the point from the ellipse centered in the origin with the given radii, negative inside.
+///The nearest point of the ellipse is found by a few steps of an iteration converging quickly for any eccentricity
+fn ellipse_distance(p: (f32, f32), radii: (f32, f32)) -> f32 {
+    let (a, b) = (radii.0.max(0.01), radii.1.max(0.01));
+    let (px, py) = (p.0.abs(), p.1.abs());
+    let (mut tx, mut ty) = (FRAC_1_SQRT_2, FRAC_1_SQRT_2);
+    for _ in 0..3 {
+        //center of the circle osculating the ellipse in the current point
+        let ex = (a * a - b * b) * tx.powi(3) / a;
+        let ey = (b * b - a * a) * ty.powi(3) / b;
+        let r = ((a * tx - ex).powi(2) + (b * ty - ey).powi(2)).sqrt();
+        let (qx, qy) = (px - ex, py - ey);
+        let q = (qx * qx + qy * qy).sqrt().max(f32::EPSILON);
+        tx = ((qx * r / q + ex) / a).clamp(0.0, 1.0);
+        ty = ((qy * r / q + ey) / b).clamp(0.0, 1.0);
+        let t = (tx * tx + ty * ty).sqrt().max(f32::EPSILON);
+        tx /= t;
+        ty /= t;
+    }
+    let d = ((px - a * tx).powi(2) + (py - b * ty).powi(2)).sqrt();
+    if (px / a).powi(2) + (py / b).powi(2) < 1.0 { -d } else { d }
+}
+
+///Fills the rectangle with the given upper-left corner, size and corner radius with the color
+pub fn fill_rectangle(image: &mut RgbaImage, pos: (i32, i32), size: (u32, u32), radius: u32, color: Rgba<u8>) {
+    let (pos, size) = ((pos.0 as f32, pos.1 as f32), (size.0 as f32, size.1 as f32));
+    let mut mask = Mask::new(&[pos, (pos.0 + size.0, pos.1 + size.1)], 1.0, image);
+    mask.add_rounded_rect(pos, size, radius as f32);
+    mask.apply(image, color);
+}
+
+///Strokes the contour of the rectangle with the given upper-left corner, size and corner radius with the color,
+///the stroke lying inside the rectangle. Without a radius the corners are sharp
+pub fn stroke_rectangle(image: &mut RgbaImage, pos: (i32, i32), size: (u32, u32), radius: u32, width: i32, color: Rgba<u8>) {
+    let half = width.max(1) as f32 / 2.0;
+    let (x, y, w, h) = (pos.0 as f32, pos.1 as f32, size.0 as f32, size.1 as f32);
+    if 2.0 * half >= f32::min(w, h) {
+        fill_rectangle(image, pos, size, radius, color);
+        return;
+    }
+    let mut mask = Mask::new(&[(x, y), (x + w, y + h)], 1.0, image);
+    if radius == 0 {
+        let corners = [(x + half, y + half), (x + w - half, y + half), (x + w - half, y + h - half), (x + half, y + h - half)];
+        mask.add_stroke(&corners, true, 2.0 * half, Join::Miter);
+    } else {
+        mask.add_rounded_rect_border((x, y), (w, h), radius as f32, 2.0 * half);
+    }
+    mask.apply(image, color);
+}
+
+///Fills the ellipse inscribed in the rectangle with the given upper-left corner and size with the color
+pub fn fill_ellipse(image: &mut RgbaImage, pos: (i32, i32), size: (u32, u32), color: Rgba<u8>) {
+    let radii = (size.0 as f32 / 2.0, size.1 as f32 / 2.0);
+    let center = (pos.0 as f32 + radii.0, pos.1 as f32 + radii.1);
+    let mut mask = Mask::new(&[center], f32::max(radii.0, radii.1) + 1.0, image);
+    mask.add_ellipse(center, radii);
+    mask.apply(image, color);
+}
+
+///Strokes the contour of the ellipse inscribed in the rectangle with the given upper-left corner and size with the color,
+///the stroke lying inside the rectangle
+pub fn stroke_ellipse(image: &mut RgbaImage, pos: (i32, i32), size: (u32, u32), width: i32, color: Rgba<u8>) {
+    let half = width.max(1) as f32 / 2.0;
+    let radii = (size.0 as f32 / 2.0 - half, size.1 as f32 / 2.0 - half);
+    if f32::min(radii.0, radii.1) <= 0.0 {
+        fill_ellipse(image, pos, size, color);
+        return;
+    }
+    let center = (pos.0 as f32 + size.0 as f32 / 2.0, pos.1 as f32 + size.1 as f32 / 2.0);
+    let mut mask = Mask::new(&[center], f32::max(radii.0, radii.1) + half + 1.0, image);
+    mask.add_ellipse_stroke(center, radii, 2.0 * half);
+    mask.apply(image, color);
+}
+
+///Fills the polygon with the given vertices with the color
+pub fn fill_polygon(image: &mut RgbaImage, vertices: &[(f32, f32)], color: Rgba<u8>) {
+    let mut mask = Mask::new(vertices, 1.0, image);
+    mask.add_polygon(vertices);
+    mask.apply(image, color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage_at(mask: &Mask, x: i32, y: i32) -> f32 {
+        mask.coverage(((y - mask.y0) * mask.width + x - mask.x0) as usize)
+    }
+
+    #[test]
+    fn interior_pixels_are_fully_covered() {
+        let image = RgbaImage::new(20, 20);
+        let mut mask = Mask::new(&[(2.0, 2.0), (12.0, 12.0)], 1.0, &image);
+        mask.add_rounded_rect((2.0, 2.0), (10.0, 10.0), 0.0);
+        for (x, y) in [(2, 2), (5, 7), (11, 11)] {
+            assert_eq!(coverage_at(&mask, x, y), 1.0);
+        }
+        for (x, y) in [(1, 5), (12, 5), (5, 12)] {
+            assert_eq!(coverage_at(&mask, x, y), 0.0);
+        }
+    }
+
+    #[test]
+    fn edge_pixels_are_partly_covered() {
+        let image = RgbaImage::new(20, 20);
+        let mut mask = Mask::new(&[(2.0, 2.0), (13.0, 13.0)], 1.0, &image);
+        mask.add_rounded_rect((2.5, 2.0), (10.0, 10.0), 0.0);
+        assert_eq!(coverage_at(&mask, 2, 5), 0.5);
+        assert_eq!(coverage_at(&mask, 12, 5), 0.5);
+        assert_eq!(coverage_at(&mask, 3, 5), 1.0);
+        //a corner pixel is covered by half of its width and all of its height
+        assert_eq!(coverage_at(&mask, 2, 2), 0.5);
+    }
+
+    #[test]
+    fn segment_has_the_given_width() {
+        let image = RgbaImage::new(20, 20);
+        let mut mask = Mask::new(&[(0.0, 0.0), (20.0, 20.0)], 0.0, &image);
+        mask.add_segment((2.0, 10.0), (18.0, 10.0), 4.0);
+        let column: Vec<f32> = (6..14).map(|y| coverage_at(&mask, 10, y)).collect();
+        assert_eq!(column, vec![0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn rectangle_stroke_has_the_given_width() {
+        let mut image = RgbaImage::new(20, 20);
+        let color = Rgba([255, 0, 0, 255]);
+        stroke_rectangle(&mut image, (2, 2), (16, 16), 0, 3, color);
+        let row: Vec<u8> = (0..20).map(|x| image.get_pixel(x, 10).0[3]).collect();
+        let mut expected = vec![0u8; 20];
+        for x in [2, 3, 4, 15, 16, 17] {
+            expected[x] = 255;
+        }
+        assert_eq!(row, expected);
+        //the sharp corners are filled up to the corner pixel
+        assert_eq!(image.get_pixel(2, 2).0[3], 255);
+    }
+
+    #[test]
+    fn thick_ellipse_stroke_has_no_holes() {
+        let image = RgbaImage::new(100, 40);
+        let (center, radii, width) = ((50.0, 20.0), (40.0, 12.0), 6.0);
+        let mut mask = Mask::new(&[(0.0, 0.0), (100.0, 40.0)], 0.0, &image);
+        mask.add_ellipse_stroke(center, radii, width);
+
+        //distance of the pixel centers from a dense sampling of the contour
+        let contour: Vec<(f32, f32)> = (0..3600).map(|i| {
+            let t = i as f32 * std::f32::consts::TAU / 3600.0;
+            (center.0 + radii.0 * t.cos(), center.1 + radii.1 * t.sin())
+        }).collect();
+        for y in 0..40 {
+            for x in 0..100 {
+                let p = (x as f32 + 0.5, y as f32 + 0.5);
+                let d = contour.iter().map(|c| ((p.0 - c.0).powi(2) + (p.1 - c.1).powi(2)).sqrt()).fold(f32::MAX, f32::min);
+                if d < width / 2.0 - 0.75 {
+                    assert_eq!(coverage_at(&mask, x, y), 1.0, "hole at {:?}", (x, y));
+                } else if d > width / 2.0 + 0.75 {
+                    assert_eq!(coverage_at(&mask, x, y), 0.0, "stray pixel at {:?}", (x, y));
+                }
+            }
+        }
+    }
+}
\ No newline at end of file
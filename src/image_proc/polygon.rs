@@ -10,4 +10,8 @@ impl Polygon {
             vertices: vertices
         }
     }
+    ///Returns the vertices as pairs of coordinates
+    pub fn points(&self) -> Vec<(f32, f32)> {
+        self.vertices.iter().map(|v| (v.x as f32, v.y as f32)).collect()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,315 @@
+use image::{Rgba, RgbaImage};
+use rusttype::{point, Font, Scale};
+
+use super::annotation::{TextAlign, TextStyle};
+use super::blend::blend;
+
+///Returns the width in pixels of a line of text
+fn line_width(font: &Font<'static>, scale: Scale, line: &str) -> f32 {
+    font.layout(line, scale, point(0.0, 0.0))
+        .last()
+        .map(|g| g.position().x + g.unpositioned().h_metrics().advance_width)
+        .unwrap_or(0.0)
+}
+
+///Returns the height in pixels of a line of text, including the gap to the following one
+fn line_height(font: &Font<'static>, scale: Scale) -> f32 {
+    let v = font.v_metrics(scale);
+    (v.ascent - v.descent + v.line_gap).ceil()
+}
+
+///Splits the text into lines at every line break and, if max_width is not 0, wherever a line would be wider
+///than max_width: between words, or inside a word too long to fit on a line of its own
+pub fn wrap(text: &str, font: &Font<'static>, scale: Scale, max_width: u32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        if max_width == 0 {
+            lines.push(paragraph.to_string());
+            continue;
+        }
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if line_width(font, scale, &candidate) <= max_width as f32 {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if line.chars().count() > 1 && line_width(font, scale, &line) > max_width as f32 {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, c.to_string()));
+                }
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+///Returns the space left between the letters and the sides of the box: the padding, plus the outline width
+fn margin(style: &TextStyle) -> u32 {
+    style.padding + style.outline.map(|(_, width)| width).unwrap_or(0)
+}
+
+///Returns the size of the box holding the text drawn with the given font, size and style
+pub fn text_size(text: &str, font: &Font<'static>, font_size: f32, style: &TextStyle) -> (u32, u32) {
+    let scale = Scale::uniform(font_size);
+    let lines = wrap(text, font, scale, style.max_width);
+    let width = lines.iter().map(|l| line_width(font, scale, l)).fold(0.0, f32::max).ceil() as u32;
+    let height = line_height(font, scale) as u32 * lines.len() as u32;
+    (width + 2 * margin(style), height + 2 * margin(style))
+}
+
+///Draws the text in the box with the given upper-left corner: first the background box, then the outline of the
+///letters and finally the letters themselves, all blended over the image with anti-aliased edges
+pub fn draw_text(image: &mut RgbaImage, pos: (i32, i32), text: &str, font: &Font<'static>, font_size: f32, style: &TextStyle, color: Rgba<u8>) {
+    let scale = Scale::uniform(font_size);
+    let lines = wrap(text, font, scale, style.max_width);
+    let (width, height) = text_size(text, font, font_size, style);
+    let (width, height) = (width as i32, height as i32);
+    let margin = margin(style) as f32;
+    let content_width = width as f32 - 2.0 * margin;
+
+    //coverage of the box pixels by the letters, from 0 to 1
+    let mut coverage = vec![0.0f32; (width * height) as usize];
+    let ascent = font.v_metrics(scale).ascent;
+    for (i, line) in lines.iter().enumerate() {
+        let free = content_width - line_width(font, scale, line);
+        let x = margin + match style.align {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => (free / 2.0).floor(),
+            TextAlign::Right => free
+        };
+        let y = margin + i as f32 * line_height(font, scale) + ascent;
+        for glyph in font.layout(line, scale, point(x, y)) {
+            if let Some(bb) = glyph.pixel_bounding_box() {
+                glyph.draw(|gx, gy, v| {
+                    let (px, py) = (bb.min.x + gx as i32, bb.min.y + gy as i32);
+                    if px >= 0 && py >= 0 && px < width && py < height {
+                        let cell = &mut coverage[(py * width + px) as usize];
+                        *cell = f32::max(*cell, v);
+                    }
+                });
+            }
+        }
+    }
+
+    let mut paint = |weights: &[f32], color: Rgba<u8>| {
+        for y in 0..height {
+            for x in 0..width {
+                let (ix, iy) = (pos.0 + x, pos.1 + y);
+                let w = weights[(y * width + x) as usize];
+                if w > 0.0 && ix >= 0 && iy >= 0 && ix < image.width() as i32 && iy < image.height() as i32 {
+                    let pixel = image.get_pixel_mut(ix as u32, iy as u32);
+                    *pixel = blend(*pixel, color, w);
+                }
+            }
+        }
+    };
+
+    if let Some(background) = style.background {
+        paint(&vec![1.0; (width * height) as usize], background.color);
+    }
+    match style.outline {
+        Some((outline, thickness)) if thickness > 0 => {
+            //the letters are grown by the outline width, fading over its last pixel
+            let r = thickness as i32;
+            let mut grown = vec![0.0f32; coverage.len()];
+            for y in 0..height {
+                for x in 0..width {
+                    let mut best = 0.0f32;
+                    for dy in -r..=r {
+                        for dx in -r..=r {
+                            let (nx, ny) = (x + dx, y + dy);
+                            if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                                continue;
+                            }
+                            let falloff = (thickness as f32 + 0.5 - ((dx * dx + dy * dy) as f32).sqrt()).clamp(0.0, 1.0);
+                            best = f32::max(best, coverage[(ny * width + nx) as usize] * falloff);
+                        }
+                    }
+                    grown[(y * width + x) as usize] = best;
+                }
+            }
+            paint(&grown, outline.color);
+        }
+        _ => {}
+    }
+    paint(&coverage, color);
+}
+
+///Returns the largest font size, up to the height of the area, at which the text drawn with the given style fits in the area
+pub fn fit_font_size(text: &str, font: &Font<'static>, area: (u32, u32), style: &TextStyle) -> f32 {
+    let fits = |size: f32| {
+        let (w, h) = text_size(text, font, size, style);
+        w <= area.0 && h <= area.1
+    };
+    let (mut low, mut high) = (4.0f32, f32::max(area.1 as f32, 4.0));
+    if fits(high) {
+        return high;
+    }
+    while high - low > 0.5 {
+        let middle = (low + high) / 2.0;
+        if fits(middle) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    low
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_proc::colors::Color;
+
+    fn font() -> Font<'static> {
+        Font::try_from_bytes(include_bytes!("../../fonts/JetBrainsMono-Regular.ttf")).unwrap()
+    }
+
+    fn fits(text: &str, font: &Font<'static>, font_size: f32, area: (u32, u32), style: &TextStyle) -> bool {
+        let (w, h) = text_size(text, font, font_size, style);
+        w <= area.0 && h <= area.1
+    }
+
+    #[test]
+    fn empty_text_is_a_single_empty_line() {
+        let font = font();
+        assert_eq!(wrap("", &font, Scale::uniform(20.0), 100), vec![String::new()]);
+        assert_eq!(wrap("", &font, Scale::uniform(20.0), 0), vec![String::new()]);
+
+        let style = TextStyle::default();
+        let size = fit_font_size("", &font, (100, 40), &style);
+        assert!(size > 4.0 && fits("", &font, size, (100, 40), &style));
+    }
+
+    #[test]
+    fn lines_are_wrapped_between_words() {
+        let font = font();
+        let scale = Scale::uniform(20.0);
+        let max_width = line_width(&font, scale, "hello world").ceil() as u32;
+        assert_eq!(wrap("hello world again\nhi", &font, scale, max_width), vec!["hello world", "again", "hi"]);
+    }
+
+    #[test]
+    fn word_wider_than_the_box_is_split() {
+        let font = font();
+        let scale = Scale::uniform(20.0);
+        let max_width = line_width(&font, scale, "abcd").ceil() as u32;
+        assert_eq!(wrap("abcdefghij", &font, scale, max_width), vec!["abcd", "efgh", "ij"]);
+        //a box narrower than a single letter still gets a letter per line
+        assert_eq!(wrap("abc", &font, scale, 1), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn zero_width_does_not_wrap() {
+        let font = font();
+        let scale = Scale::uniform(20.0);
+        assert_eq!(wrap("a very long line\nof text", &font, scale, 0), vec!["a very long line", "of text"]);
+    }
+
+    #[test]
+    fn font_size_fits_the_area() {
+        let font = font();
+        let style = TextStyle { padding: 4, ..TextStyle::default() };
+        let size = fit_font_size("step one", &font, (200, 100), &style);
+        assert!(fits("step one", &font, size, (200, 100), &style));
+        assert!(!fits("step one", &font, size + 1.0, (200, 100), &style));
+    }
+
+    #[test]
+    fn font_size_of_a_word_wider_than_the_box() {
+        let font = font();
+        //with wrapping, the word is split across lines instead of being shrunk to fit on one
+        let wrapped = TextStyle { max_width: 60, ..TextStyle::default() };
+        let size = fit_font_size("abcdefghijklmnop", &font, (60, 200), &wrapped);
+        assert!(fits("abcdefghijklmnop", &font, size, (60, 200), &wrapped));
+        let unwrapped = fit_font_size("abcdefghijklmnop", &font, (60, 200), &TextStyle::default());
+        assert!(fits("abcdefghijklmnop", &font, unwrapped, (60, 200), &TextStyle::default()));
+        assert!(unwrapped < size);
+    }
+
+    #[test]
+    fn nothing_fits_a_zero_sized_area() {
+        //the smallest font size is returned even if the text does not fit
+        let font = font();
+        let style = TextStyle::default();
+        assert_eq!(fit_font_size("text", &font, (0, 100), &style), 4.0);
+        assert_eq!(fit_font_size("text", &font, (100, 0), &style), 4.0);
+        assert_eq!(fit_font_size("", &font, (0, 0), &style), 4.0);
+    }
+
+    fn opaque(r: u8, g: u8, b: u8) -> Color {
+        Color { color: Rgba([r, g, b, 255]) }
+    }
+
+    ///Returns the first column of the rows from y0 to y1 holding a pixel different from the given background
+    fn first_column(image: &RgbaImage, y0: u32, y1: u32, background: Rgba<u8>) -> Option<u32> {
+        (0..image.width()).find(|&x| (y0..y1).any(|y| *image.get_pixel(x, y) != background))
+    }
+
+    #[test]
+    fn translucent_box_is_blended_over_the_image() {
+        let font = font();
+        let style = TextStyle { background: Some(Color { color: Rgba([255, 0, 0, 128]) }), padding: 10, ..TextStyle::default() };
+        let mut image = RgbaImage::from_pixel(120, 80, Rgba([0, 0, 255, 255]));
+        draw_text(&mut image, (5, 5), "a", &font, 20.0, &style, Rgba([255, 255, 255, 255]));
+        //the corner of the box is inside the padding, far from the letter
+        assert_eq!(*image.get_pixel(6, 6), Rgba([128, 0, 127, 255]));
+        assert_eq!(*image.get_pixel(4, 4), Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn outline_surrounds_the_letters_only() {
+        let font = font();
+        let black = Rgba([0, 0, 0, 255]);
+        let white = Rgba([255, 255, 255, 255]);
+        //same margin with and without the outline, so the letters are drawn in the same place
+        let mut plain = RgbaImage::from_pixel(80, 80, black);
+        draw_text(&mut plain, (0, 0), "H", &font, 40.0, &TextStyle { padding: 7, ..TextStyle::default() }, white);
+        let mut outlined = RgbaImage::from_pixel(80, 80, black);
+        let style = TextStyle { padding: 4, outline: Some((opaque(0, 255, 0), 3)), ..TextStyle::default() };
+        draw_text(&mut outlined, (0, 0), "H", &font, 40.0, &style, white);
+
+        //a pixel left empty by the letter but close to a fully covered one gets the outline color
+        let (w, h) = (plain.width() as i32, plain.height() as i32);
+        let near_letter = |x: i32, y: i32| (-2..=2).any(|dy: i32| (-2..=2).any(|dx: i32| {
+            let (nx, ny) = (x + dx, y + dy);
+            dx * dx + dy * dy <= 5 && nx >= 0 && ny >= 0 && nx < w && ny < h && *plain.get_pixel(nx as u32, ny as u32) == white
+        }));
+        let outside: Vec<(u32, u32)> = plain.enumerate_pixels()
+            .filter(|(x, y, p)| **p == black && near_letter(*x as i32, *y as i32))
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert!(!outside.is_empty());
+        for (x, y) in outside {
+            assert_eq!(*outlined.get_pixel(x, y), Rgba([0, 255, 0, 255]), "{} {}", x, y);
+        }
+        assert_eq!(*outlined.get_pixel(0, 0), black);
+    }
+
+    #[test]
+    fn short_line_is_aligned_in_a_wider_box() {
+        let font = font();
+        let scale = Scale::uniform(20.0);
+        let background = Rgba([0, 0, 0, 255]);
+        let style = TextStyle { padding: 5, ..TextStyle::default() };
+        //the box is as wide as the longest line, rounded up to a whole pixel
+        let free = line_width(&font, scale, "abcdef").ceil() - line_width(&font, scale, "a");
+
+        let start = |align: TextAlign| {
+            let mut image = RgbaImage::from_pixel(150, 80, background);
+            draw_text(&mut image, (0, 0), "a\nabcdef", &font, 20.0, &TextStyle { align, ..style }, Rgba([255, 255, 255, 255]));
+            first_column(&image, 0, 5 + line_height(&font, scale) as u32, background).unwrap()
+        };
+        let glyph_start = |x: f32| font.layout("a", scale, point(x, 0.0)).next().unwrap().pixel_bounding_box().unwrap().min.x as u32;
+        assert_eq!(start(TextAlign::Left), glyph_start(5.0));
+        assert_eq!(start(TextAlign::Center), glyph_start(5.0 + (free / 2.0).floor()));
+        assert_eq!(start(TextAlign::Right), glyph_start(5.0 + free));
+    }
+}
\ No newline at end of file
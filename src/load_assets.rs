@@ -24,4 +24,12 @@ pub fn load_borders() -> Result<HashMap<String,DynamicImage>,std::io::Error>{
 
     return Ok(borders);
 
+}
+
+pub fn load_cursor() -> Result<DynamicImage,std::io::Error>{
+
+    let mut path = PathBuf::from("assets");
+    path.push("cursor.png");
+    image::open(&path).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+
 }
\ No newline at end of file
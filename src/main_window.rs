@@ -2,23 +2,25 @@ use std::collections::{HashMap, BTreeMap};
 use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use eframe::egui;
 use eframe::epaint::TextureHandle;
 use eframe::glow::Context;
 use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager};
-use global_hotkey::hotkey::HotKey;
 use image::DynamicImage;
 use rusttype::Font;
 use crate::hotkey_popup::*;
 use crate::main_window::Status::*;
 use crate::cursor_scaling::*;
 use crate::screensh::{Screen};
+use crate::screensh::recording::{Recording, RecordingFormat};
+use crate::screensh::window_list::{get_window_provider, StaticWindowProvider, WindowInfo, WindowProvider};
 use crate::image_proc::blur_area::BlurArea;
 use crate::image_proc::colors::{Color};
-use crate::image_proc::Image;
+use crate::image_proc::{get_image_from_memory, set_history_memory_limit, Image};
 use crate::image_proc::extensions::Extensions;
 use crate::image_proc::layer::Layer;
-use crate::load_assets::load_borders;
+use crate::load_assets::{load_borders, load_cursor};
 use crate::load_fonts::{load_fonts, load_fonts_fallback};
 use crate::main_window::crop_win::crop_window;
 use crate::main_window::draw_win::draw_window;
@@ -27,6 +29,11 @@ use crate::main_window::image_win::image_window;
 use crate::main_window::initial_win::initial_window;
 use crate::main_window::settings_win::settings_window;
 use crate::main_window::text_win::text_window;
+use crate::main_window::region_win::region_window;
+use crate::main_window::recording_win::recording_window;
+use crate::main_window::scroll_win::scroll_window;
+use crate::main_window::countdown_win::{countdown_window, COUNTDOWN_SIZE};
+use crate::main_window::palette::Palette;
 
 pub mod crop_win;
 pub mod draw_win;
@@ -34,6 +41,12 @@ pub mod text_win;
 pub mod initial_win;
 pub mod settings_win;
 pub mod image_win;
+pub mod region_win;
+pub mod recording_win;
+pub mod scroll_win;
+pub mod countdown_win;
+pub mod eyedropper;
+pub mod palette;
 mod hidden_win;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -45,6 +58,10 @@ pub enum Status{
     Crop,
     Draw,
     Text,
+    Region,
+    Record,
+    Scroll,
+    Countdown,
 }
 
 impl Default for Status{
@@ -58,24 +75,26 @@ pub enum DrawStatus{
     Draw,
     Rubber,
     Highlight,
-    Shape(u8)
+    Shape(u8),
+    Select,
+    Line
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub enum Pointing{
-    Up,
-    Down,
-    Left,
-    Right
-}
+pub use crate::image_proc::annotation::Pointing;
+use crate::image_proc::annotation::{Annotation, ArrowHead, RedactionStyle, TextAlign, TextFont, TextStyle};
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Shape{
     FilledRectangle,
     EmptyRectangle,
-    FilledCircle,
-    EmptyCircle,
-    Arrow(Pointing)
+    FilledEllipse,
+    EmptyEllipse,
+    Arrow(Pointing),
+    Redact(RedactionStyle),
+    Step,
+    Magnifier,
+    Label,
+    Bubble
 }
 
 impl Default for DrawStatus{
@@ -84,6 +103,8 @@ impl Default for DrawStatus{
     }
 }
 
+///Upper-left corner and size of an area of the image
+type Area = ((u32, u32), (u32, u32));
 
 pub struct MyApp {
     prev: Status,
@@ -108,12 +129,46 @@ pub struct MyApp {
     save_path_copy: String,
     delay_secs: u32,
     delay_secs_cp: u32,
+    interval_secs: u32,
+    interval_secs_cp: u32,
+    interval_shots: u32,
+    interval_shots_cp: u32,
+    interval_running: bool,
+    interval_count: u32,
+    interval_next: f64,
+    interval_name: String,
+    recording: Option<Recording>,
+    record_fps: u32,
+    record_fps_cp: u32,
+    record_secs: u32,
+    record_secs_cp: u32,
+    record_format: RecordingFormat,
+    record_format_cp: RecordingFormat,
+    history_mb: u32,
+    history_mb_cp: u32,
+    record_start: f64,
+    record_next: f64,
+    record_stop: bool,
+    record_name: String,
+    record_region: Option<((u32, u32), (u32, u32))>,
+    record_region_flag: bool,
+    record_result: Option<String>,
+    scroll_frames: Vec<DynamicImage>,
+    scroll_next: f64,
+    scroll_stop: bool,
+    scroll_region: Option<((u32, u32), (u32, u32))>,
+    scroll_region_flag: bool,
     save_name: String,
     clipboard: arboard::Clipboard,
     hk_num: usize,
     any_pressed: bool,
     sel_screen: usize,
     all_screens: bool,
+    desktop_flag: bool,
+    window_provider: Box<dyn WindowProvider>,
+    windows: Vec<WindowInfo>,
+    sel_window: Option<WindowInfo>,
+    window_flag: bool,
     window_image_ratio: f32,
     scroll_qty: f32,
     is_ratio_along_y: bool,
@@ -123,34 +178,88 @@ pub struct MyApp {
     cur_mouse_pos: Option<(u32, u32)>,
     anchor_corner: Option<((f32, f32), f32)>,
     draw_layer: Option<Layer>,
-    prev_edge: Option<((i32, i32), (i32, i32), (i32, i32))>,
+    prev_edge: Option<(i32, i32)>,
     fonts: Option<BTreeMap<String, Font<'static>>>,
     borders: Option<HashMap<String, DynamicImage>>,
+    cursor_sprite: Option<DynamicImage>,
+    include_cursor: bool,
+    include_cursor_cp: bool,
     sel_font: Option<String>,
     sel_font_size: usize,
     sel_color: Color,
     image_text: String,
+    text_align: TextAlign,
+    text_max_width: u32,
+    text_box: bool,
+    text_box_color: Color,
+    text_padding: u32,
+    text_outline: bool,
+    text_outline_color: Color,
+    text_outline_width: u32,
+    //text drawn over the image while it follows the cursor, before being placed
+    text_preview: Option<Annotation>,
     is_sel_color: bool,
     dropdown_on: bool,
     rubber: bool,
     highlight: bool,
     rubber_layer: Option<Layer>,
-    last_crop_data: Option<((u32, u32), (u32, u32))>,
     draw_status: DrawStatus,
     pencil_rubber_thickness: i32,
     draw_color: Color,
     highlight_color: Color,
     highlight_thickness: i32,
     which_shape: Option<Shape>,
+    line_head: ArrowHead,
+    marker_size: u32,
+    magnifier_zoom: u32,
+    //radius of the corners of the rectangles, 0 for sharp corners
+    corner_radius: u32,
+    //width over height of the shape resized while Shift is held
+    resize_ratio: Option<f32>,
+    bubble_text: String,
+    //tip of the tail of the speech bubble being placed
+    bubble_tail: Option<(i32, i32)>,
+    tail_drag: bool,
+    //capture sampled by the eyedropper while it is on
+    eyedropper: Option<DynamicImage>,
+    picked_color: Option<Color>,
+    //recent and favourite colors, shared by all the tools
+    palette: Palette,
+    //index of the annotation selected in the draw window, along with the area it is being moved or resized to
+    sel_annotation: Option<(usize, Area)>,
+    region_flag: bool,
+    region_image: Option<Image>,
+    region_texture: Option<TextureHandle>,
+    region_start: Option<(u32, u32)>,
+    window_backup: Option<(Option<egui::Pos2>, egui::Vec2)>,
+    show_history: bool,
+    //problems found while starting, such as screens that cannot be captured, shown in the initial window
+    startup_errors: Vec<String>,
 }
 
 impl MyApp {
     pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
+        let mut startup_errors = Vec::new();
+        let screens = match Screen::get_screens() {
+            Ok(screens) => screens,
+            Err(e) => {
+                startup_errors.push(format!("Screens not available, captures are disabled: {}", e));
+                Vec::new()
+            }
+        };
+        let scale_factor = screens.first().map(|s| s.geometry().scale_factor).unwrap_or(1.0);
+        let window_provider = match get_window_provider(scale_factor) {
+            Ok(provider) => provider,
+            Err(e) => {
+                startup_errors.push(format!("Windows not available for capture: {}", e));
+                Box::new(StaticWindowProvider::new(Vec::new()))
+            }
+        };
         let mut ret = MyApp{
             prev: Status::default(), status: Status::default(),
             hk: HotKeyPopUp::default(), hk_copy: HotKeyPopUp::default(),
             manager_hk: GlobalHotKeyManager::new().unwrap(),
-            screens: Screen::get_screens().unwrap(), image: None,
+            screens, image: None,
             image_to_save: None, backup_image: None, backup_image_to_save: None,
             all_images: Vec::new(),
             all_images_to_save: Vec::new(),
@@ -161,13 +270,41 @@ impl MyApp {
             save_path: std::env::current_dir().unwrap().as_os_str().to_str().unwrap().to_string(),
             save_path_copy: std::env::current_dir().unwrap().as_os_str().to_str().unwrap().to_string(),
             delay_secs: 3u32, delay_secs_cp: 3u32,
+            interval_secs: 5u32, interval_secs_cp: 5u32,
+            interval_shots: 10u32, interval_shots_cp: 10u32,
+            interval_running: false,
+            interval_count: 0,
+            interval_next: 0.0,
+            interval_name: String::new(),
+            recording: None,
+            record_fps: 10u32, record_fps_cp: 10u32,
+            record_secs: 10u32, record_secs_cp: 10u32,
+            record_format: RecordingFormat::Gif, record_format_cp: RecordingFormat::Gif,
+            history_mb: 512u32, history_mb_cp: 512u32,
+            record_start: 0.0,
+            record_next: 0.0,
+            record_stop: false,
+            record_name: String::new(),
+            record_region: None,
+            record_region_flag: false,
+            record_result: None,
+            scroll_frames: Vec::new(),
+            scroll_next: 0.0,
+            scroll_stop: false,
+            scroll_region: None,
+            scroll_region_flag: false,
             save_name: String::new(),
             clipboard: arboard::Clipboard::new().unwrap(),
-            hk_num: 7usize,
-            forbidden_hk: vec![false; 7usize],
+            hk_num: 11usize,
+            forbidden_hk: vec![false; 11usize],
             any_pressed: false,
             sel_screen: 0usize,
             all_screens: false,
+            desktop_flag: false,
+            window_provider,
+            windows: Vec::new(),
+            sel_window: None,
+            window_flag: false,
             window_image_ratio: 0.2,
             is_ratio_along_y: true,
             scroll_qty: 0.0,
@@ -180,49 +317,73 @@ impl MyApp {
             prev_edge: None,
             fonts: None,
             borders: None,
+            cursor_sprite: None,
+            include_cursor: false,
+            include_cursor_cp: false,
             sel_font: None,
             sel_font_size: 12usize,
             sel_color: Color::new(0, 0, 0, 1.0),
             image_text: String::from("Insert text here"),
+            text_align: TextAlign::Left,
+            text_max_width: 0,
+            text_box: false,
+            text_box_color: Color::new(0, 0, 0, 0.6),
+            text_padding: 8,
+            text_outline: false,
+            text_outline_color: Color::new(255, 255, 255, 1.0),
+            text_outline_width: 2,
+            text_preview: None,
             is_sel_color: false,
             dropdown_on: false,
             rubber: false,
             highlight: false,
             rubber_layer: None,
-            last_crop_data: None,
             draw_status: DrawStatus::default(),
             pencil_rubber_thickness: 5,
             draw_color: Color::new(255, 0, 0, 1.0),
             highlight_color: Color::new(255, 255, 0, 0.3),
             highlight_thickness: 5,
-            which_shape: None
+            which_shape: None,
+            line_head: ArrowHead::Filled,
+            marker_size: 48,
+            magnifier_zoom: 2,
+            corner_radius: 0,
+            resize_ratio: None,
+            bubble_text: String::from("Text"),
+            bubble_tail: None,
+            tail_drag: false,
+            eyedropper: None,
+            picked_color: None,
+            palette: Palette::load(),
+            sel_annotation: None,
+            region_flag: false,
+            region_image: None,
+            region_texture: None,
+            region_start: None,
+            window_backup: None,
+            show_history: false,
+            startup_errors
         };
 
         match File::open("settings/settings"){
             Ok(f) => {
-                let mut vec_op = Vec::new();
+                //every line keeps the default value if it is missing or not valid
+                let mut vec_op = HotKeyPopUp::default().get_all_shortcuts();
                 let br = BufReader::new(f);
-                for (i, l) in br.lines().enumerate(){
-                    let h = l.unwrap();
+                for (i, h) in br.lines().map_while(Result::ok).enumerate(){
                     match i {
-                        0..=6 => {
-                            match parse(h.clone(), i) {
-                                Ok(s) => {
-                                    vec_op.push(s);
-                                    let hh: HotKey = h.parse().unwrap();
-                                    ret.manager_hk.register(hh).unwrap();
-                                },
-                                Err(_) => {}
+                        0..=6 | 17 | 19 | 22 | 26 => {
+                            let index = hotkey_index(i);
+                            if let Ok(op) = parse(h.clone(), index) {
+                                vec_op[index] = op;
                             }
                         },
                         7 => {
-                            ret.hk = HotKeyPopUp::initialize(vec_op.clone());
-                            ret.hk_copy = HotKeyPopUp::initialize(vec_op.clone());
-                            ret.delay_secs = h.clone().parse().unwrap();
-                            ret.delay_secs_cp = h.clone().parse().unwrap();
+                            ret.delay_secs = parse_or(&h, ret.delay_secs);
+                            ret.delay_secs_cp = ret.delay_secs;
                         },
                         8 => {
-                            match h.clone().as_str() {
+                            match h.as_str() {
                                 "PNG" => {ret.extension = Extensions::PNG; ret.extension_copy = Extensions::PNG},
                                 "JPG" => {ret.extension = Extensions::JPG; ret.extension_copy = Extensions::JPG},
                                 "GIF" => {ret.extension = Extensions::GIF; ret.extension_copy = Extensions::GIF},
@@ -233,46 +394,67 @@ impl MyApp {
                             ret.sel_font = Some(h.clone());
                         },
                         10 => {
-                            ret.sel_font_size = h.parse().unwrap();
+                            ret.sel_font_size = parse_or(&h, ret.sel_font_size);
                         },
                         11 => {
-                            let mut iter = h.split_whitespace();
-                            ret.sel_color.color[0] = iter.next().unwrap().parse().unwrap();
-                            ret.sel_color.color[1] = iter.next().unwrap().parse().unwrap();
-                            ret.sel_color.color[2] = iter.next().unwrap().parse().unwrap();
+                            ret.sel_color = parse_color(&h, ret.sel_color);
                         },
                         12 => {
-                            ret.pencil_rubber_thickness = h.parse().unwrap();
+                            ret.pencil_rubber_thickness = parse_or(&h, ret.pencil_rubber_thickness);
                         },
                         13 => {
-                            let mut iter = h.split_whitespace();
-                            ret.draw_color.color[0] = iter.next().unwrap().parse().unwrap();
-                            ret.draw_color.color[1] = iter.next().unwrap().parse().unwrap();
-                            ret.draw_color.color[2] = iter.next().unwrap().parse().unwrap();
+                            ret.draw_color = parse_color(&h, ret.draw_color);
                         },
                         14 => {
-                            ret.highlight_thickness = h.parse().unwrap();
+                            ret.highlight_thickness = parse_or(&h, ret.highlight_thickness);
                         },
                         15 => {
-                            let mut iter = h.split_whitespace();
-                            ret.highlight_color.color[0] = iter.next().unwrap().parse().unwrap();
-                            ret.highlight_color.color[1] = iter.next().unwrap().parse().unwrap();
-                            ret.highlight_color.color[2] = iter.next().unwrap().parse().unwrap();
+                            ret.highlight_color = parse_color(&h, ret.highlight_color);
                         },
-                        16 => {
-                            let path = h.clone();
-                            if !Path::new(&path).exists(){
-                                ret.save_path = std::env::current_dir().unwrap().as_os_str().to_str().unwrap().to_string();
-                                ret.save_path_copy = std::env::current_dir().unwrap().as_os_str().to_str().unwrap().to_string();
-                            }
-                            else{
-                                ret.save_path = path.clone();
-                                ret.save_path_copy = path.clone();
+                        16 if Path::new(&h).exists() => {
+                            ret.save_path = h.clone();
+                            ret.save_path_copy = h.clone();
+                        },
+                        18 => {
+                            ret.include_cursor = h.trim() == "true";
+                            ret.include_cursor_cp = ret.include_cursor;
+                        },
+                        20 => {
+                            ret.interval_secs = parse_or(&h, ret.interval_secs);
+                            ret.interval_secs_cp = ret.interval_secs;
+                        },
+                        21 => {
+                            ret.interval_shots = parse_or(&h, ret.interval_shots);
+                            ret.interval_shots_cp = ret.interval_shots;
+                        },
+                        23 => {
+                            ret.record_fps = parse_or(&h, ret.record_fps).max(1);
+                            ret.record_fps_cp = ret.record_fps;
+                        },
+                        24 => {
+                            ret.record_secs = parse_or(&h, ret.record_secs);
+                            ret.record_secs_cp = ret.record_secs;
+                        },
+                        25 => {
+                            match h.as_str() {
+                                "GIF" => {ret.record_format = RecordingFormat::Gif; ret.record_format_cp = RecordingFormat::Gif},
+                                "APNG" => {ret.record_format = RecordingFormat::Apng; ret.record_format_cp = RecordingFormat::Apng},
+                                _ => {}
                             }
                         },
+                        27 => {
+                            ret.history_mb = parse_or(&h, ret.history_mb);
+                            ret.history_mb_cp = ret.history_mb;
+                        },
                         _ => {}
                     }
                 }
+                //a hotkey that cannot be registered, for instance because another program uses it, is left inactive
+                for op in vec_op.iter() {
+                    let _ = ret.manager_hk.register(op.get_immut_hotkey());
+                }
+                ret.hk = HotKeyPopUp::initialize(vec_op.clone());
+                ret.hk_copy = HotKeyPopUp::initialize(vec_op);
             }
             Err(_) => {}
         }
@@ -291,7 +473,9 @@ impl MyApp {
             }
         });
 
+        set_history_memory_limit(ret.history_mb as usize * 1024 * 1024);
         ret.borders = Some(load_borders().unwrap());
+        ret.cursor_sprite = load_cursor().ok();
 
         if !ret.fonts.as_ref().unwrap().contains_key(ret.sel_font.as_ref().unwrap()){
             ret.sel_font = Some(ret.fonts.as_ref().unwrap().iter().nth(0).unwrap().0.clone());
@@ -321,13 +505,44 @@ impl eframe::App for MyApp {
                             self.instant_flag = true;
                             self.status = Hidden;
                         },
-                        "Delay capture" => {
+                        "Region capture" => {
                             frame.set_visible(false);
                             self.disabled_time = ctx.input(|i| i.time);
-                            self.prev = self.status.clone();
-                            self.instant_flag = false;
+                            self.prev = self.status;
+                            self.instant_flag = true;
+                            self.region_flag = true;
                             self.status = Hidden;
                         },
+                        "Interval capture" => {
+                            if self.interval_running {
+                                self.interval_running = false;
+                            } else {
+                                self.start_interval(ctx.input(|i| i.time));
+                            }
+                        },
+                        "Record screen" => {
+                            if self.status == Record {
+                                self.record_stop = true;
+                            } else if self.status != Hidden && self.status != Region {
+                                self.record_region = None;
+                                self.start_recording(frame, ctx.input(|i| i.time));
+                            }
+                        },
+                        "Scroll capture" => {
+                            if self.status == Scroll {
+                                self.scroll_stop = true;
+                            } else if self.status != Hidden && self.status != Region && self.status != Record {
+                                self.scroll_region = None;
+                                self.start_scrolling(frame, ctx.input(|i| i.time));
+                            }
+                        },
+                        "Delay capture" => {
+                            if self.status == Countdown {
+                                self.cancel_countdown(frame);
+                            } else if self.status == Start || self.status == Image {
+                                self.start_countdown(frame, ctx.input(|i| i.time));
+                            }
+                        },
                         "Crop capture" => {
                             if self.image_to_save.is_some() {
                                 let w = self.image_to_save.as_ref().unwrap().width();
@@ -344,8 +559,10 @@ impl eframe::App for MyApp {
                             }
                         },
                         "Draw capture" => {
-                            if self.image_to_save.is_some() {
-                                self.draw_layer = Some(self.image_to_save.as_ref().unwrap().free_hand_draw_init());
+                            if let Some(image) = self.image_to_save.as_ref() {
+                                let (rl, dl) = image.free_hand_draw_init();
+                                self.rubber_layer = Some(rl);
+                                self.draw_layer = Some(dl);
                                 self.backup_image = self.image.clone();
                                 self.backup_image_to_save = self.image_to_save.clone();
                                 self.highlight = false;
@@ -402,6 +619,20 @@ impl eframe::App for MyApp {
             }
         }
 
+        if self.interval_running && (self.status == Start || self.status == Image) {
+            let now = ctx.input(|i| i.time);
+            if now >= self.interval_next {
+                frame.set_visible(false);
+                self.disabled_time = now;
+                self.prev = self.status;
+                self.instant_flag = true;
+                self.status = Hidden;
+            } else {
+                //repainting every second keeps the countdown shown in the image window up to date
+                ctx.request_repaint_after(Duration::from_secs_f64(f64::min(self.interval_next - now, 1.0)));
+            }
+        }
+
         match self.status {
             Start => {
                 initial_window(self, ctx, frame);
@@ -423,6 +654,18 @@ impl eframe::App for MyApp {
             },
             Text => {
                 text_window(self, ctx, frame);
+            },
+            Region => {
+                region_window(self, ctx, frame);
+            }
+            Record => {
+                recording_window(self, ctx, frame);
+            }
+            Scroll => {
+                scroll_window(self, ctx, frame);
+            }
+            Countdown => {
+                countdown_window(self, ctx, frame);
             }
         }
     }
@@ -430,7 +673,8 @@ impl eframe::App for MyApp {
     fn on_exit(&mut self, _gl: Option<&Context>) {
         match File::create("settings/settings"){
             Ok(mut f) => {
-                for el in self.hk.get_all_shortcuts(){
+                let shortcuts = self.hk.get_all_shortcuts();
+                for el in shortcuts.iter().take(7){
                     let (_,mut b,_) = el.id_gen();
                     b.push_str("\n");
                     f.write_all(b.as_bytes()).unwrap();
@@ -439,44 +683,271 @@ impl eframe::App for MyApp {
                 f.write_all(format!("{}\n", self.extension).as_bytes()).unwrap();
                 f.write_all(format!("{}\n", self.sel_font.as_ref().unwrap()).as_bytes()).unwrap();
                 f.write_all(format!("{}\n", self.sel_font_size).as_bytes()).unwrap();
-                f.write_all(format!("{} {} {}\n",
+                f.write_all(format!("{} {} {} {}\n",
                                     self.sel_color.color.0[0],
                                     self.sel_color.color.0[1],
-                                    self.sel_color.color.0[2]
+                                    self.sel_color.color.0[2],
+                                    self.sel_color.color.0[3]
                 ).as_bytes()).unwrap();
                 f.write_all(format!("{}\n", self.pencil_rubber_thickness).as_bytes()).unwrap();
-                f.write_all(format!("{} {} {}\n",
+                f.write_all(format!("{} {} {} {}\n",
                                     self.draw_color.color.0[0],
                                     self.draw_color.color.0[1],
-                                    self.draw_color.color.0[2]
+                                    self.draw_color.color.0[2],
+                                    self.draw_color.color.0[3]
                 ).as_bytes()).unwrap();
                 f.write_all(format!("{}\n", self.highlight_thickness).as_bytes()).unwrap();
-                f.write_all(format!("{} {} {}\n",
+                f.write_all(format!("{} {} {} {}\n",
                                     self.highlight_color.color.0[0],
                                     self.highlight_color.color.0[1],
-                                    self.highlight_color.color.0[2]
+                                    self.highlight_color.color.0[2],
+                                    self.highlight_color.color.0[3]
                 ).as_bytes()).unwrap();
                 f.write_all(format!("{}\n", self.save_path).as_bytes()).unwrap();
+                for el in shortcuts.iter().skip(7).take(1){
+                    let (_,mut b,_) = el.id_gen();
+                    b.push('\n');
+                    f.write_all(b.as_bytes()).unwrap();
+                }
+                f.write_all(format!("{}\n", self.include_cursor).as_bytes()).unwrap();
+                for el in shortcuts.iter().skip(8).take(1){
+                    let (_,mut b,_) = el.id_gen();
+                    b.push('\n');
+                    f.write_all(b.as_bytes()).unwrap();
+                }
+                f.write_all(format!("{}\n", self.interval_secs).as_bytes()).unwrap();
+                f.write_all(format!("{}\n", self.interval_shots).as_bytes()).unwrap();
+                for el in shortcuts.iter().skip(9).take(1){
+                    let (_,mut b,_) = el.id_gen();
+                    b.push('\n');
+                    f.write_all(b.as_bytes()).unwrap();
+                }
+                f.write_all(format!("{}\n", self.record_fps).as_bytes()).unwrap();
+                f.write_all(format!("{}\n", self.record_secs).as_bytes()).unwrap();
+                f.write_all(format!("{}\n", self.record_format).as_bytes()).unwrap();
+                for el in shortcuts.iter().skip(10).take(1){
+                    let (_,mut b,_) = el.id_gen();
+                    b.push('\n');
+                    f.write_all(b.as_bytes()).unwrap();
+                }
+                f.write_all(format!("{}\n", self.history_mb).as_bytes()).unwrap();
             }
             Err(_) => {}
         }
+        self.palette.save();
     }
 }
 
-fn take_capture(screen: &Screen) -> Option<Image> {
-    match screen.capture(){
-        Ok(sh) => {
-            sh.save().expect("Failed to save screenshot");
-            match Image::open(".tmp.png") {
-                Ok(im) => return Some(im),
-                Err(_) => {}
+impl MyApp {
+    ///Returns the save directory, ending with the path separator
+    fn save_location(&self) -> String {
+        let mut location = String::from(self.save_path.as_str());
+        if !location.ends_with(std::path::MAIN_SEPARATOR) {
+            location.push(std::path::MAIN_SEPARATOR);
+        }
+        location
+    }
+    ///Hides the window and starts recording the selected screen, or only the area in record_region if set
+    fn start_recording(&mut self, frame: &mut eframe::Frame, now: f64) {
+        if self.screens.is_empty() {
+            return;
+        }
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        self.record_name = format!("recording_{}", secs);
+        self.recording = Some(Recording::new());
+        self.record_stop = false;
+        self.record_result = None;
+        //the first frame is taken once the window is actually hidden
+        self.record_start = now + 0.2;
+        self.record_next = self.record_start;
+        frame.set_visible(false);
+        self.prev = self.status;
+        self.status = Record;
+    }
+    ///Turns the window into a small always-on-top overlay, showing the seconds left before a delayed capture
+    fn start_countdown(&mut self, frame: &mut eframe::Frame, now: f64) {
+        if self.screens.is_empty() {
+            return;
+        }
+        let info = frame.info().window_info;
+        self.window_backup = Some((info.position, info.size));
+        let geometry = self.screens[self.sel_screen].geometry();
+        frame.set_decorations(false);
+        frame.set_always_on_top(true);
+        frame.set_window_size(COUNTDOWN_SIZE);
+        frame.set_window_pos(egui::Pos2::new(
+            (geometry.x + geometry.width as i32) as f32 / geometry.scale_factor - COUNTDOWN_SIZE.x - 20.0,
+            geometry.y as f32 / geometry.scale_factor + 20.0
+        ));
+        self.disabled_time = now;
+        self.prev = self.status;
+        self.status = Countdown;
+    }
+    ///Stops the countdown of a delayed capture without taking it
+    fn cancel_countdown(&mut self, frame: &mut eframe::Frame) {
+        self.restore_window(frame);
+        self.status = self.prev;
+    }
+    ///Restores the decorations, position and size the window had before becoming an overlay
+    fn restore_window(&mut self, frame: &mut eframe::Frame) {
+        frame.set_fullscreen(false);
+        frame.set_decorations(true);
+        frame.set_always_on_top(false);
+        if let Some((position, size)) = self.window_backup.take() {
+            if let Some(position) = position {
+                frame.set_window_pos(position);
             }
-            return None
+            frame.set_window_size(size);
         }
-        Err(_) => {
+    }
+    ///Hides the window and starts a scrolling capture of the selected screen, or only of the area in scroll_region if set.
+    ///Frames are taken while the user scrolls, until the scroll capture hotkey is pressed again
+    fn start_scrolling(&mut self, frame: &mut eframe::Frame, now: f64) {
+        if self.screens.is_empty() {
+            return;
         }
+        self.scroll_frames.clear();
+        self.scroll_stop = false;
+        //the first frame is taken once the window is actually hidden
+        self.scroll_next = now + 0.2;
+        frame.set_visible(false);
+        self.prev = self.status;
+        self.status = Scroll;
+    }
+    ///Returns the style of the text placed by the text window
+    fn text_style(&self) -> TextStyle {
+        TextStyle {
+            align: self.text_align,
+            max_width: self.text_max_width,
+            background: if self.text_box { Some(self.text_box_color) } else { None },
+            padding: if self.text_box { self.text_padding } else { 0 },
+            outline: if self.text_outline { Some((self.text_outline_color, self.text_outline_width)) } else { None }
+        }
+    }
+    ///Returns the font selected for texts, labels and bubbles, along with its name
+    fn selected_font(&self) -> TextFont {
+        let name = self.sel_font.as_ref().unwrap();
+        TextFont { name: name.clone(), font: self.fonts.as_ref().unwrap()[name].clone() }
+    }
+    ///Reloads the image shown in the draw window, along with the handles of the selected annotation if any
+    fn refresh_selection(&mut self, ctx: &egui::Context) {
+        let im = self.backup_image_to_save.as_ref().unwrap();
+        let di = match self.sel_annotation {
+            Some((i, area)) => im.show_selected(i, area),
+            None => im.show()
+        };
+        self.backup_image = Some(ctx.load_texture(
+            "my-image",
+            get_image_from_memory(di, 0, 0, 1, 1),
+            Default::default()
+        ));
+    }
+    ///Starts a new interval capture: the first shot is taken right away,
+    ///the following ones every interval_secs seconds
+    fn start_interval(&mut self, now: f64) {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        self.interval_name = format!("interval_{}", secs);
+        self.interval_count = 0;
+        self.interval_next = now;
+        self.interval_running = true;
+    }
+    ///Saves the given capture as the next frame of the running interval capture and schedules the following one.
+    ///The interval capture stops when all the shots are taken or the frame cannot be saved
+    fn save_interval_frame(&mut self, im: &Image, now: f64) {
+        let location = self.save_location();
+        let name = format!("{}_{:04}", self.interval_name, self.interval_count + 1);
+        match im.save_as(location.as_str(), name.as_str(), self.extension) {
+            Ok(_) => {
+                self.interval_count += 1;
+                self.interval_next = now + self.interval_secs as f64;
+                if self.interval_shots != 0 && self.interval_count >= self.interval_shots {
+                    self.interval_running = false;
+                }
+            }
+            Err(_) => {
+                self.interval_running = false;
+            }
+        }
+    }
+}
+
+fn take_capture(screen: &Screen, cursor: Option<&DynamicImage>) -> Option<(Image, egui::ColorImage)> {
+    let sh = screen.capture(cursor).ok()?;
+    Some((Image::from_dynamic_image(sh.to_dynamic_image()), sh.to_color_image()))
+}
+
+fn take_desktop_capture(screens: &[Screen], cursor: Option<&DynamicImage>) -> Option<(Image, egui::ColorImage)> {
+    let sh = Screen::capture_virtual_desktop(screens, cursor).ok()?;
+    Some((Image::from_dynamic_image(sh.to_dynamic_image()), sh.to_color_image()))
+}
+
+fn take_window_capture(screens: &[Screen], window: &WindowInfo, cursor: Option<&DynamicImage>) -> Option<(Image, egui::ColorImage)> {
+    let sh = Screen::capture_window(screens, window, cursor).ok()?;
+    Some((Image::from_dynamic_image(sh.to_dynamic_image()), sh.to_color_image()))
+}
+
+///Shows the combo box used to choose the window to capture.
+///The list of the open windows is refreshed only when the popup is opened
+fn window_selector(app: &mut MyApp, ui: &mut egui::Ui) {
+    let response = egui::ComboBox::from_id_source("window-selector").width(150.0)
+        .selected_text(match app.sel_window.as_ref() {
+            Some(w) => format!("Window: {}", w.title.chars().take(20).collect::<String>()),
+            None => String::from("Window: none")
+        })
+        .show_ui(ui, |ui| {
+            for w in app.windows.iter() {
+                ui.selectable_value(&mut app.sel_window, Some(w.clone()), format!("{} ({}x{})", w.title.chars().take(40).collect::<String>(), w.width, w.height));
+            }
+        });
+    if response.response.clicked() && response.inner.is_some() {
+        app.windows = app.window_provider.windows().unwrap_or_default();
+        ui.ctx().request_repaint();
+    }
+}
+
+///Returns the index in the hotkeys of the one stored in the given line of the settings file.
+///The hotkeys added after the first seven follow the settings that were already there
+fn hotkey_index(line: usize) -> usize {
+    match line {
+        17 => 7,
+        19 => 8,
+        22 => 9,
+        26 => 10,
+        _ => line
+    }
+}
+
+///Parses a value of the settings file, returning the default if it is not valid
+fn parse_or<T: std::str::FromStr>(value: &str, default: T) -> T {
+    value.trim().parse().unwrap_or(default)
+}
+
+///Parses a color of the settings file, saved as "r g b a". The alpha is missing in the settings saved by older versions,
+///meaning an opaque color; the default is returned if the color is not valid
+fn parse_color(value: &str, default: Color) -> Color {
+    let channels: Vec<u8> = match value.split_whitespace().map(|c| c.parse()).collect() {
+        Ok(channels) => channels,
+        Err(_) => return default
+    };
+    match channels[..] {
+        [r, g, b] => Color { color: image::Rgba([r, g, b, 255]) },
+        [r, g, b, a] => Color { color: image::Rgba([r, g, b, a]) },
+        _ => default
     }
-    return None
+}
+
+///Returns whether undo (Ctrl+Z) and redo (Ctrl+Shift+Z or Ctrl+Y) were requested through the keyboard.
+///Ignored while a text field has the focus, since it handles them by itself
+fn history_shortcuts(ctx: &egui::Context) -> (bool, bool) {
+    if ctx.memory(|m| m.focus().is_some()) {
+        return (false, false);
+    }
+    ctx.input_mut(|i| {
+        let redo = i.consume_key(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::Z)
+            || i.consume_key(egui::Modifiers::COMMAND, egui::Key::Y);
+        let undo = i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z);
+        (undo, redo)
+    })
 }
 
 fn min_my(a: f32, b: f32) -> f32{
@@ -484,4 +955,37 @@ fn min_my(a: f32, b: f32) -> f32{
         return b;
     }
     a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_settings_keep_the_default() {
+        assert_eq!(parse_or(" 25 ", 3u32), 25);
+        assert_eq!(parse_or("", 3u32), 3);
+        assert_eq!(parse_or("-1", 3u32), 3);
+        assert_eq!(parse_or("ten", 512u32), 512);
+    }
+
+    #[test]
+    fn colors_with_and_without_alpha() {
+        let default = Color { color: image::Rgba([1, 2, 3, 4]) };
+        assert_eq!(parse_color("253 0 0", default), Color { color: image::Rgba([253, 0, 0, 255]) });
+        assert_eq!(parse_color("10 20 30 40", default), Color { color: image::Rgba([10, 20, 30, 40]) });
+        for value in ["", "10 20", "10 20 30 40 50", "10 20 300", "red"] {
+            assert_eq!(parse_color(value, default), default, "{}", value);
+        }
+    }
+
+    #[test]
+    fn hotkeys_keep_their_position() {
+        let lines = [0, 6, 17, 19, 22, 26].map(hotkey_index);
+        assert_eq!(lines, [0, 6, 7, 8, 9, 10]);
+        //an invalid hotkey is not parsed, so the default one stays in its place
+        assert!(parse(String::from("alt+shift+KeyZZ"), 7).is_err());
+        assert!(parse(String::from("alt+shift"), 7).is_err());
+        assert_eq!(parse(String::from("alt+shift+KeyR"), 7).unwrap().get_name(), "Region capture");
+    }
 }
\ No newline at end of file
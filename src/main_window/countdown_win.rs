@@ -0,0 +1,44 @@
+use std::time::Duration;
+use eframe::egui;
+use crate::main_window::Status::*;
+use crate::main_window::MyApp;
+
+///Size of the window while it shows the countdown
+pub const COUNTDOWN_SIZE: egui::Vec2 = egui::Vec2::new(180.0, 110.0);
+
+pub fn countdown_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::Frame){
+    let left = app.delay_secs as f64 - (ctx.input(|i| i.time) - app.disabled_time);
+
+    if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+        app.cancel_countdown(frame);
+        return;
+    }
+
+    if left <= 0.0 {
+        //the overlay is hidden first, the shot is taken by the hidden window in one of the next frames
+        app.restore_window(frame);
+        frame.set_visible(false);
+        app.disabled_time = ctx.input(|i| i.time);
+        app.instant_flag = true;
+        app.status = Hidden;
+        return;
+    }
+
+    let mut cancel = false;
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.style_mut().visuals.override_text_color = Some(egui::Color32::WHITE);
+        ui.vertical_centered(|ui| {
+            ui.label(egui::RichText::new(format!("{}", left.ceil())).size(40.0).strong());
+            ui.label("Esc or the delay hotkey to cancel");
+            if ui.button("✖ Cancel").clicked() {
+                cancel = true;
+            }
+        });
+    });
+
+    if cancel {
+        app.cancel_countdown(frame);
+    } else {
+        ctx.request_repaint_after(Duration::from_millis(100));
+    }
+}
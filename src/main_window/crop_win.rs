@@ -135,7 +135,6 @@ pub fn crop_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
             if ui.add(egui::Button::new("OK")).clicked() {
                 app.prev = app.status;
                 app.status = Image;
-                app.last_crop_data = Some(app.bl_ar.as_ref().unwrap().get_crop_data());
                 app.image_to_save.as_mut().unwrap().crop(app.bl_ar.take().unwrap());
                 app.image = Some(ctx.load_texture(
                     "my-image",
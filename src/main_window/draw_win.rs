@@ -5,9 +5,13 @@ use crate::main_window::Status::*;
 use crate::cursor_scaling::*;
 use crate::image_proc::get_image_from_memory;
 use crate::image_proc::Image;
-use crate::main_window::{DrawStatus, min_my, MyApp, Pointing, Shape};
+use crate::image_proc::annotation::{AnnotationKind, ArrowHead, RedactionStyle};
+use crate::main_window::eyedropper::{apply_picked, eyedropper, eyedropper_buttons};
+use crate::main_window::palette::{opacity_slider, palette_swatches};
+use crate::main_window::{DrawStatus, history_shortcuts, min_my, MyApp, Pointing, Shape};
 
 pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Frame){
+    let (undo_key, redo_key) = history_shortcuts(ctx);
 
     egui::CentralPanel::default().show(ctx, |ui| {
         egui::ScrollArea::vertical()
@@ -26,6 +30,7 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                     app.is_ratio_along_y = false;
                 }
 
+                let was_select = app.draw_status == DrawStatus::Select;
                 ui.horizontal(|ui| {
                     ui.style_mut().visuals.override_text_color = Some(egui::Color32::WHITE);
                     if ui.button("✏ Draw").on_hover_text("Free-hand drawing").clicked() {
@@ -45,7 +50,8 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                             }
                         }
 
-                        let dl = app.backup_image_to_save.as_ref().unwrap().free_hand_draw_init();
+                        let (rl, dl) = app.backup_image_to_save.as_ref().unwrap().free_hand_draw_init();
+                        app.rubber_layer = Some(rl);
                         app.draw_layer = Some(dl);
                         app.draw_status = DrawStatus::Draw;
                     }
@@ -66,7 +72,7 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                             }
                         }
 
-                        let (rl, dl) = app.backup_image_to_save.as_ref().unwrap().rubber_init(app.last_crop_data);
+                        let (rl, dl) = app.backup_image_to_save.as_ref().unwrap().rubber_init();
                         app.rubber_layer = Some(rl);
                         app.draw_layer = Some(dl);
                         app.draw_status = DrawStatus::Rubber;
@@ -92,6 +98,22 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                         app.draw_layer = Some(dl);
                         app.draw_status = DrawStatus::Highlight;
                     }
+                    if ui.button("☝ Select").on_hover_text("Select an annotation to move or resize it, Delete removes it").clicked() {
+                        app.rubber = false;
+                        if app.draw_layer.is_some() && app.rubber_layer.is_some() {
+                            if let DrawStatus::Shape(1) = app.draw_status {
+                                app.backup_image_to_save.as_mut().unwrap().shape_set(app.rubber_layer.take().unwrap(), app.draw_layer.take().unwrap());
+                                let di = app.backup_image_to_save.as_ref().unwrap().show();
+                                app.backup_image = Some(ctx.load_texture(
+                                    "my-image",
+                                    get_image_from_memory(di, 0, 0, 1, 1),
+                                    Default::default()
+                                ));
+                            }
+                        }
+                        app.sel_annotation = None;
+                        app.draw_status = DrawStatus::Select;
+                    }
 
                     match app.draw_status{
                         DrawStatus::Draw | DrawStatus::Rubber => {
@@ -102,12 +124,55 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                         }
                         DrawStatus::Shape(_) => {
                             match app.which_shape.as_ref().unwrap(){
-                                Shape::EmptyRectangle | Shape::EmptyCircle => {
+                                Shape::FilledRectangle => {
+                                    ui.add(egui::Slider::new(&mut app.corner_radius, 0..=100).text("Corner radius"));
+                                }
+                                Shape::EmptyRectangle => {
+                                    ui.add(egui::Slider::new(&mut app.pencil_rubber_thickness, 1..=80).text("Trait size"));
+                                    ui.add(egui::Slider::new(&mut app.corner_radius, 0..=100).text("Corner radius"));
+                                }
+                                Shape::EmptyEllipse => {
                                     ui.add(egui::Slider::new(&mut app.pencil_rubber_thickness, 1..=80).text("Trait size"));
                                 }
+                                Shape::Redact(style) => {
+                                    //the shape being placed is redrawn with the new style in the next frame
+                                    let mut style = *style;
+                                    egui::ComboBox::from_label("Redaction").width(80.0)
+                                        .selected_text(format!("{}", style))
+                                        .show_ui(ui, |ui| {
+                                            ui.selectable_value(&mut style, RedactionStyle::Pixelate, "Pixelate");
+                                            ui.selectable_value(&mut style, RedactionStyle::Blur, "Blur")
+                                                .on_hover_text("Cosmetic only: blurred text can often be recovered, use Pixelate or Fill for secrets");
+                                            ui.selectable_value(&mut style, RedactionStyle::Fill, "Fill");
+                                        });
+                                    app.which_shape = Some(Shape::Redact(style));
+                                }
+                                Shape::Step => {
+                                    ui.add(egui::Slider::new(&mut app.marker_size, 16..=160).text("Marker size"));
+                                }
+                                Shape::Magnifier => {
+                                    ui.add(egui::Slider::new(&mut app.pencil_rubber_thickness, 1..=20).text("Border size"));
+                                    ui.add(egui::Slider::new(&mut app.magnifier_zoom, 2..=6).text("Zoom"));
+                                }
+                                Shape::Label | Shape::Bubble => {
+                                    //the text is fitted to the shape being placed in the next frame
+                                    ui.add(egui::TextEdit::multiline(&mut app.bubble_text).desired_rows(1));
+                                }
                                 _ => {}
                             }
                         }
+                        DrawStatus::Line => {
+                            ui.add(egui::Slider::new(&mut app.pencil_rubber_thickness, 1..=80).text("Trait size"));
+                            egui::ComboBox::from_label("Head").width(80.0)
+                                .selected_text(format!("{}", app.line_head))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut app.line_head, ArrowHead::None, "None");
+                                    ui.selectable_value(&mut app.line_head, ArrowHead::Open, "Open");
+                                    ui.selectable_value(&mut app.line_head, ArrowHead::Filled, "Filled");
+                                    ui.selectable_value(&mut app.line_head, ArrowHead::Double, "Double");
+                                });
+                        }
+                        DrawStatus::Select => {}
                     }
 
                 });
@@ -153,7 +218,7 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                         app.cur_mouse_pos = None;
                     }
 
-                    if ui.button("⏺").on_hover_text("Filled circle").clicked() {
+                    if ui.button("⏺").on_hover_text("Filled ellipse").clicked() {
                         if app.draw_layer.is_some() && app.rubber_layer.is_some() {
                             match app.draw_status {
                                 DrawStatus::Shape(1) => {
@@ -168,12 +233,12 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                                 _ => {}
                             }
                         }
-                        app.which_shape = Some(Shape::FilledCircle);
+                        app.which_shape = Some(Shape::FilledEllipse);
                         app.draw_status = DrawStatus::Shape(0);
                         app.cur_mouse_pos = None;
                     }
 
-                    if ui.button("○").on_hover_text("Empty circle").clicked() {
+                    if ui.button("○").on_hover_text("Empty ellipse").clicked() {
                         if app.draw_layer.is_some() && app.rubber_layer.is_some() {
                             match app.draw_status {
                                 DrawStatus::Shape(1) => {
@@ -188,7 +253,7 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                                 _ => {}
                             }
                         }
-                        app.which_shape = Some(Shape::EmptyCircle);
+                        app.which_shape = Some(Shape::EmptyEllipse);
                         app.draw_status = DrawStatus::Shape(0);
                         app.cur_mouse_pos = None;
                     }
@@ -273,6 +338,133 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                         app.cur_mouse_pos = None;
                     }
 
+                    if ui.button("▦").on_hover_text("Redact: pixelate, blur or fill an area to hide its content").clicked() {
+                        if app.draw_layer.is_some() && app.rubber_layer.is_some() {
+                            if let DrawStatus::Shape(1) = app.draw_status {
+                                app.backup_image_to_save.as_mut().unwrap().shape_set(app.rubber_layer.take().unwrap(), app.draw_layer.take().unwrap());
+                                let di = app.backup_image_to_save.as_ref().unwrap().show();
+                                app.backup_image = Some(ctx.load_texture(
+                                    "my-image",
+                                    get_image_from_memory(di, 0, 0, 1, 1),
+                                    Default::default()
+                                ));
+                            }
+                        }
+                        app.which_shape = Some(Shape::Redact(RedactionStyle::Pixelate));
+                        app.draw_status = DrawStatus::Shape(0);
+                        app.cur_mouse_pos = None;
+                    }
+
+                    if ui.button("①").on_hover_text("Numbered step marker: each click places the next number").clicked() {
+                        if app.draw_layer.is_some() && app.rubber_layer.is_some() {
+                            if let DrawStatus::Shape(1) = app.draw_status {
+                                app.backup_image_to_save.as_mut().unwrap().shape_set(app.rubber_layer.take().unwrap(), app.draw_layer.take().unwrap());
+                                let di = app.backup_image_to_save.as_ref().unwrap().show();
+                                app.backup_image = Some(ctx.load_texture(
+                                    "my-image",
+                                    get_image_from_memory(di, 0, 0, 1, 1),
+                                    Default::default()
+                                ));
+                            }
+                        }
+                        app.which_shape = Some(Shape::Step);
+                        app.draw_status = DrawStatus::Shape(0);
+                        app.cur_mouse_pos = None;
+                    }
+
+                    if ui.button("🔍").on_hover_text("Magnifier: shows an enlarged copy of an area beside it").clicked() {
+                        if app.draw_layer.is_some() && app.rubber_layer.is_some() {
+                            if let DrawStatus::Shape(1) = app.draw_status {
+                                app.backup_image_to_save.as_mut().unwrap().shape_set(app.rubber_layer.take().unwrap(), app.draw_layer.take().unwrap());
+                                let di = app.backup_image_to_save.as_ref().unwrap().show();
+                                app.backup_image = Some(ctx.load_texture(
+                                    "my-image",
+                                    get_image_from_memory(di, 0, 0, 1, 1),
+                                    Default::default()
+                                ));
+                            }
+                        }
+                        app.which_shape = Some(Shape::Magnifier);
+                        app.draw_status = DrawStatus::Shape(0);
+                        app.cur_mouse_pos = None;
+                    }
+
+                    if ui.button("▭").on_hover_text("Label: rounded rectangle holding a text").clicked() {
+                        if app.draw_layer.is_some() && app.rubber_layer.is_some() {
+                            if let DrawStatus::Shape(1) = app.draw_status {
+                                app.backup_image_to_save.as_mut().unwrap().shape_set(app.rubber_layer.take().unwrap(), app.draw_layer.take().unwrap());
+                                let di = app.backup_image_to_save.as_ref().unwrap().show();
+                                app.backup_image = Some(ctx.load_texture(
+                                    "my-image",
+                                    get_image_from_memory(di, 0, 0, 1, 1),
+                                    Default::default()
+                                ));
+                            }
+                        }
+                        app.which_shape = Some(Shape::Label);
+                        app.draw_status = DrawStatus::Shape(0);
+                        app.cur_mouse_pos = None;
+                    }
+
+                    if ui.button("💬").on_hover_text("Speech bubble: drag the tip of its tail to point it").clicked() {
+                        if app.draw_layer.is_some() && app.rubber_layer.is_some() {
+                            if let DrawStatus::Shape(1) = app.draw_status {
+                                app.backup_image_to_save.as_mut().unwrap().shape_set(app.rubber_layer.take().unwrap(), app.draw_layer.take().unwrap());
+                                let di = app.backup_image_to_save.as_ref().unwrap().show();
+                                app.backup_image = Some(ctx.load_texture(
+                                    "my-image",
+                                    get_image_from_memory(di, 0, 0, 1, 1),
+                                    Default::default()
+                                ));
+                            }
+                        }
+                        app.which_shape = Some(Shape::Bubble);
+                        app.draw_status = DrawStatus::Shape(0);
+                        app.cur_mouse_pos = None;
+                    }
+
+                    if ui.button("╱").on_hover_text("Straight line: drag from start to end").clicked() {
+                        app.rubber = false;
+                        if app.draw_layer.is_some() && app.rubber_layer.is_some() {
+                            if let DrawStatus::Shape(1) = app.draw_status {
+                                app.backup_image_to_save.as_mut().unwrap().shape_set(app.rubber_layer.take().unwrap(), app.draw_layer.take().unwrap());
+                                let di = app.backup_image_to_save.as_ref().unwrap().show();
+                                app.backup_image = Some(ctx.load_texture(
+                                    "my-image",
+                                    get_image_from_memory(di, 0, 0, 1, 1),
+                                    Default::default()
+                                ));
+                            }
+                        }
+                        app.line_head = ArrowHead::None;
+                        app.rubber_layer = None;
+                        app.draw_layer = None;
+                        app.draw_status = DrawStatus::Line;
+                        app.cur_mouse_pos = None;
+                    }
+
+                    if ui.button("➚").on_hover_text("Arrow: drag from tail to head").clicked() {
+                        app.rubber = false;
+                        if app.draw_layer.is_some() && app.rubber_layer.is_some() {
+                            if let DrawStatus::Shape(1) = app.draw_status {
+                                app.backup_image_to_save.as_mut().unwrap().shape_set(app.rubber_layer.take().unwrap(), app.draw_layer.take().unwrap());
+                                let di = app.backup_image_to_save.as_ref().unwrap().show();
+                                app.backup_image = Some(ctx.load_texture(
+                                    "my-image",
+                                    get_image_from_memory(di, 0, 0, 1, 1),
+                                    Default::default()
+                                ));
+                            }
+                        }
+                        if app.line_head == ArrowHead::None {
+                            app.line_head = ArrowHead::Filled;
+                        }
+                        app.rubber_layer = None;
+                        app.draw_layer = None;
+                        app.draw_status = DrawStatus::Line;
+                        app.cur_mouse_pos = None;
+                    }
+
 
                     if !app.rubber && !app.is_sel_color && ui.add(egui::Button::new("Edit Color")).clicked() {
                         app.is_sel_color = true;
@@ -282,7 +474,7 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                         app.dropdown_on = true;
 
                         match app.draw_status{
-                            DrawStatus::Draw |  DrawStatus::Shape(_) | DrawStatus::Rubber=> {
+                            DrawStatus::Draw |  DrawStatus::Shape(_) | DrawStatus::Rubber | DrawStatus::Select | DrawStatus::Line => {
                                 let mut color_vec = [app.draw_color.color.0[0], app.draw_color.color.0[1], app.draw_color.color.0[2]];
                                 egui::widgets::color_picker::color_edit_button_srgb(ui, &mut color_vec);
 
@@ -303,12 +495,49 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                         if ui.add(egui::Button::new("OK")).clicked() {
                             app.is_sel_color = false;
                             app.dropdown_on = false;
+                            match app.draw_status {
+                                DrawStatus::Highlight => app.palette.use_color(app.highlight_color),
+                                _ => app.palette.use_color(app.draw_color)
+                            }
+                        }
+                    }
+
+                    if !app.rubber {
+                        eyedropper_buttons(app, ui);
+                        let save_dir = app.save_location();
+                        match app.draw_status {
+                            DrawStatus::Highlight => {
+                                opacity_slider(ui, &mut app.highlight_color);
+                                palette_swatches(&mut app.palette, ui, &mut app.highlight_color, &save_dir);
+                            }
+                            _ => {
+                                opacity_slider(ui, &mut app.draw_color);
+                                palette_swatches(&mut app.palette, ui, &mut app.draw_color, &save_dir);
+                            }
                         }
                     }
                 });
 
+                //the handles of the selection are removed when another tool is chosen
+                if was_select && app.draw_status != DrawStatus::Select {
+                    app.sel_annotation = None;
+                    app.refresh_selection(ctx);
+                }
+
                 let di;
                 let offset = (ctx.screen_rect().width() - app.backup_image.as_ref().unwrap().size_vec2().x * app.window_image_ratio) / 2.0 -5.0;
+                if let Some(color) = eyedropper(app, ctx, offset, 50.0) {
+                    match app.draw_status {
+                        DrawStatus::Highlight => {
+                            apply_picked(&mut app.highlight_color, color);
+                            app.palette.use_color(app.highlight_color);
+                        }
+                        _ => {
+                            apply_picked(&mut app.draw_color, color);
+                            app.palette.use_color(app.draw_color);
+                        }
+                    }
+                }
                 match ctx.input(|i| i.pointer.hover_pos()) {
                     None => {}
                     Some(pos) => {
@@ -346,17 +575,17 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                                         match app.draw_status {
                                             DrawStatus::Draw => {
                                                 app.prev_edge = Some(Image::draw_point(app.draw_layer.as_mut().unwrap(), app.prev_edge.clone(), (cur.0 as i32, cur.1 as i32), (app.pencil_rubber_thickness as f32) as i32, &app.draw_color));
-                                                di = app.draw_layer.as_ref().unwrap().show();
+                                                di = app.draw_layer.as_ref().unwrap().show_over(app.rubber_layer.as_ref().unwrap());
                                                 app.backup_image = Some(ctx.load_texture("my-image", get_image_from_memory(di, 0, 0, 1, 1), Default::default()));
                                             },
                                             DrawStatus::Rubber => {
                                                 app.prev_edge = Some(Image::rubber(app.draw_layer.as_mut().unwrap(), app.prev_edge.clone(), (cur.0 as i32, cur.1 as i32), app.pencil_rubber_thickness));
-                                                di = app.draw_layer.as_ref().unwrap().show_rubber(app.rubber_layer.as_ref().unwrap());
+                                                di = app.draw_layer.as_ref().unwrap().show_over(app.rubber_layer.as_ref().unwrap());
                                                 app.backup_image = Some(ctx.load_texture("my-image", get_image_from_memory(di, 0, 0, 1, 1), Default::default()));
                                             },
                                             DrawStatus::Highlight => {
                                                 app.prev_edge = Some(Image::highlight(app.draw_layer.as_mut().unwrap(), app.prev_edge.clone(), (cur.0 as i32, cur.1 as i32), (app.highlight_thickness as f32) as i32, &app.highlight_color));
-                                                di = app.draw_layer.as_ref().unwrap().show_higlight(app.rubber_layer.as_ref().unwrap());
+                                                di = app.draw_layer.as_ref().unwrap().show_over(app.rubber_layer.as_ref().unwrap());
                                                 app.backup_image = Some(ctx.load_texture("my-image", get_image_from_memory(di, 0, 0, 1, 1), Default::default()));
                                             },
                                             _ => {}
@@ -367,17 +596,21 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                                         app.any_pressed = false;
                                         match app.draw_status {
                                             DrawStatus::Draw => {
-                                                app.backup_image_to_save.as_mut().unwrap().free_hand_draw_set(app.draw_layer.take().unwrap(), app.prev_edge.unwrap().clone().2, (app.pencil_rubber_thickness as f32 *1.5) as i32, &app.draw_color);
-                                                app.draw_layer = Some(app.backup_image_to_save.as_ref().unwrap().free_hand_draw_init());
+                                                app.backup_image_to_save.as_mut().unwrap().free_hand_draw_set(app.draw_layer.take().unwrap(), app.pencil_rubber_thickness, &app.draw_color);
+                                                app.palette.use_color(app.draw_color);
+                                                let (rl, dl) = app.backup_image_to_save.as_ref().unwrap().free_hand_draw_init();
+                                                app.rubber_layer = Some(rl);
+                                                app.draw_layer = Some(dl);
                                             },
                                             DrawStatus::Rubber => {
-                                                app.backup_image_to_save.as_mut().unwrap().rubber_set(app.draw_layer.take().unwrap(), app.rubber_layer.as_ref().unwrap(), app.prev_edge.unwrap().clone().2, app.pencil_rubber_thickness*2);
-                                                let (rl, dl) = app.backup_image_to_save.as_ref().unwrap().rubber_init(app.last_crop_data);
+                                                app.backup_image_to_save.as_mut().unwrap().rubber_set(app.draw_layer.take().unwrap(), app.rubber_layer.as_ref().unwrap(), app.pencil_rubber_thickness);
+                                                let (rl, dl) = app.backup_image_to_save.as_ref().unwrap().rubber_init();
                                                 app.rubber_layer = Some(rl);
                                                 app.draw_layer = Some(dl);
                                             },
                                             DrawStatus::Highlight => {
-                                                app.backup_image_to_save.as_mut().unwrap().highlight_set(app.draw_layer.take().unwrap(), app.rubber_layer.as_ref().unwrap(), app.prev_edge.unwrap().clone().2, (app.highlight_thickness as f32 * 1.5) as i32, &app.highlight_color);
+                                                app.backup_image_to_save.as_mut().unwrap().highlight_set(app.draw_layer.take().unwrap(), app.rubber_layer.as_ref().unwrap(), app.highlight_thickness, &app.highlight_color);
+                                                app.palette.use_color(app.highlight_color);
                                                 let (rl, dl) = app.backup_image_to_save.as_ref().unwrap().highlight_init();
                                                 app.rubber_layer = Some(rl);
                                                 app.draw_layer = Some(dl);
@@ -390,6 +623,38 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                             },
                             DrawStatus::Shape(click) => {
                                 match click {
+                                    //the tail of a speech bubble is dragged by its tip, the rest of the bubble stays in place
+                                    1 if app.which_shape == Some(Shape::Bubble) && grab_tail(app, ctx, (pos.x - offset, pos.y + app.scroll_qty - 50.0)) => {
+                                        let tip = cursor_position(((pos.x - offset).max(0.0) as u32, (pos.y + app.scroll_qty - 50.0).max(0.0) as u32), app.window_image_ratio);
+                                        let tip = (i32::min(tip.0 as i32, image_size.x as i32 - 1), i32::min(tip.1 as i32, image_size.y as i32 - 1));
+                                        app.bubble_tail = Some(tip);
+                                        let ((x, y), (w, h)) = app.draw_layer.as_ref().unwrap().get_pos_size().unwrap();
+                                        let font = app.selected_font();
+                                        Image::draw_bubble(app.draw_layer.as_mut().unwrap(), ((x + w / 2) as i32, (y + h / 2) as i32), (w as i32, h as i32),
+                                                           app.bubble_tail, &app.bubble_text, font, &app.draw_color);
+                                        let di = app.draw_layer.as_ref().unwrap().show_shape(app.rubber_layer.as_ref().unwrap());
+                                        app.backup_image = Some(ctx.load_texture("my-image", get_image_from_memory(di, 0, 0, 1, 1), Default::default()));
+
+                                        if ctx.input(|i| i.pointer.any_released()) {
+                                            app.tail_drag = false;
+                                            app.any_pressed = false;
+                                        }
+                                    },
+                                    //step markers are placed as soon as the image is clicked, without resizing
+                                    0 if app.which_shape == Some(Shape::Step) => {
+                                        let clicked = pos.x - offset > 0.0 && pos.x - offset < image_size.x * app.window_image_ratio
+                                            && pos.y+app.scroll_qty > 50.0 && pos.y+app.scroll_qty < (image_size.y * app.window_image_ratio + 50.0) && !app.dropdown_on
+                                            && ctx.input(|i| i.pointer.any_click());
+                                        if clicked {
+                                            let center = cursor_position(((pos.x - offset) as u32, (pos.y+app.scroll_qty - 50.0) as u32), app.window_image_ratio);
+                                            let font_name = app.sel_font.as_ref().unwrap().as_str();
+                                            app.backup_image_to_save.as_mut().unwrap().put_marker((center.0 as i32, center.1 as i32), app.marker_size, &app.draw_color,
+                                                                                                  font_name, app.fonts.as_ref().unwrap().get(font_name).unwrap());
+                                            app.palette.use_color(app.draw_color);
+                                            let di = app.backup_image_to_save.as_ref().unwrap().show();
+                                            app.backup_image = Some(ctx.load_texture("my-image", get_image_from_memory(di, 0, 0, 1, 1), Default::default()));
+                                        }
+                                    },
                                     0 => {
                                         if pos.x - offset > 0.0 && pos.x - offset < image_size.x * app.window_image_ratio
                                             && pos.y+app.scroll_qty > 50.0 && pos.y+app.scroll_qty < (image_size.y * app.window_image_ratio + 50.0) && !app.dropdown_on { //&& !app.any_pressed
@@ -414,12 +679,26 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                                                     let (rl, dl) = app.backup_image_to_save.as_ref().unwrap().shape_init(start, (300, 200));
                                                     app.rubber_layer = Some(rl);
                                                     app.draw_layer = Some(dl);
+                                                    app.palette.use_color(app.draw_color);
 
                                                     match app.which_shape.unwrap() {
-                                                        Shape::FilledRectangle => Image::draw_filled_rectangle(app.draw_layer.as_mut().unwrap(), app.rubber_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), (300, 200), &app.draw_color),
-                                                        Shape::EmptyRectangle => Image::draw_empty_rectangle(app.draw_layer.as_mut().unwrap(), app.rubber_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), (300, 200), &app.draw_color, app.pencil_rubber_thickness),
-                                                        Shape::FilledCircle => Image::draw_filled_circle(app.draw_layer.as_mut().unwrap(), app.rubber_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), 200, &app.draw_color),
-                                                        Shape::EmptyCircle => Image::draw_empty_circle(app.draw_layer.as_mut().unwrap(), app.rubber_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), 200, &app.draw_color, app.pencil_rubber_thickness),
+                                                        Shape::FilledRectangle => Image::draw_filled_rectangle(app.draw_layer.as_mut().unwrap(), app.rubber_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), (300, 200), app.corner_radius, &app.draw_color),
+                                                        Shape::EmptyRectangle => Image::draw_empty_rectangle(app.draw_layer.as_mut().unwrap(), app.rubber_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), (300, 200), app.corner_radius, &app.draw_color, app.pencil_rubber_thickness),
+                                                        Shape::FilledEllipse => Image::draw_filled_ellipse(app.draw_layer.as_mut().unwrap(), app.rubber_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), (300, 200), &app.draw_color),
+                                                        Shape::EmptyEllipse => Image::draw_empty_ellipse(app.draw_layer.as_mut().unwrap(), app.rubber_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), (300, 200), &app.draw_color, app.pencil_rubber_thickness),
+                                                        Shape::Redact(style) => Image::draw_redaction(app.draw_layer.as_mut().unwrap(), app.rubber_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), (300, 200), style, &app.draw_color),
+                                                        Shape::Step => {}
+                                                        Shape::Magnifier => Image::draw_magnifier(app.draw_layer.as_mut().unwrap(), app.rubber_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), (120, 80), app.magnifier_zoom, &app.draw_color, app.pencil_rubber_thickness),
+                                                        Shape::Label => {
+                                                            let font = app.selected_font();
+                                                            Image::draw_bubble(app.draw_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), (300, 200), None, &app.bubble_text, font, &app.draw_color);
+                                                        }
+                                                        Shape::Bubble => {
+                                                            //the tail starts pointing down-left of the bubble
+                                                            app.bubble_tail = Some((start.0 as i32 - 100, i32::min(start.1 as i32 + 180, image_size.y as i32 - 1)));
+                                                            let font = app.selected_font();
+                                                            Image::draw_bubble(app.draw_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), (300, 200), app.bubble_tail, &app.bubble_text, font, &app.draw_color);
+                                                        }
                                                         Shape::Arrow(dir) => match dir {
                                                             Pointing::Left => Image::draw_filled_left_arrow(app.draw_layer.as_mut().unwrap(), app.rubber_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), (300, 200), &app.draw_color),
                                                             Pointing::Right => Image::draw_filled_right_arrow(app.draw_layer.as_mut().unwrap(), app.rubber_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), (300, 200), &app.draw_color),
@@ -515,46 +794,49 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                                                 None => {}
                                                 Some(_p) => {
                                                     let ((x, y), (w, h)) = app.draw_layer.as_ref().unwrap().get_pos_size().unwrap();
-                                                    let ((xn, yn), (wn, hn)) = match app.which_shape.as_ref().unwrap() {
-                                                        Shape::FilledCircle | Shape::EmptyCircle => {
-                                                            get_new_area_circle(
-                                                                app.prev_mouse_pos.unwrap(),
-                                                                app.cur_mouse_pos.unwrap(),
-                                                                (x, y),
-                                                                w,
-                                                                (app.backup_image_to_save.as_ref().unwrap().width(), app.backup_image_to_save.as_ref().unwrap().height()),
-                                                                app.corner.unwrap()
-                                                            )
-                                                        },
-                                                        _ => {
-                                                            get_new_area(
-                                                                app.prev_mouse_pos.unwrap(),
-                                                                app.cur_mouse_pos.unwrap(),
-                                                                (x, y),
-                                                                (w, h),
-                                                                (app.backup_image_to_save.as_ref().unwrap().width(), app.backup_image_to_save.as_ref().unwrap().height()),
-                                                                app.corner.unwrap()
-                                                            )
-                                                        }
-                                                    };
+                                                    let limits = (app.backup_image_to_save.as_ref().unwrap().width(), app.backup_image_to_save.as_ref().unwrap().height());
+                                                    let area = get_new_area(
+                                                        app.prev_mouse_pos.unwrap(),
+                                                        app.cur_mouse_pos.unwrap(),
+                                                        (x, y),
+                                                        (w, h),
+                                                        limits,
+                                                        app.corner.unwrap()
+                                                    );
+                                                    let ((xn, yn), (wn, hn)) = lock_aspect(app, ctx, area, (w, h), limits);
 
                                                     match app.which_shape.unwrap() {
                                                         Shape::FilledRectangle => Image::draw_filled_rectangle(app.draw_layer.as_mut().unwrap(),
                                                                                                                app.rubber_layer.as_mut().unwrap(),
                                                                                                                ((xn + wn / 2) as i32, (yn + hn / 2) as i32),
-                                                                                                               (wn as i32, hn as i32), &app.draw_color),
+                                                                                                               (wn as i32, hn as i32), app.corner_radius, &app.draw_color),
                                                         Shape::EmptyRectangle => Image::draw_empty_rectangle(app.draw_layer.as_mut().unwrap(),
                                                                                                              app.rubber_layer.as_mut().unwrap(),
                                                                                                              ((xn + wn / 2) as i32, (yn + hn / 2) as i32),
-                                                                                                             (wn as i32, hn as i32), &app.draw_color, app.pencil_rubber_thickness),
-                                                        Shape::FilledCircle => Image::draw_filled_circle(app.draw_layer.as_mut().unwrap(),
+                                                                                                             (wn as i32, hn as i32), app.corner_radius, &app.draw_color, app.pencil_rubber_thickness),
+                                                        Shape::FilledEllipse => Image::draw_filled_ellipse(app.draw_layer.as_mut().unwrap(),
                                                                                                          app.rubber_layer.as_mut().unwrap(),
                                                                                                          ((xn + wn / 2) as i32, (yn + hn / 2) as i32),
-                                                                                                         wn as i32, &app.draw_color),
-                                                        Shape::EmptyCircle => Image::draw_empty_circle(app.draw_layer.as_mut().unwrap(),
+                                                                                                         (wn as i32, hn as i32), &app.draw_color),
+                                                        Shape::EmptyEllipse => Image::draw_empty_ellipse(app.draw_layer.as_mut().unwrap(),
                                                                                                        app.rubber_layer.as_mut().unwrap(),
                                                                                                        ((xn + wn / 2) as i32, (yn + hn / 2) as i32),
-                                                                                                       wn as i32, &app.draw_color, app.pencil_rubber_thickness),
+                                                                                                       (wn as i32, hn as i32), &app.draw_color, app.pencil_rubber_thickness),
+                                                        Shape::Redact(style) => Image::draw_redaction(app.draw_layer.as_mut().unwrap(),
+                                                                                                      app.rubber_layer.as_mut().unwrap(),
+                                                                                                      ((xn + wn / 2) as i32, (yn + hn / 2) as i32),
+                                                                                                      (wn as i32, hn as i32), style, &app.draw_color),
+                                                        Shape::Step => {}
+                                                        Shape::Magnifier => Image::draw_magnifier(app.draw_layer.as_mut().unwrap(),
+                                                                                                  app.rubber_layer.as_mut().unwrap(),
+                                                                                                  ((xn + wn / 2) as i32, (yn + hn / 2) as i32),
+                                                                                                  (wn as i32, hn as i32), app.magnifier_zoom, &app.draw_color, app.pencil_rubber_thickness),
+                                                        Shape::Label | Shape::Bubble => {
+                                                            let tail = if app.which_shape == Some(Shape::Bubble) { app.bubble_tail } else { None };
+                                                            let font = app.selected_font();
+                                                            Image::draw_bubble(app.draw_layer.as_mut().unwrap(), ((xn + wn / 2) as i32, (yn + hn / 2) as i32), (wn as i32, hn as i32),
+                                                                               tail, &app.bubble_text, font, &app.draw_color);
+                                                        }
                                                         Shape::Arrow(dir) => match dir {
                                                             Pointing::Left => Image::draw_filled_left_arrow(app.draw_layer.as_mut().unwrap(),
                                                                                                             app.rubber_layer.as_mut().unwrap(),
@@ -601,19 +883,34 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                                                 Shape::FilledRectangle => Image::draw_filled_rectangle(app.draw_layer.as_mut().unwrap(),
                                                                                                        app.rubber_layer.as_mut().unwrap(),
                                                                                                        ((xn + wn / 2) as i32, (yn + hn / 2) as i32),
-                                                                                                       (wn as i32, hn as i32), &app.draw_color),
+                                                                                                       (wn as i32, hn as i32), app.corner_radius, &app.draw_color),
                                                 Shape::EmptyRectangle => Image::draw_empty_rectangle(app.draw_layer.as_mut().unwrap(),
                                                                                                      app.rubber_layer.as_mut().unwrap(),
                                                                                                      ((xn + wn / 2) as i32, (yn + hn / 2) as i32),
-                                                                                                     (wn as i32, hn as i32), &app.draw_color, app.pencil_rubber_thickness),
-                                                Shape::FilledCircle => Image::draw_filled_circle(app.draw_layer.as_mut().unwrap(),
+                                                                                                     (wn as i32, hn as i32), app.corner_radius, &app.draw_color, app.pencil_rubber_thickness),
+                                                Shape::FilledEllipse => Image::draw_filled_ellipse(app.draw_layer.as_mut().unwrap(),
                                                                                                  app.rubber_layer.as_mut().unwrap(),
                                                                                                  ((xn + wn / 2) as i32, (yn + hn / 2) as i32),
-                                                                                                 wn as i32, &app.draw_color),
-                                                Shape::EmptyCircle => Image::draw_empty_circle(app.draw_layer.as_mut().unwrap(),
+                                                                                                 (wn as i32, hn as i32), &app.draw_color),
+                                                Shape::EmptyEllipse => Image::draw_empty_ellipse(app.draw_layer.as_mut().unwrap(),
                                                                                                app.rubber_layer.as_mut().unwrap(),
                                                                                                ((xn + wn / 2) as i32, (yn + hn / 2) as i32),
-                                                                                               wn as i32, &app.draw_color, app.pencil_rubber_thickness),
+                                                                                               (wn as i32, hn as i32), &app.draw_color, app.pencil_rubber_thickness),
+                                                Shape::Redact(style) => Image::draw_redaction(app.draw_layer.as_mut().unwrap(),
+                                                                                              app.rubber_layer.as_mut().unwrap(),
+                                                                                              ((xn + wn / 2) as i32, (yn + hn / 2) as i32),
+                                                                                              (wn as i32, hn as i32), style, &app.draw_color),
+                                                Shape::Step => {}
+                                                Shape::Magnifier => Image::draw_magnifier(app.draw_layer.as_mut().unwrap(),
+                                                                                          app.rubber_layer.as_mut().unwrap(),
+                                                                                          ((xn + wn / 2) as i32, (yn + hn / 2) as i32),
+                                                                                          (wn as i32, hn as i32), app.magnifier_zoom, &app.draw_color, app.pencil_rubber_thickness),
+                                                Shape::Label | Shape::Bubble => {
+                                                    let tail = if app.which_shape == Some(Shape::Bubble) { app.bubble_tail } else { None };
+                                                    let font = app.selected_font();
+                                                    Image::draw_bubble(app.draw_layer.as_mut().unwrap(), ((xn + wn / 2) as i32, (yn + hn / 2) as i32), (wn as i32, hn as i32),
+                                                                       tail, &app.bubble_text, font, &app.draw_color);
+                                                }
                                                 Shape::Arrow(dir) => match dir {
                                                     Pointing::Left => Image::draw_filled_left_arrow(app.draw_layer.as_mut().unwrap(),
                                                                                                     app.rubber_layer.as_mut().unwrap(),
@@ -649,16 +946,109 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                                             app.corner = None;
                                             app.prev_mouse_pos = None;
                                             app.cur_mouse_pos = None;
-
+                                            app.resize_ratio = None;
                                         }
                                     }
                                     _ => {}
                                 }
                             },
+                            DrawStatus::Select => {
+                                if pos.x - offset > 0.0 && pos.x - offset < image_size.x * app.window_image_ratio
+                                    && pos.y+app.scroll_qty > 50.0 && pos.y+app.scroll_qty < (image_size.y * app.window_image_ratio + 50.0) && !app.dropdown_on {
+                                    let cur = cursor_position(((pos.x - offset) as u32, (pos.y+app.scroll_qty - 50.0) as u32), app.window_image_ratio);
+                                    let im = app.backup_image_to_save.as_ref().unwrap();
+                                    let limits = (im.width(), im.height());
+
+                                    if ctx.input(|i| i.pointer.any_pressed()) {
+                                        //the handles of the selected annotation come first, then the annotation under the cursor
+                                        app.corner = match app.sel_annotation {
+                                            Some((_, (p, s))) => get_corner(cur, p, s, (10.0 / app.window_image_ratio) as u32),
+                                            None => None
+                                        };
+                                        if app.corner.is_none() {
+                                            app.sel_annotation = im.annotation_at(cur).map(|i| (i, im.annotation_area(i)));
+                                            if app.sel_annotation.is_some() {
+                                                app.corner = Some(Corner::Centre);
+                                            }
+                                        }
+                                        app.any_pressed = app.corner.is_some();
+                                        app.cur_mouse_pos = Some(cur);
+                                        app.refresh_selection(ctx);
+                                    }
+                                    else if app.any_pressed {
+                                        app.prev_mouse_pos = app.cur_mouse_pos;
+                                        app.cur_mouse_pos = Some(cur);
+                                        if let (Some((i, (p, s))), Some(prev)) = (app.sel_annotation, app.prev_mouse_pos) {
+                                            let area = match im.annotations()[i].kind {
+                                                AnnotationKind::Marker { .. } => get_new_area_circle(prev, cur, p, s.0, limits, app.corner.unwrap()),
+                                                _ => {
+                                                    let area = get_new_area(prev, cur, p, s, limits, app.corner.unwrap());
+                                                    lock_aspect(app, ctx, area, s, limits)
+                                                }
+                                            };
+                                            app.sel_annotation = Some((i, area));
+                                            app.refresh_selection(ctx);
+                                        }
+                                    }
+                                }
+
+                                if ctx.input(|i| i.pointer.any_released()) && app.any_pressed {
+                                    app.any_pressed = false;
+                                    app.corner = None;
+                                    app.prev_mouse_pos = None;
+                                    app.cur_mouse_pos = None;
+                                    app.resize_ratio = None;
+                                    if let Some((i, area)) = app.sel_annotation {
+                                        app.backup_image_to_save.as_mut().unwrap().move_annotation(i, area);
+                                    }
+                                }
+                            },
+                            DrawStatus::Line => {
+                                if pos.x - offset > 0.0 && pos.x - offset < image_size.x * app.window_image_ratio
+                                    && pos.y+app.scroll_qty > 50.0 && pos.y+app.scroll_qty < (image_size.y * app.window_image_ratio + 50.0) && !app.dropdown_on {
+                                    let cur = cursor_position(((pos.x - offset) as u32, (pos.y+app.scroll_qty - 50.0) as u32), app.window_image_ratio);
+
+                                    if ctx.input(|i| i.pointer.any_pressed()) {
+                                        //the line starts where the button is pressed and follows the cursor until it is released
+                                        let (rl, dl) = app.backup_image_to_save.as_ref().unwrap().line_init();
+                                        app.rubber_layer = Some(rl);
+                                        app.draw_layer = Some(dl);
+                                        app.prev_mouse_pos = Some(cur);
+                                        app.any_pressed = true;
+                                    }
+                                    else if app.any_pressed {
+                                        if let Some(start) = app.prev_mouse_pos {
+                                            Image::draw_line(app.draw_layer.as_mut().unwrap(), (start.0 as i32, start.1 as i32), (cur.0 as i32, cur.1 as i32),
+                                                             app.pencil_rubber_thickness, app.line_head, &app.draw_color);
+                                            let di = app.draw_layer.as_ref().unwrap().show_over(app.rubber_layer.as_ref().unwrap());
+                                            app.backup_image = Some(ctx.load_texture("my-image", get_image_from_memory(di, 0, 0, 1, 1), Default::default()));
+                                        }
+                                    }
+                                }
+
+                                if ctx.input(|i| i.pointer.any_released()) && app.any_pressed {
+                                    app.any_pressed = false;
+                                    app.prev_mouse_pos = None;
+                                    app.rubber_layer = None;
+                                    if let Some(layer) = app.draw_layer.take() {
+                                        app.backup_image_to_save.as_mut().unwrap().line_set(layer);
+                                        app.palette.use_color(app.draw_color);
+                                        let di = app.backup_image_to_save.as_ref().unwrap().show();
+                                        app.backup_image = Some(ctx.load_texture("my-image", get_image_from_memory(di, 0, 0, 1, 1), Default::default()));
+                                    }
+                                }
+                            },
                         }
                     }
                 }
 
+                if app.draw_status == DrawStatus::Select && !app.any_pressed && ctx.input(|i| i.key_pressed(egui::Key::Delete)) {
+                    if let Some((i, _)) = app.sel_annotation.take() {
+                        app.backup_image_to_save.as_mut().unwrap().remove_annotation(i);
+                        app.refresh_selection(ctx);
+                    }
+                }
+
                 ui.vertical_centered(|ui| {
                         ui.add(egui::Image::new(app.backup_image.as_ref().unwrap(),
                                                 app.backup_image.as_ref().unwrap().size_vec2() * app.window_image_ratio));
@@ -667,6 +1057,7 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                 ui.horizontal(|ui| {
                     ui.style_mut().visuals.override_text_color = Some(egui::Color32::WHITE);
                     if ui.add(egui::Button::new("OK")).clicked() {
+                        app.eyedropper = None;
                         if app.draw_layer.is_some() && app.rubber_layer.is_some() {
                             match app.draw_status {
                                 DrawStatus::Shape(_) => {
@@ -678,6 +1069,10 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                                         Default::default()
                                     ));
                                 },
+                                DrawStatus::Select => {
+                                    app.sel_annotation = None;
+                                    app.refresh_selection(ctx);
+                                },
                                 _ => {}
                             }
                         }
@@ -697,15 +1092,24 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                         }
                     }
 
-                    if ui.add(egui::Button::new("↩")).clicked(){
+                    let undo = ui.add(egui::Button::new("↩")).on_hover_text("Undo last edit").clicked() || undo_key;
+                    let redo = ui.add_enabled(app.backup_image_to_save.as_ref().unwrap().can_redo(), egui::Button::new("↪")).on_hover_text("Redo last undone edit").clicked() || redo_key;
+                    if undo || redo {
                         match app.draw_status {
                             DrawStatus::Shape(_) => {
-                                app.backup_image_to_save.as_mut().unwrap().shape_set(app.rubber_layer.take().unwrap(), app.draw_layer.take().unwrap());
+                                if app.draw_layer.is_some() && app.rubber_layer.is_some() {
+                                    app.backup_image_to_save.as_mut().unwrap().shape_set(app.rubber_layer.take().unwrap(), app.draw_layer.take().unwrap());
+                                }
                                 app.draw_status = DrawStatus::default();
                             },
                             _ => {}
                         }
-                        let di = app.backup_image_to_save.as_mut().unwrap().undo();
+                        app.sel_annotation = None;
+                        let di = if undo {
+                            app.backup_image_to_save.as_mut().unwrap().undo()
+                        } else {
+                            app.backup_image_to_save.as_mut().unwrap().redo()
+                        };
                         app.backup_image = Some(ctx.load_texture(
                             "my-image",
                             get_image_from_memory(di, 0, 0, 1, 1),
@@ -713,14 +1117,16 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                         ));
                         match app.draw_status {
                             DrawStatus::Draw => {
-                                app.draw_layer = Some(app.backup_image_to_save.as_ref().unwrap().free_hand_draw_init());
-                            },
-                            DrawStatus::Highlight => {
-                                let (rl, dl) = app.backup_image_to_save.as_ref().unwrap().rubber_init(app.last_crop_data);
+                                let (rl, dl) = app.backup_image_to_save.as_ref().unwrap().free_hand_draw_init();
                                 app.rubber_layer = Some(rl);
                                 app.draw_layer = Some(dl);
                             },
                             DrawStatus::Rubber => {
+                                let (rl, dl) = app.backup_image_to_save.as_ref().unwrap().rubber_init();
+                                app.rubber_layer = Some(rl);
+                                app.draw_layer = Some(dl);
+                            },
+                            DrawStatus::Highlight => {
                                 let (rl, dl) = app.backup_image_to_save.as_ref().unwrap().highlight_init();
                                 app.rubber_layer = Some(rl);
                                 app.draw_layer = Some(dl);
@@ -730,6 +1136,8 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                     }
 
                     if ui.add(egui::Button::new("Back")).clicked() {
+                        app.eyedropper = None;
+                        app.sel_annotation = None;
                         app.prev = app.status;
                         app.backup_image = app.image.clone();
                         app.backup_image_to_save = app.image_to_save.clone();
@@ -744,4 +1152,32 @@ pub fn draw_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                 });
             });
     });
+}
+
+///Returns true while the tip of the tail of the speech bubble being placed is dragged, starting the drag if the
+///pointer is pressed over the tip. The position is the one of the pointer relative to the image shown
+fn grab_tail(app: &mut MyApp, ctx: &egui::Context, pos: (f32, f32)) -> bool {
+    if app.tail_drag {
+        return true;
+    }
+    match app.bubble_tail {
+        Some(tip) if ctx.input(|i| i.pointer.any_pressed()) => {
+            let (x, y) = cursor_position((tip.0.max(0) as u32, tip.1.max(0) as u32), 1.0 / app.window_image_ratio);
+            app.tail_drag = (pos.0 - x as f32).abs() < 10.0 && (pos.1 - y as f32).abs() < 10.0;
+            app.any_pressed = app.any_pressed || app.tail_drag;
+            app.tail_drag
+        }
+        _ => false
+    }
+}
+
+///Returns the area resized by dragging a corner, changed to keep the width over height ratio of the shape while Shift is
+///held. The ratio is the one of the old size when Shift is pressed, so that it does not drift while dragging
+fn lock_aspect(app: &mut MyApp, ctx: &egui::Context, area: ((u32, u32), (u32, u32)), old_size: (u32, u32), limits: (u32, u32)) -> ((u32, u32), (u32, u32)) {
+    if !ctx.input(|i| i.modifiers.shift) {
+        app.resize_ratio = None;
+        return area;
+    }
+    let ratio = *app.resize_ratio.get_or_insert(old_size.0.max(1) as f32 / old_size.1.max(1) as f32);
+    keep_aspect(area, ratio, limits, app.corner.unwrap())
 }
\ No newline at end of file
@@ -0,0 +1,96 @@
+use eframe::egui;
+use image::GenericImageView;
+use crate::cursor_scaling::cursor_position;
+use crate::image_proc::colors::Color;
+use crate::main_window::MyApp;
+
+///Number of pixels on each side of the loupe
+const LOUPE_PIXELS: i32 = 11;
+///Side of each pixel in the loupe, in points
+const LOUPE_ZOOM: f32 = 9.0;
+
+///Returns the color as a hex code, such as #1E90FF
+pub fn hex(color: &Color) -> String {
+    format!("#{:02X}{:02X}{:02X}", color.color.0[0], color.color.0[1], color.color.0[2])
+}
+
+///Shows the button turning the eyedropper on and off and, once a color has been picked, the button copying its hex code.
+///The image sampled is the capture as it is when the eyedropper is turned on, annotations included
+pub fn eyedropper_buttons(app: &mut MyApp, ui: &mut egui::Ui) {
+    let active = app.eyedropper.is_some();
+    if ui.selectable_label(active, "💧 Pick").on_hover_text("Pick a color from the capture").clicked() {
+        app.eyedropper = if active { None } else { Some(app.backup_image_to_save.as_ref().unwrap().show()) };
+    }
+    if let Some(color) = app.picked_color {
+        if ui.button(format!("📋 {}", hex(&color))).on_hover_text("Copy the hex code of the picked color").clicked() {
+            let _ = app.clipboard.set_text(hex(&color));
+        }
+    }
+}
+
+///While the eyedropper is on, shows a loupe with the pixels under the cursor and their color, and returns the color
+///of the pixel clicked, turning the eyedropper off. Offset and top give the position of the image in the window.
+///The other tools are disabled while the eyedropper is on
+pub fn eyedropper(app: &mut MyApp, ctx: &egui::Context, offset: f32, top: f32) -> Option<Color> {
+    let image = app.eyedropper.as_ref()?;
+    app.dropdown_on = true;
+    if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+        app.eyedropper = None;
+        return None;
+    }
+    let pos = ctx.input(|i| i.pointer.hover_pos())?;
+    let (x, y) = (pos.x - offset, pos.y + app.scroll_qty - top);
+    if x < 0.0 || y < 0.0 {
+        return None;
+    }
+    let (px, py) = cursor_position((x as u32, y as u32), app.window_image_ratio);
+    if px >= image.width() || py >= image.height() {
+        return None;
+    }
+    let pixel = image.get_pixel(px, py);
+    let color = Color { color: pixel };
+
+    egui::Area::new("eyedropper-loupe")
+        .order(egui::Order::Tooltip)
+        .fixed_pos(pos + egui::vec2(20.0, 20.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                let side = LOUPE_PIXELS as f32 * LOUPE_ZOOM;
+                let (rect, _) = ui.allocate_exact_size(egui::vec2(side, side), egui::Sense::hover());
+                let painter = ui.painter_at(rect);
+                let half = LOUPE_PIXELS / 2;
+                for dy in -half..=half {
+                    for dx in -half..=half {
+                        let (sx, sy) = (px as i32 + dx, py as i32 + dy);
+                        let fill = if sx >= 0 && sy >= 0 && sx < image.width() as i32 && sy < image.height() as i32 {
+                            let p = image.get_pixel(sx as u32, sy as u32).0;
+                            egui::Color32::from_rgb(p[0], p[1], p[2])
+                        } else {
+                            egui::Color32::BLACK
+                        };
+                        let min = rect.min + egui::vec2((dx + half) as f32 * LOUPE_ZOOM, (dy + half) as f32 * LOUPE_ZOOM);
+                        painter.rect_filled(egui::Rect::from_min_size(min, egui::vec2(LOUPE_ZOOM, LOUPE_ZOOM)), 0.0, fill);
+                    }
+                }
+                let center = egui::Rect::from_min_size(rect.min + egui::vec2(half as f32 * LOUPE_ZOOM, half as f32 * LOUPE_ZOOM), egui::vec2(LOUPE_ZOOM, LOUPE_ZOOM));
+                painter.rect_stroke(center, 0.0, egui::Stroke::new(1.5, egui::Color32::WHITE));
+                ui.label(hex(&color));
+                ui.label(format!("RGB {} {} {}", pixel.0[0], pixel.0[1], pixel.0[2]));
+            });
+        });
+
+    if ctx.input(|i| i.pointer.any_click()) {
+        app.eyedropper = None;
+        app.picked_color = Some(color);
+        return Some(color);
+    }
+    None
+}
+
+///Sets the red, green and blue components of the color to the ones of the picked color, leaving its alpha unchanged
+pub fn apply_picked(target: &mut Color, picked: Color) {
+    target.color.0[0] = picked.color.0[0];
+    target.color.0[1] = picked.color.0[1];
+    target.color.0[2] = picked.color.0[2];
+}
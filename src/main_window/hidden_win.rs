@@ -1,7 +1,7 @@
 use eframe::egui;
 use crate::main_window::Status::*;
-use crate::image_proc::get_image;
-use crate::main_window::{MyApp, take_capture};
+use crate::image_proc::get_image_from_memory;
+use crate::main_window::{MyApp, take_capture, take_desktop_capture, take_window_capture};
 
 pub fn hidden_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::Frame){
     let enabled;
@@ -11,23 +11,102 @@ pub fn hidden_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::F
         enabled = ctx.input(|i| i.time) - app.disabled_time > 0.0;
     }
 
-    if !app.all_images.is_empty(){
+    let cursor = if app.include_cursor { app.cursor_sprite.as_ref() } else { None };
+
+    if !app.all_images.is_empty() && !app.region_flag{
         app.all_images.clear();
         app.all_images_to_save.clear();
     }
 
     if !enabled{
         ctx.request_repaint();
+    }else if app.screens.is_empty() {
+        //there is nothing to capture, the reason is shown in the initial window
+        app.region_flag = false;
+        app.record_region_flag = false;
+        app.scroll_region_flag = false;
+        app.all_screens = false;
+        app.desktop_flag = false;
+        app.window_flag = false;
+        app.interval_running = false;
+        frame.set_visible(true);
+        app.status = Start;
+    }else if app.region_flag {
+        match take_capture(&app.screens[app.sel_screen], cursor) {
+            None => {
+                app.region_flag = false;
+                frame.set_visible(true);
+                app.status = app.prev;
+            }
+            Some((im, _)) => {
+                let (w, h) = (im.width(), im.height());
+                let di = im.blur_area(0, 0, w, h).show_background();
+                app.region_texture = Some(ctx.load_texture(
+                    "region-image",
+                    get_image_from_memory(di, 0, 0, 1, 1),
+                    Default::default()
+                ));
+                app.region_image = Some(im);
+                app.bl_ar = None;
+                app.region_start = None;
+                app.any_pressed = false;
+                app.corner = None;
+                app.prev_mouse_pos = None;
+                app.cur_mouse_pos = None;
+
+                let info = frame.info().window_info;
+                app.window_backup = Some((info.position, info.size));
+                let geometry = app.screens[app.sel_screen].geometry();
+                frame.set_decorations(false);
+                frame.set_always_on_top(true);
+                frame.set_window_pos(egui::Pos2::new(geometry.x as f32 / geometry.scale_factor, geometry.y as f32 / geometry.scale_factor));
+                frame.set_fullscreen(true);
+                frame.set_visible(true);
+                app.status = Region;
+            }
+        }
     }else {
-        if app.all_screens{
+        if app.window_flag && app.sel_window.is_some(){
+            //the window may have been moved or resized since it was selected
+            let selected = app.sel_window.clone().unwrap();
+            let window = match app.window_provider.windows() {
+                Ok(windows) => windows.into_iter().find(|w| w.id == selected.id).unwrap_or(selected),
+                Err(_) => selected
+            };
+            match take_window_capture(&app.screens, &window, cursor) {
+                None => {}
+                Some((im, ci)) => {
+                    app.image = Some(ctx.load_texture(
+                        "my-image",
+                        ci,
+                        Default::default()
+                    ));
+                    app.image_to_save = Some(im);
+                }
+            }
+        }
+        else if app.desktop_flag{
+            match take_desktop_capture(&app.screens, cursor) {
+                None => {}
+                Some((im, ci)) => {
+                    app.image = Some(ctx.load_texture(
+                        "my-image",
+                        ci,
+                        Default::default()
+                    ));
+                    app.image_to_save = Some(im);
+                }
+            }
+        }
+        else if app.all_screens{
             for screen in &app.screens{
-                match take_capture(screen) {
+                match take_capture(screen, cursor) {
                     None => {}
-                    Some(im) => {
+                    Some((im, ci)) => {
                         app.all_images.push(
                             ctx.load_texture(
                                 "my-image",
-                                get_image(".tmp.png", 0, 0, 1, 1),
+                                ci,
                                 Default::default()
                             )
                         );
@@ -40,12 +119,17 @@ pub fn hidden_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::F
             app.image_to_save = Some(app.all_images_to_save.get(app.sel_image).unwrap().clone());
         }
         else {
-            match take_capture(&app.screens[app.sel_screen]) {
-                None => {}
-                Some(im) => {
+            match take_capture(&app.screens[app.sel_screen], cursor) {
+                None => {
+                    app.interval_running = false;
+                }
+                Some((im, ci)) => {
+                    if app.interval_running {
+                        app.save_interval_frame(&im, ctx.input(|i| i.time));
+                    }
                     app.image = Some(ctx.load_texture(
                         "my-image",
-                        get_image(".tmp.png", 0, 0, 1, 1),
+                        ci,
                         Default::default()
                     ));
                     app.image_to_save = Some(im);
@@ -53,6 +137,8 @@ pub fn hidden_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::F
             }
         }
         app.all_screens = false;
+        app.desktop_flag = false;
+        app.window_flag = false;
         frame.set_visible(true);
         app.status = Image;
     }
@@ -3,10 +3,40 @@ use eframe::egui::scroll_area::ScrollBarVisibility;
 use eframe::egui::Vec2;
 use crate::image_proc::get_image_from_memory;
 use crate::main_window::Status::*;
-use crate::main_window::{DrawStatus, min_my, MyApp};
+use crate::main_window::{DrawStatus, history_shortcuts, min_my, window_selector, MyApp};
 
 
 pub fn image_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::Frame){
+    let (undo_key, redo_key) = history_shortcuts(ctx);
+
+    if app.show_history {
+        egui::SidePanel::right("history-panel").show(ctx, |ui| {
+            ui.style_mut().visuals.override_text_color = Some(egui::Color32::WHITE);
+            ui.heading("History");
+            ui.separator();
+            let im = app.image_to_save.as_ref().unwrap();
+            let current = im.current_step();
+            let mut jump = None;
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (i, step) in im.steps().into_iter().enumerate() {
+                    //the undone steps are dimmed, they are dropped by the next edit
+                    let text = if i > current { egui::RichText::new(step).weak() } else { egui::RichText::new(step) };
+                    if ui.selectable_label(i == current, text).clicked() {
+                        jump = Some(i);
+                    }
+                }
+            });
+            if let Some(i) = jump {
+                let di = app.image_to_save.as_mut().unwrap().go_to_step(i);
+                app.image = Some(ctx.load_texture(
+                    "my-image",
+                    get_image_from_memory(di, 0, 0, 1, 1),
+                    Default::default()
+                ));
+            }
+        });
+    }
+
     egui::CentralPanel::default().show(ctx, |ui| {
         egui::ScrollArea::vertical()
             .scroll_bar_visibility(ScrollBarVisibility::AlwaysVisible)
@@ -32,16 +62,59 @@ pub fn image_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::Fr
                             app.status = Hidden;
                             app.all_screens = true;
                         }
+
+                        if ui.button("🖥 Desktop").on_hover_text("Take a single capture of the whole desktop, with all the screens in their position").clicked() {
+                            frame.set_visible(false);
+                            app.disabled_time = ui.input(|i| i.time);
+                            app.prev = app.status;
+                            app.instant_flag = true;
+                            app.status = Hidden;
+                            app.desktop_flag = true;
+                        }
                     }
 
-                    if ui.button("⏰ Delay").on_hover_text("Delay a new capture").clicked() {
+                    if ui.button("⛶ Region").on_hover_text("Select an area of the screen to capture").clicked() {
+                        frame.set_visible(false);
+                        app.disabled_time = ui.input(|i| i.time);
+                        app.prev = app.status;
+                        app.instant_flag = true;
+                        app.region_flag = true;
+                        app.status = Hidden;
+                    }
+
+                    if ui.add_enabled(!app.interval_running, egui::Button::new("🔁 Interval")).on_hover_text("Take and save a capture every few seconds").clicked() {
+                        app.start_interval(ui.input(|i| i.time));
+                    }
+
+                    if ui.button("⏺ Record").on_hover_text("Record the screen as an animated image").clicked() {
+                        app.record_region = None;
+                        app.start_recording(frame, ui.input(|i| i.time));
+                    }
+
+                    if ui.button("⏺ Record Area").on_hover_text("Select an area of the screen and record it as an animated image").clicked() {
+                        frame.set_visible(false);
+                        app.disabled_time = ui.input(|i| i.time);
+                        app.prev = app.status;
+                        app.instant_flag = true;
+                        app.region_flag = true;
+                        app.record_region_flag = true;
+                        app.status = Hidden;
+                    }
+
+                    if ui.button("⇕ Scroll").on_hover_text("Select an area and capture it while scrolling its content; press the scroll capture hotkey to stop").clicked() {
                         frame.set_visible(false);
                         app.disabled_time = ui.input(|i| i.time);
                         app.prev = app.status;
-                        app.instant_flag = false;
+                        app.instant_flag = true;
+                        app.region_flag = true;
+                        app.scroll_region_flag = true;
                         app.status = Hidden;
                     }
 
+                    if ui.button("⏰ Delay").on_hover_text("Delay a new capture").clicked() {
+                        app.start_countdown(frame, ui.input(|i| i.time));
+                    }
+
                     if ui.button("✂ Crop").on_hover_text("Crop the taken capture").clicked() {
                         let w = app.image_to_save.as_ref().unwrap().width();
                         let h = app.image_to_save.as_ref().unwrap().height();
@@ -57,7 +130,9 @@ pub fn image_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::Fr
                     }
 
                     if ui.button("✏ Draw").on_hover_text("Draw over the capture").clicked() {
-                        app.draw_layer = Some(app.image_to_save.as_ref().unwrap().free_hand_draw_init());
+                        let (rl, dl) = app.image_to_save.as_ref().unwrap().free_hand_draw_init();
+                        app.rubber_layer = Some(rl);
+                        app.draw_layer = Some(dl);
                         app.backup_image = app.image.clone();
                         app.backup_image_to_save = app.image_to_save.clone();
                         app.highlight = false;
@@ -113,11 +188,23 @@ pub fn image_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::Fr
                             .selected_text(format!("Screen: {}", app.sel_screen + 1)).width(10.0)
                             .show_ui(ui, |ui| {
                                 for i in 0..app.screens.len() {
-                                    ui.selectable_value(&mut app.sel_screen, i, format!("{}", i + 1));
+                                    let geometry = app.screens[i].geometry();
+                                    ui.selectable_value(&mut app.sel_screen, i, format!("{} ({}x{})", i + 1, geometry.width, geometry.height));
                                 }
                             });
                     }
 
+                    if ui.add_enabled(app.sel_window.is_some(), egui::Button::new("🗔 Window")).on_hover_text("Take a new capture of the selected window").clicked() {
+                        frame.set_visible(false);
+                        app.disabled_time = ui.input(|i| i.time);
+                        app.prev = app.status;
+                        app.instant_flag = true;
+                        app.status = Hidden;
+                        app.window_flag = true;
+                    }
+
+                    window_selector(app, ui);
+
 
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Max), |ui| {
                         if ui.button("⚙").on_hover_text("General settings").clicked() {
@@ -199,16 +286,25 @@ pub fn image_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::Fr
                         ));
                     }
 
-                    if ui.button("↩").on_hover_text("Undo last edit").clicked() {
-                        let di = app.backup_image_to_save.as_mut().unwrap().undo();
-                        app.backup_image = Some(ctx.load_texture(
+                    if ui.button("↩").on_hover_text("Undo last edit").clicked() || undo_key {
+                        let di = app.image_to_save.as_mut().unwrap().undo();
+                        app.image = Some(ctx.load_texture(
                             "my-image",
                             get_image_from_memory(di, 0, 0, 1, 1),
                             Default::default()
                         ));
-                        app.image = app.backup_image.clone();
-                        app.image_to_save = app.backup_image_to_save.clone();
                     }
+
+                    if ui.add_enabled(app.image_to_save.as_ref().unwrap().can_redo(), egui::Button::new("↪")).on_hover_text("Redo last undone edit").clicked() || redo_key {
+                        let di = app.image_to_save.as_mut().unwrap().redo();
+                        app.image = Some(ctx.load_texture(
+                            "my-image",
+                            get_image_from_memory(di, 0, 0, 1, 1),
+                            Default::default()
+                        ));
+                    }
+
+                    ui.toggle_value(&mut app.show_history, "🕘 History").on_hover_text("Show the list of the edits, click one to go back to it");
                 });
 
                 ui.horizontal(|ui| {
@@ -218,6 +314,28 @@ pub fn image_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::Fr
 
                     ui.style_mut().visuals.widgets.hovered.bg_stroke.color = egui::Color32::WHITE;
                     ui.add(egui::TextEdit::singleline(&mut app.save_name)).highlight();
+
+                    if app.interval_running || app.interval_count > 0 {
+                        ui.add_space(20.0);
+                        let shots = match app.interval_shots {
+                            0 => String::from("∞"),
+                            n => format!("{}", n)
+                        };
+                        if app.interval_running {
+                            let left = (app.interval_next - ui.input(|i| i.time)).max(0.0).ceil();
+                            ui.label(format!("Interval capture: {}/{} saved, next in {}s", app.interval_count, shots, left));
+                            if ui.button("⏹ Stop").on_hover_text("Stop the interval capture").clicked() {
+                                app.interval_running = false;
+                            }
+                        } else {
+                            ui.label(format!("Interval capture: {}/{} saved as {}_*", app.interval_count, shots, app.interval_name));
+                        }
+                    }
+
+                    if let Some(result) = app.record_result.as_ref() {
+                        ui.add_space(20.0);
+                        ui.label(result.as_str());
+                    }
                 });
             });
     });
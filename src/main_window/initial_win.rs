@@ -3,7 +3,7 @@ use eframe::egui;
 
 use crate::main_window::Status::*;
 
-use crate::main_window::MyApp;
+use crate::main_window::{window_selector, MyApp};
 
 
 pub fn initial_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::Frame){
@@ -27,26 +27,81 @@ pub fn initial_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::
                     app.status = Hidden;
                     app.all_screens = true;
                 }
+
+                if ui.button("🖥 Desktop").on_hover_text("Take a single capture of the whole desktop, with all the screens in their position").clicked(){
+                    frame.set_visible(false);
+                    app.disabled_time = ui.input(|i| i.time);
+                    app.prev = app.status;
+                    app.instant_flag = true;
+                    app.status = Hidden;
+                    app.desktop_flag = true;
+                }
             }
 
-            if ui.button("⏰ Delay").on_hover_text("Delay a new capture").clicked(){
+            if ui.button("⛶ Region").on_hover_text("Select an area of the screen to capture").clicked(){
                 frame.set_visible(false);
                 app.disabled_time = ui.input(|i| i.time);
                 app.prev = app.status;
-                app.instant_flag = false;
+                app.instant_flag = true;
+                app.region_flag = true;
                 app.status = Hidden;
             }
 
+            if ui.add_enabled(!app.interval_running, egui::Button::new("🔁 Interval")).on_hover_text("Take and save a capture every few seconds").clicked(){
+                app.start_interval(ui.input(|i| i.time));
+            }
+
+            if ui.button("⏺ Record").on_hover_text("Record the screen as an animated image").clicked(){
+                app.record_region = None;
+                app.start_recording(frame, ui.input(|i| i.time));
+            }
+
+            if ui.button("⏺ Record Area").on_hover_text("Select an area of the screen and record it as an animated image").clicked(){
+                frame.set_visible(false);
+                app.disabled_time = ui.input(|i| i.time);
+                app.prev = app.status;
+                app.instant_flag = true;
+                app.region_flag = true;
+                app.record_region_flag = true;
+                app.status = Hidden;
+            }
+
+            if ui.button("⇕ Scroll").on_hover_text("Select an area and capture it while scrolling its content; press the scroll capture hotkey to stop").clicked(){
+                frame.set_visible(false);
+                app.disabled_time = ui.input(|i| i.time);
+                app.prev = app.status;
+                app.instant_flag = true;
+                app.region_flag = true;
+                app.scroll_region_flag = true;
+                app.status = Hidden;
+            }
+
+            if ui.button("⏰ Delay").on_hover_text("Delay a new capture").clicked(){
+                app.start_countdown(frame, ui.input(|i| i.time));
+            }
+
             if app.screens.len()>1{
                 egui::ComboBox::from_label("").width(10.0)
                     .selected_text(format!("Screen: {}", app.sel_screen+1))
                     .show_ui(ui, |ui| {
                         for i in 0..app.screens.len(){
-                            ui.selectable_value(&mut app.sel_screen, i, format!("{}", i+1));
+                            let geometry = app.screens[i].geometry();
+                            ui.selectable_value(&mut app.sel_screen, i, format!("{} ({}x{})", i+1, geometry.width, geometry.height));
                         }
                     });
             }
 
+            if ui.add_enabled(app.sel_window.is_some(), egui::Button::new("🗔 Window")).on_hover_text("Take a new capture of the selected window").clicked(){
+                frame.set_visible(false);
+                app.disabled_time = ui.input(|i| i.time);
+                app.prev = app.status;
+                app.instant_flag = true;
+                app.status = Hidden;
+                app.window_flag = true;
+            }
+
+            window_selector(app, ui);
+
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Max), |ui|{
                 if ui.button("⚙").on_hover_text("General settings").clicked(){
                     app.prev = app.status;
@@ -58,6 +113,11 @@ pub fn initial_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::
         ui.vertical_centered(|ui|{
             ui.add(egui::TextEdit::singleline(&mut "Press Take to capture a new screenshot"));
         });
+        for error in app.startup_errors.iter() {
+            ui.vertical_centered(|ui|{
+                ui.colored_label(egui::Color32::LIGHT_RED, error.as_str());
+            });
+        }
         if app.screens.len()>1{
             ui.vertical_centered(|ui|{
                 ui.add(egui::TextEdit::multiline(&mut "Press Take All to capture a new screenshot from all the connected screens"));
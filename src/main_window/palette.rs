@@ -0,0 +1,249 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use eframe::egui;
+use crate::image_proc::colors::Color;
+
+///Number of recently used colors kept in the palette
+const RECENT_COLORS: usize = 8;
+///File where the palette of the user is persisted
+const PALETTE_FILE: &str = "settings/palette";
+///Name of the file, in the save directory, through which favourites are shared with the team
+const TEAM_PALETTE_FILE: &str = "team.palette";
+///Side of a swatch, in points
+const SWATCH_SIZE: f32 = 16.0;
+
+///Colors shared by all the tools: the last ones used and the ones pinned by the user
+#[derive(Debug, Clone, Default)]
+pub struct Palette {
+    recent: Vec<Color>,
+    favourites: Vec<Color>,
+    //outcome of the last import or export, shown in the menu
+    message: String
+}
+
+///Returns the color as a hex code with alpha, such as #1E90FFCC
+fn to_hex(color: &Color) -> String {
+    let c = color.color.0;
+    format!("#{:02X}{:02X}{:02X}{:02X}", c[0], c[1], c[2], c[3])
+}
+
+///Parses a hex code such as #1E90FF or #1E90FFCC, or a short one such as #19F or #19FC whose digits are doubled;
+///a missing alpha means an opaque color
+fn from_hex(hex: &str) -> Option<Color> {
+    let hex = hex.trim().strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digits = match hex.len() {
+        3 | 4 => 1,
+        6 | 8 => 2,
+        _ => return None
+    };
+    let mut c = [255u8; 4];
+    for (i, v) in c.iter_mut().enumerate().take(hex.len() / digits) {
+        let value = u8::from_str_radix(&hex[digits * i..digits * (i + 1)], 16).ok()?;
+        *v = if digits == 1 { value * 17 } else { value };
+    }
+    Some(Color { color: image::Rgba(c) })
+}
+
+impl Palette {
+    ///Loads the palette saved by the previous session, if any
+    pub fn load() -> Self {
+        let mut ret = Self::default();
+        if let Ok(f) = File::open(PALETTE_FILE) {
+            for l in BufReader::new(f).lines().map_while(Result::ok) {
+                match l.split_once(' ') {
+                    Some(("recent", hex)) => match from_hex(hex) {
+                        Some(color) if ret.recent.len() < RECENT_COLORS => ret.recent.push(color),
+                        _ => {}
+                    },
+                    Some(("favourite", hex)) => {
+                        if let Some(color) = from_hex(hex) {
+                            ret.pin(color);
+                        }
+                    },
+                    _ => {}
+                }
+            }
+        }
+        ret
+    }
+
+    ///Saves the palette, so that it is restored by the next session
+    pub fn save(&self) {
+        if let Ok(mut f) = File::create(PALETTE_FILE) {
+            for color in self.recent.iter() {
+                f.write_all(format!("recent {}\n", to_hex(color)).as_bytes()).unwrap();
+            }
+            for color in self.favourites.iter() {
+                f.write_all(format!("favourite {}\n", to_hex(color)).as_bytes()).unwrap();
+            }
+        }
+    }
+
+    ///Moves the color to the front of the recent ones, forgetting the oldest if they are too many
+    pub fn use_color(&mut self, color: Color) {
+        self.recent.retain(|c| *c != color);
+        self.recent.insert(0, color);
+        self.recent.truncate(RECENT_COLORS);
+    }
+
+    ///Adds the color to the favourites, unless it is already there
+    pub fn pin(&mut self, color: Color) {
+        if !self.favourites.contains(&color) {
+            self.favourites.push(color);
+        }
+    }
+
+    ///Removes the color from the favourites, if it is there
+    pub fn unpin(&mut self, color: Color) {
+        self.favourites.retain(|c| *c != color);
+    }
+
+    ///Adds to the favourites the colors of a team palette file: one hex code per line, other lines are ignored.
+    ///Returns the number of colors read
+    pub fn import(&mut self, path: &str) -> std::io::Result<usize> {
+        let f = File::open(path)?;
+        let mut n = 0;
+        for l in BufReader::new(f).lines() {
+            if let Some(color) = from_hex(&l?) {
+                self.pin(color);
+                n += 1;
+            }
+        }
+        Ok(n)
+    }
+
+    ///Writes the favourites to a team palette file, one hex code per line
+    pub fn export(&self, path: &str) -> std::io::Result<()> {
+        let mut f = File::create(path)?;
+        for color in self.favourites.iter() {
+            f.write_all(format!("{}\n", to_hex(color)).as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+///Shows a clickable square filled with the color, on a checkerboard revealing its transparency
+fn swatch(ui: &mut egui::Ui, color: &Color, selected: bool) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(SWATCH_SIZE, SWATCH_SIZE), egui::Sense::click());
+    let painter = ui.painter_at(rect);
+    let half = SWATCH_SIZE / 2.0;
+    for (i, corner) in [(0.0, 0.0), (half, 0.0), (0.0, half), (half, half)].iter().enumerate() {
+        let fill = if i == 0 || i == 3 { egui::Color32::WHITE } else { egui::Color32::GRAY };
+        painter.rect_filled(egui::Rect::from_min_size(rect.min + egui::vec2(corner.0, corner.1), egui::vec2(half, half)), 0.0, fill);
+    }
+    let c = color.color.0;
+    painter.rect_filled(rect, 0.0, egui::Color32::from_rgba_unmultiplied(c[0], c[1], c[2], c[3]));
+    let stroke = if selected || response.hovered() { egui::Stroke::new(2.0, egui::Color32::WHITE) } else { egui::Stroke::new(1.0, egui::Color32::DARK_GRAY) };
+    painter.rect_stroke(rect, 0.0, stroke);
+    response
+}
+
+///Shows a slider setting the alpha of the color as a percentage
+pub fn opacity_slider(ui: &mut egui::Ui, color: &mut Color) {
+    let mut opacity = (color.color.0[3] as f32 / 2.55).round() as u32;
+    ui.add(egui::Slider::new(&mut opacity, 0..=100).text("Opacity %"));
+    color.color.0[3] = (opacity as f32 * 2.55).round() as u8;
+}
+
+///Shows the favourite and the recent colors as swatches: a click applies the swatch to the color of the tool,
+///a right click pins a recent color or unpins a favourite one. The menu pins the current color and shares the
+///favourites with the team through a palette file in the save directory
+pub fn palette_swatches(palette: &mut Palette, ui: &mut egui::Ui, color: &mut Color, save_dir: &str) {
+    let mut chosen = None;
+    let mut unpinned = None;
+    let mut pinned = None;
+    for c in palette.favourites.iter() {
+        let r = swatch(ui, c, c == color).on_hover_text(format!("{} (right click to unpin)", to_hex(c)));
+        if r.clicked() {
+            chosen = Some(*c);
+        }
+        if r.secondary_clicked() {
+            unpinned = Some(*c);
+        }
+    }
+    if !palette.favourites.is_empty() && !palette.recent.is_empty() {
+        ui.separator();
+    }
+    for c in palette.recent.iter() {
+        let r = swatch(ui, c, c == color).on_hover_text(format!("{} (right click to pin)", to_hex(c)));
+        if r.clicked() {
+            chosen = Some(*c);
+        }
+        if r.secondary_clicked() {
+            pinned = Some(*c);
+        }
+    }
+    if let Some(c) = chosen {
+        *color = c;
+        palette.use_color(c);
+    }
+    if let Some(c) = unpinned {
+        palette.unpin(c);
+    }
+    if let Some(c) = pinned {
+        palette.pin(c);
+    }
+
+    let path = format!("{}{}", save_dir, TEAM_PALETTE_FILE);
+    ui.menu_button("☆", |ui| {
+        if ui.button("Pin current color").clicked() {
+            palette.pin(*color);
+            ui.close_menu();
+        }
+        if ui.button("Import team palette").on_hover_text(path.as_str()).clicked() {
+            palette.message = match palette.import(&path) {
+                Ok(n) => format!("{} colors imported", n),
+                Err(e) => format!("Palette not imported: {}", e)
+            };
+        }
+        if ui.add_enabled(!palette.favourites.is_empty(), egui::Button::new("Export favourites as team palette")).on_hover_text(path.as_str()).clicked() {
+            palette.message = match palette.export(&path) {
+                Ok(()) => format!("Palette saved in {}", path),
+                Err(e) => format!("Palette not saved: {}", e)
+            };
+        }
+        if !palette.message.is_empty() {
+            ui.label(palette.message.as_str());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba(r: u8, g: u8, b: u8, a: u8) -> Option<Color> {
+        Some(Color { color: image::Rgba([r, g, b, a]) })
+    }
+
+    #[test]
+    fn long_hex_codes() {
+        assert_eq!(from_hex("#1E90FF"), rgba(0x1E, 0x90, 0xFF, 255));
+        assert_eq!(from_hex("#1E90FFCC"), rgba(0x1E, 0x90, 0xFF, 0xCC));
+        assert_eq!(from_hex(" #1e90ff00 "), rgba(0x1E, 0x90, 0xFF, 0));
+    }
+
+    #[test]
+    fn short_hex_codes() {
+        assert_eq!(from_hex("#19F"), rgba(0x11, 0x99, 0xFF, 255));
+        assert_eq!(from_hex("#19FC"), rgba(0x11, 0x99, 0xFF, 0xCC));
+        assert_eq!(from_hex("#000"), rgba(0, 0, 0, 255));
+    }
+
+    #[test]
+    fn invalid_hex_codes_are_rejected() {
+        for hex in ["", "#", "1E90FF", "#1E90F", "#1E90FFC", "#1E90FFCC00", "#GG90FF", "#+1+2+3", "#1E 0FF", "#1É90F"] {
+            assert_eq!(from_hex(hex), None, "{}", hex);
+        }
+    }
+
+    #[test]
+    fn hex_codes_round_trip() {
+        let color = rgba(0x12, 0xAB, 0x00, 0x7F).unwrap();
+        assert_eq!(to_hex(&color), "#12AB007F");
+        assert_eq!(from_hex(&to_hex(&color)), Some(color));
+    }
+}
\ No newline at end of file
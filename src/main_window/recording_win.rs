@@ -0,0 +1,63 @@
+use std::time::Duration;
+use eframe::egui;
+use crate::main_window::Status::*;
+use crate::image_proc::{get_image_from_memory, Image};
+use crate::main_window::MyApp;
+
+pub fn recording_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::Frame){
+    let now = ctx.input(|i| i.time);
+    let frame_time = 1.0 / app.record_fps as f64;
+
+    if !app.record_stop && now - app.record_start < app.record_secs as f64 {
+        if now >= app.record_next {
+            let cursor = if app.include_cursor { app.cursor_sprite.as_ref() } else { None };
+            match app.screens[app.sel_screen].capture(cursor) {
+                Ok(sh) => {
+                    let mut di = sh.to_dynamic_image();
+                    if let Some(((x, y), (w, h))) = app.record_region {
+                        di = di.crop_imm(x, y, w, h);
+                    }
+                    app.recording.as_mut().unwrap().push_frame(di, now);
+                }
+                Err(_) => {
+                    app.record_stop = true;
+                }
+            }
+            //frames that could not be taken in time are skipped, instead of being taken all at once
+            app.record_next = f64::max(app.record_next + frame_time, now);
+        }
+        ctx.request_repaint_after(Duration::from_secs_f64(f64::max(app.record_next - now, 0.0)));
+        return;
+    }
+
+    let mut recording = app.recording.take().unwrap();
+    recording.stop(now);
+
+    let name = if app.save_name.is_empty() { app.record_name.clone() } else { app.save_name.clone() };
+    let path = format!("{}{}.{}", app.save_location(), name, app.record_format.extension());
+    app.record_result = Some(match recording.save(path.as_str(), app.record_format) {
+        Ok(_) => format!("Recording saved as {} ({} frames)", path, recording.len()),
+        Err(e) => format!("Recording not saved: {}", e)
+    });
+    app.save_name = String::new();
+
+    if let Some(di) = recording.last_frame() {
+        let im = Image::from_dynamic_image(di);
+        app.image = Some(ctx.load_texture(
+            "my-image",
+            get_image_from_memory(im.show(), 0, 0, 1, 1),
+            Default::default()
+        ));
+        app.image_to_save = Some(im);
+        app.all_images.clear();
+        app.all_images_to_save.clear();
+    }
+
+    app.record_region = None;
+    frame.set_visible(true);
+    if app.image.is_some() {
+        app.status = Image;
+    } else {
+        app.status = Start;
+    }
+}
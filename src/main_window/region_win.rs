@@ -0,0 +1,171 @@
+use eframe::egui;
+use crate::main_window::Status::*;
+use crate::cursor_scaling::*;
+use crate::image_proc::{get_image_from_memory, Image};
+use crate::main_window::{min_my, MyApp};
+
+pub fn region_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::Frame){
+    let screen_rect = ctx.screen_rect();
+    let image_size = app.region_texture.as_ref().unwrap().size_vec2();
+    let ratio = min_my(screen_rect.height() / image_size.y, screen_rect.width() / image_size.x);
+    let offset = egui::Vec2::new(
+        (screen_rect.width() - image_size.x * ratio) / 2.0,
+        (screen_rect.height() - image_size.y * ratio) / 2.0
+    );
+    let limits = (app.region_image.as_ref().unwrap().width(), app.region_image.as_ref().unwrap().height());
+
+    let mut confirm = false;
+    let mut cancel = false;
+
+    let hint = egui::Area::new("region-hint")
+        .anchor(egui::Align2::CENTER_TOP, egui::Vec2::new(0.0, 10.0))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.style_mut().visuals.override_text_color = Some(egui::Color32::WHITE);
+                    ui.label("Drag to select the area to capture, Enter to confirm, Esc to cancel");
+                    if ui.add_enabled(app.bl_ar.is_some(), egui::Button::new("OK")).clicked() {
+                        confirm = true;
+                    }
+                    if ui.add(egui::Button::new("Back")).clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+        });
+
+    if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+        cancel = true;
+    }
+    if ctx.input(|i| i.key_pressed(egui::Key::Enter)) && app.bl_ar.is_some() && !app.any_pressed {
+        confirm = true;
+    }
+
+    let mut changed = false;
+
+    match ctx.input(|i| i.pointer.hover_pos()) {
+        Some(pos) if !hint.response.rect.contains(pos) || app.any_pressed => {
+            let x = (pos.x - screen_rect.min.x - offset.x).max(0.0) as u32;
+            let y = (pos.y - screen_rect.min.y - offset.y).max(0.0) as u32;
+            let cur = cursor_position((x, y), ratio);
+            let cur = (u32::min(cur.0, limits.0), u32::min(cur.1, limits.1));
+
+            if ctx.input(|i| i.pointer.any_pressed()) && !app.any_pressed {
+                app.any_pressed = true;
+                app.corner = match app.bl_ar.as_ref() {
+                    Some(bl_ar) => {
+                        let (p, s) = bl_ar.get_crop_data();
+                        get_corner(cur, p, s, (10.0 / ratio) as u32)
+                    }
+                    None => None
+                };
+                if app.corner.is_none() {
+                    app.region_start = Some(cur);
+                }
+                app.cur_mouse_pos = Some(cur);
+            } else if app.any_pressed {
+                app.prev_mouse_pos = app.cur_mouse_pos;
+                app.cur_mouse_pos = Some(cur);
+
+                match (app.region_start, app.corner) {
+                    (Some(start), _) => {
+                        let p = (
+                            u32::min(u32::min(start.0, cur.0), limits.0 - 1),
+                            u32::min(u32::min(start.1, cur.1), limits.1 - 1)
+                        );
+                        let s = (
+                            u32::max(start.0.abs_diff(cur.0), 1),
+                            u32::max(start.1.abs_diff(cur.1), 1)
+                        );
+                        match app.bl_ar.as_mut() {
+                            Some(bl_ar) => bl_ar.resize(p, s),
+                            None => app.bl_ar = Some(app.region_image.as_ref().unwrap().blur_area(p.0, p.1, s.0, s.1))
+                        }
+                        changed = true;
+                    }
+                    (None, Some(corner)) => {
+                        let (p, s) = app.bl_ar.as_ref().unwrap().get_crop_data();
+                        let (pn, sn) = get_new_area(app.prev_mouse_pos.unwrap(), cur, p, s, limits, corner);
+                        app.bl_ar.as_mut().unwrap().resize(pn, sn);
+                        changed = true;
+                    }
+                    (None, None) => {}
+                }
+            }
+
+            if ctx.input(|i| i.pointer.any_released()) && app.any_pressed {
+                app.any_pressed = false;
+                app.corner = None;
+                app.region_start = None;
+                app.prev_mouse_pos = None;
+                app.cur_mouse_pos = None;
+            }
+        }
+        _ => {}
+    }
+
+    if changed {
+        let di = app.bl_ar.as_ref().unwrap().show(app.borders.as_ref().unwrap());
+        app.region_texture = Some(ctx.load_texture(
+            "region-image",
+            get_image_from_memory(di, 0, 0, 1, 1),
+            Default::default()
+        ));
+    }
+
+    egui::CentralPanel::default().frame(egui::Frame::none().fill(egui::Color32::BLACK)).show(ctx, |ui| {
+        let rect = egui::Rect::from_min_size(screen_rect.min + offset, image_size * ratio);
+        ui.painter().image(
+            app.region_texture.as_ref().unwrap().id(),
+            rect,
+            egui::Rect::from_min_max(egui::Pos2::new(0.0, 0.0), egui::Pos2::new(1.0, 1.0)),
+            egui::Color32::WHITE
+        );
+    });
+
+    if confirm || cancel {
+        app.restore_window(frame);
+
+        app.region_flag = false;
+        app.region_texture = None;
+        app.any_pressed = false;
+
+        if confirm && app.record_region_flag {
+            app.record_region_flag = false;
+            app.record_region = Some(app.bl_ar.take().unwrap().get_crop_data());
+            app.region_image = None;
+            app.status = app.prev;
+            app.start_recording(frame, ctx.input(|i| i.time));
+        } else if confirm && app.scroll_region_flag {
+            app.scroll_region_flag = false;
+            app.scroll_region = Some(app.bl_ar.take().unwrap().get_crop_data());
+            app.region_image = None;
+            app.status = app.prev;
+            app.start_scrolling(frame, ctx.input(|i| i.time));
+        } else if confirm {
+            let ((x, y), (w, h)) = app.bl_ar.take().unwrap().get_crop_data();
+            let cropped = app.region_image.take().unwrap().show().crop_imm(x, y, w, h);
+            let im = Image::from_dynamic_image(cropped);
+            app.image = Some(ctx.load_texture(
+                "my-image",
+                get_image_from_memory(im.show(), 0, 0, 1, 1),
+                Default::default()
+            ));
+            app.image_to_save = Some(im);
+            app.all_images.clear();
+            app.all_images_to_save.clear();
+            app.status = Image;
+        } else {
+            app.record_region_flag = false;
+            app.scroll_region_flag = false;
+            app.bl_ar = None;
+            app.region_image = None;
+            if app.image.is_some() {
+                app.status = Image;
+            } else {
+                app.status = Start;
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::Duration;
+use eframe::egui;
+use crate::main_window::Status::*;
+use crate::image_proc::{get_image_from_memory, Image};
+use crate::main_window::MyApp;
+use crate::screensh::stitching::stitch_frames;
+
+///Seconds between two frames of a scrolling capture
+const SCROLL_INTERVAL: f64 = 0.5;
+///Highest number of frames of a scrolling capture, after which it stops by itself
+const MAX_SCROLL_FRAMES: usize = 60;
+
+pub fn scroll_window(app: &mut MyApp, ctx: &egui::Context, frame: &mut eframe::Frame){
+    let now = ctx.input(|i| i.time);
+
+    if !app.scroll_stop && app.scroll_frames.len() < MAX_SCROLL_FRAMES {
+        if now >= app.scroll_next {
+            //the mouse cursor is never included, since it would move along with the content
+            match app.screens[app.sel_screen].capture(None) {
+                Ok(sh) => {
+                    let mut di = sh.to_dynamic_image();
+                    if let Some(((x, y), (w, h))) = app.scroll_region {
+                        di = di.crop_imm(x, y, w, h);
+                    }
+                    //nothing to add until the content is actually scrolled
+                    if app.scroll_frames.last().map(|last| last.as_bytes() != di.as_bytes()).unwrap_or(true) {
+                        app.scroll_frames.push(di);
+                    }
+                }
+                Err(_) => {
+                    app.scroll_stop = true;
+                }
+            }
+            app.scroll_next = now + SCROLL_INTERVAL;
+        }
+        ctx.request_repaint_after(Duration::from_secs_f64(f64::max(app.scroll_next - now, 0.0)));
+        return;
+    }
+
+    if let Some(di) = stitch_frames(&app.scroll_frames) {
+        let im = Image::from_dynamic_image(di);
+        app.image = Some(ctx.load_texture(
+            "my-image",
+            get_image_from_memory(im.show(), 0, 0, 1, 1),
+            Default::default()
+        ));
+        app.image_to_save = Some(im);
+        app.all_images.clear();
+        app.all_images_to_save.clear();
+    }
+
+    app.scroll_frames.clear();
+    app.scroll_region = None;
+    frame.set_visible(true);
+    if app.image.is_some() {
+        app.status = Image;
+    } else {
+        app.status = Start;
+    }
+}
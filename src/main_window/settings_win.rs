@@ -4,7 +4,9 @@ use eframe::egui::scroll_area::ScrollBarVisibility;
 use keyboard_types::Code;
 use crate::hotkey_popup::*;
 use crate::image_proc::extensions::Extensions;
+use crate::image_proc::set_history_memory_limit;
 use crate::main_window::MyApp;
+use crate::screensh::recording::RecordingFormat;
 
 pub fn settings_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Frame){
     egui::CentralPanel::default().show(ctx, |ui| {
@@ -15,7 +17,7 @@ pub fn settings_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe
             .show(ui, |ui|{
                 ui.style_mut().visuals.override_text_color = Some(egui::Color32::WHITE);
                 ui.heading("Settings Editor");
-                let labels = ["New capture", "Delay capture", "Crop capture", "Draw capture", "Text capture", "Copy to clipboard", "Save capture"];
+                let labels = ["New capture", "Delay capture", "Crop capture", "Draw capture", "Text capture", "Copy to clipboard", "Save capture", "Region capture", "Interval capture", "Record screen", "Scroll capture"];
                 for (i, l) in labels.iter().enumerate(){
                     ui.label(l.to_string());
                     ui.horizontal(|ui| {
@@ -76,6 +78,17 @@ pub fn settings_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe
                                 app.delay_secs = app.delay_secs_cp;
                             }
 
+                            if i==8{
+                                app.interval_secs = app.interval_secs_cp;
+                                app.interval_shots = app.interval_shots_cp;
+                            }
+
+                            if i==9{
+                                app.record_fps = app.record_fps_cp;
+                                app.record_secs = app.record_secs_cp;
+                                app.record_format = app.record_format_cp;
+                            }
+
                             let hotkey_old = app.hk.get_shortcuts(i).get_immut_hotkey();
 
                             let op = Operation::new(hotk, l.to_string(), alt, shift, ctrl, sel_key);
@@ -109,6 +122,58 @@ pub fn settings_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe
                         });
                     }
 
+                    if i==8{
+                        ui.horizontal(|ui|{
+                            ui.add_space(15.0);
+                            egui::ComboBox::from_label("> Seconds between shots").width(5.0)
+                                .selected_text(format!("{}", app.interval_secs_cp))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut app.interval_secs_cp, 1u32, "1");
+                                    ui.selectable_value(&mut app.interval_secs_cp, 5u32, "5");
+                                    ui.selectable_value(&mut app.interval_secs_cp, 10u32, "10");
+                                    ui.selectable_value(&mut app.interval_secs_cp, 30u32, "30");
+                                    ui.selectable_value(&mut app.interval_secs_cp, 60u32, "60");
+                                });
+                            egui::ComboBox::from_label("> Shots").width(5.0)
+                                .selected_text(match app.interval_shots_cp {
+                                    0 => String::from("∞"),
+                                    n => format!("{}", n)
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut app.interval_shots_cp, 10u32, "10");
+                                    ui.selectable_value(&mut app.interval_shots_cp, 50u32, "50");
+                                    ui.selectable_value(&mut app.interval_shots_cp, 100u32, "100");
+                                    ui.selectable_value(&mut app.interval_shots_cp, 0u32, "∞");
+                                });
+                        });
+                    }
+
+                    if i==9{
+                        ui.horizontal(|ui|{
+                            ui.add_space(15.0);
+                            egui::ComboBox::from_label("> Frames per second").width(5.0)
+                                .selected_text(format!("{}", app.record_fps_cp))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut app.record_fps_cp, 5u32, "5");
+                                    ui.selectable_value(&mut app.record_fps_cp, 10u32, "10");
+                                    ui.selectable_value(&mut app.record_fps_cp, 15u32, "15");
+                                });
+                            egui::ComboBox::from_label("> Max seconds").width(5.0)
+                                .selected_text(format!("{}", app.record_secs_cp))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut app.record_secs_cp, 5u32, "5");
+                                    ui.selectable_value(&mut app.record_secs_cp, 10u32, "10");
+                                    ui.selectable_value(&mut app.record_secs_cp, 20u32, "20");
+                                });
+                            egui::ComboBox::from_label("> Format").width(5.0)
+                                .selected_text(format!("{}", app.record_format_cp))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut app.record_format_cp, RecordingFormat::Gif, "GIF");
+                                    ui.selectable_value(&mut app.record_format_cp, RecordingFormat::Apng, "APNG");
+                                });
+                        });
+                    }
+
                     if app.forbidden_hk[i]{
                         ui.horizontal(|ui|{
                             ui.add_space(15.0);
@@ -136,6 +201,39 @@ pub fn settings_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe
                     }
                 });
 
+                ui.add_space(10.0);
+                ui.heading("Mouse Cursor");
+                ui.horizontal(|ui|{
+                    ui.add_space(15.0);
+                    let cursor_label = ui.label("Include the mouse cursor in the captures: ");
+                    ui.add_enabled(app.cursor_sprite.is_some(), egui::Checkbox::new(&mut app.include_cursor_cp, ""))
+                        .labelled_by(cursor_label.id);
+
+                    if ui.add(egui::Button::new("OK")).clicked() {
+                        app.include_cursor = app.include_cursor_cp;
+                    }
+                });
+
+                ui.add_space(10.0);
+                ui.heading("Undo History");
+                ui.horizontal(|ui|{
+                    ui.add_space(15.0);
+                    egui::ComboBox::from_label("> Memory per image (MB), the oldest edits are forgotten beyond it").width(15.0)
+                        .selected_text(format!("{}", app.history_mb_cp))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut app.history_mb_cp, 128u32, "128");
+                            ui.selectable_value(&mut app.history_mb_cp, 256u32, "256");
+                            ui.selectable_value(&mut app.history_mb_cp, 512u32, "512");
+                            ui.selectable_value(&mut app.history_mb_cp, 1024u32, "1024");
+                            ui.selectable_value(&mut app.history_mb_cp, 2048u32, "2048");
+                        });
+
+                    if ui.add(egui::Button::new("OK")).clicked() {
+                        app.history_mb = app.history_mb_cp;
+                        set_history_memory_limit(app.history_mb as usize * 1024 * 1024);
+                    }
+                });
+
                 ui.add_space(10.0);
                 ui.heading("Save Directory");
                 ui.horizontal(|ui|{
@@ -167,6 +265,13 @@ pub fn settings_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe
                     app.extension_copy = app.extension;
                     app.save_path_copy = app.save_path.clone();
                     app.delay_secs_cp = app.delay_secs;
+                    app.interval_secs_cp = app.interval_secs;
+                    app.interval_shots_cp = app.interval_shots;
+                    app.record_fps_cp = app.record_fps;
+                    app.record_secs_cp = app.record_secs;
+                    app.record_format_cp = app.record_format;
+                    app.include_cursor_cp = app.include_cursor;
+                    app.history_mb_cp = app.history_mb;
                 }
             });
 
@@ -8,10 +8,16 @@ use crate::main_window::Status::*;
 use crate::cursor_scaling::*;
 
 use crate::image_proc::get_image_from_memory;
+use crate::image_proc::Image;
+use crate::image_proc::annotation::TextAlign;
+use crate::image_proc::colors::Color;
 
-use crate::main_window::{min_my, MyApp};
+use crate::main_window::eyedropper::{apply_picked, eyedropper, eyedropper_buttons};
+use crate::main_window::palette::{opacity_slider, palette_swatches};
+use crate::main_window::{history_shortcuts, min_my, MyApp};
 
 pub fn text_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Frame){
+    let (undo_key, redo_key) = history_shortcuts(ctx);
     egui::CentralPanel::default().show(ctx, |ui| {
         egui::ScrollArea::vertical()
             .scroll_bar_visibility(ScrollBarVisibility::AlwaysVisible)
@@ -58,7 +64,7 @@ pub fn text_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                         });
 
                     ui.style_mut().visuals.widgets.hovered.bg_stroke.color = egui::Color32::WHITE;
-                    ui.add(egui::TextEdit::singleline(&mut app.image_text)).highlight();
+                    ui.add(egui::TextEdit::multiline(&mut app.image_text).desired_rows(2)).highlight();
 
                 });
 
@@ -78,12 +84,53 @@ pub fn text_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
 
                         if ui.add(egui::Button::new("OK")).clicked() {
                             app.is_sel_color = false;
+                            app.palette.use_color(app.sel_color);
                         }
                     }
+
+                    opacity_slider(ui, &mut app.sel_color);
+                    eyedropper_buttons(app, ui);
+                    let save_dir = app.save_location();
+                    palette_swatches(&mut app.palette, ui, &mut app.sel_color, &save_dir);
                 });
 
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_label("Align")
+                        .selected_text(format!("{}", app.text_align))
+                        .show_ui(ui, |ui| {
+                            app.dropdown_on = true;
+                            ui.selectable_value(&mut app.text_align, TextAlign::Left, "Left");
+                            ui.selectable_value(&mut app.text_align, TextAlign::Center, "Center");
+                            ui.selectable_value(&mut app.text_align, TextAlign::Right, "Right");
+                        });
+                    ui.add(egui::Slider::new(&mut app.text_max_width, 0..=2000).text("Wrap width (0 = off)"));
+
+                    ui.checkbox(&mut app.text_box, "Box");
+                    if app.text_box {
+                        color_button(ui, &mut app.text_box_color);
+                        opacity_slider(ui, &mut app.text_box_color);
+                        ui.add(egui::Slider::new(&mut app.text_padding, 0..=40).text("Padding"));
+                    }
+
+                    ui.checkbox(&mut app.text_outline, "Outline");
+                    if app.text_outline {
+                        color_button(ui, &mut app.text_outline_color);
+                        ui.add(egui::Slider::new(&mut app.text_outline_width, 1..=8).text("Outline size"));
+                    }
+                });
+
+                //the color pickers of the box and of the outline are popups over the image
+                if ctx.memory(|m| m.any_popup_open()) {
+                    app.dropdown_on = true;
+                }
+
+                if let Some(color) = eyedropper(app, ctx, offset, 60.0) {
+                    apply_picked(&mut app.sel_color, color);
+                    app.palette.use_color(app.sel_color);
+                }
+
                 match ctx.input(|i| i.pointer.hover_pos()) {
-                    None => {}
+                    None => hide_preview(app, ctx),
                     Some(pos) => {
                         let scroll = ctx.input(|i| i.scroll_delta).y;
 
@@ -102,19 +149,24 @@ pub fn text_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
 
                         if pos.x - offset > 0.0 && pos.x - offset < image_size.x * app.window_image_ratio
                             && pos.y+app.scroll_qty > 51.0 && pos.y+app.scroll_qty < (image_size.y * app.window_image_ratio + 51.0) && !app.any_pressed && !app.dropdown_on {
+                            let start = cursor_position(((pos.x-offset) as u32, (pos.y-60.0+app.scroll_qty) as u32), app.window_image_ratio);
+                            let start = (start.0 as i32, start.1 as i32);
+                            let text = Image::text(
+                                start,
+                                &app.sel_color,
+                                app.image_text.as_str(),
+                                (app.sel_font_size as f32)*5.0,
+                                app.sel_font.as_ref().unwrap().as_str(),
+                                app.fonts.as_ref().unwrap().get(app.sel_font.as_ref().unwrap().as_str()).unwrap(),
+                                app.text_style()
+                            );
                             match ctx.input(|i| i.pointer.any_pressed()) {
                                 true => {
                                     app.any_pressed = true;
 
-                                    let start = cursor_position(((pos.x-offset) as u32, (pos.y-60.0+app.scroll_qty) as u32), app.window_image_ratio);
-                                    let start = (start.0 as i32, start.1 as i32);
-                                    app.backup_image_to_save.as_mut().unwrap().put_text(
-                                        start,
-                                        &app.sel_color,
-                                        app.image_text.as_str(),
-                                        (app.sel_font_size as f32)*5.0,
-                                        app.fonts.as_ref().unwrap().get(app.sel_font.as_ref().unwrap().as_str()).unwrap()
-                                    );
+                                    app.backup_image_to_save.as_mut().unwrap().push_annotation(text);
+                                    app.palette.use_color(app.sel_color);
+                                    app.text_preview = None;
                                     app.any_pressed = false;
                                     app.backup_image = Some(ctx.load_texture(
                                         "my-image",
@@ -122,9 +174,22 @@ pub fn text_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                                         Default::default()
                                     ));
                                 }
-                                false => {}
+                                false => {
+                                    //the preview is drawn again only when the cursor or the text change
+                                    if app.text_preview.as_ref() != Some(&text) {
+                                        app.backup_image = Some(ctx.load_texture(
+                                            "my-image",
+                                            get_image_from_memory(app.backup_image_to_save.as_ref().unwrap().preview(&text), 0, 0, 1, 1),
+                                            Default::default()
+                                        ));
+                                        app.text_preview = Some(text);
+                                    }
+                                }
                             }
                         }
+                        else {
+                            hide_preview(app, ctx);
+                        }
                     }
                 }
 
@@ -136,9 +201,11 @@ pub fn text_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
 
                 ui.horizontal(|ui| {
                     if ui.add(egui::Button::new("OK")).clicked() {
+                        app.eyedropper = None;
                         app.status = app.prev;
                         app.prev = Text;
                         app.any_pressed = false;
+                        app.text_preview = None;
                         app.image_text = String::from("Insert text here");
                         app.backup_image = Some(ctx.load_texture(
                             "my-image",
@@ -158,7 +225,8 @@ pub fn text_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
 
                     }
 
-                    if ui.add(egui::Button::new("↩")).clicked(){
+                    if ui.add(egui::Button::new("↩")).on_hover_text("Undo last edit").clicked() || undo_key {
+                        app.text_preview = None;
                         let di = app.backup_image_to_save.as_mut().unwrap().undo();
                         app.backup_image = Some(ctx.load_texture(
                             "my-image",
@@ -167,8 +235,20 @@ pub fn text_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                         ));
                     }
 
+                    if ui.add_enabled(app.backup_image_to_save.as_ref().unwrap().can_redo(), egui::Button::new("↪")).on_hover_text("Redo last undone edit").clicked() || redo_key {
+                        app.text_preview = None;
+                        let di = app.backup_image_to_save.as_mut().unwrap().redo();
+                        app.backup_image = Some(ctx.load_texture(
+                            "my-image",
+                            get_image_from_memory(di, 0, 0, 1, 1),
+                            Default::default()
+                        ));
+                    }
+
                     if ui.add(egui::Button::new("Back")).clicked() {
+                        app.eyedropper = None;
                         app.any_pressed = false;
+                        app.text_preview = None;
                         app.backup_image = app.image.clone();
                         app.backup_image_to_save = app.image_to_save.clone();
                         app.status = Image;
@@ -183,4 +263,24 @@ pub fn text_window(app: &mut MyApp, ctx: &egui::Context, _frame: &mut eframe::Fr
                 });
             });
     });
+}
+
+///Removes the preview of the text from the image shown, if any
+fn hide_preview(app: &mut MyApp, ctx: &egui::Context) {
+    if app.text_preview.take().is_some() {
+        app.backup_image = Some(ctx.load_texture(
+            "my-image",
+            get_image_from_memory(app.backup_image_to_save.as_ref().unwrap().show(), 0, 0, 1, 1),
+            Default::default()
+        ));
+    }
+}
+
+///Shows a button to edit the red, green and blue components of the color, leaving its alpha unchanged
+fn color_button(ui: &mut egui::Ui, color: &mut Color) {
+    let mut color_vec = [color.color.0[0], color.color.0[1], color.color.0[2]];
+    egui::widgets::color_picker::color_edit_button_srgb(ui, &mut color_vec);
+    color.color.0[0] = color_vec[0];
+    color.color.0[1] = color_vec[1];
+    color.color.0[2] = color_vec[2];
 }
\ No newline at end of file
@@ -1,64 +1,307 @@
 pub mod screensh_errors;
+pub mod capture_source;
+pub mod fake_source;
+pub mod virtual_desktop;
+pub mod window_list;
+pub mod recording;
+pub mod stitching;
 
-use std::fs::File;
-use std::io::Write;
+use eframe::egui;
+use image::DynamicImage;
+use image::imageops::{overlay, FilterType};
 use screensh_errors::ScreenshotError;
+use capture_source::{CaptureSource, ScreenGeometry, ScreenshotsSource};
+use fake_source::{FakeSource, FAKE_SCREENS_VAR};
+use virtual_desktop::compose_virtual_desktop;
+use window_list::WindowInfo;
 
 pub struct Screen {
-    screen: screenshots::Screen,
+    source: Box<dyn CaptureSource>,
 }
 
 pub struct Screenshot {
-    image: screenshots::Image,
+    image: DynamicImage,
 }
 
 impl Screen {
-    fn new(screen: screenshots::Screen) -> Self {
+    ///Returns a Screen grabbing its images from the given source
+    pub fn new(source: Box<dyn CaptureSource>) -> Self {
         Self {
-            screen: screen
+            source
         }
     }
+    ///Returns all the available screens.
+    ///If the SCREEN_CAPTURE_FAKE_SCREENS variable is set, the screens are the fake ones it describes,
+    ///otherwise the ones connected to the machine are used
     pub fn get_screens() -> Result<Vec<Screen>,ScreenshotError> {
-        match screenshots::Screen::all() {
-            Ok(screens) => {
-                let mut v = vec![];
-                for screen in screens {
-                    v.push(Screen::new(screen));
-                }
-                return Ok(v);
+        if let Ok(specs) = std::env::var(FAKE_SCREENS_VAR) {
+            let sources = FakeSource::all_from_spec(&specs)?;
+            return Ok(sources.into_iter().map(|s| Screen::new(Box::new(s))).collect());
+        }
+        let sources = ScreenshotsSource::all()?;
+        Ok(sources.into_iter().map(|s| Screen::new(Box::new(s))).collect())
+    }
+    ///Returns the position and size of the screen in the virtual desktop
+    pub fn geometry(&self) -> ScreenGeometry {
+        self.source.geometry()
+    }
+    ///Captures the screen. If a cursor sprite is given, it is drawn where the mouse pointer is
+    pub fn capture(&self, cursor: Option<&DynamicImage>) -> Result<Screenshot,ScreenshotError> {
+        let image = self.grab(cursor)?;
+        Ok(Screenshot::new(image))
+    }
+    fn grab(&self, cursor: Option<&DynamicImage>) -> Result<DynamicImage,ScreenshotError> {
+        let mut image = self.source.capture()?;
+        if let (Some(sprite), Some((x, y))) = (cursor, self.source.cursor_position()) {
+            //the sprite is meant for a scale factor of 1, so it is enlarged on HiDPI screens
+            let density = image.width() as f32 / self.geometry().width.max(1) as f32;
+            if density > 1.0 {
+                let w = (sprite.width() as f32 * density) as u32;
+                let h = (sprite.height() as f32 * density) as u32;
+                overlay(&mut image, &sprite.resize_exact(w, h, FilterType::Nearest), x as i64, y as i64);
+            } else {
+                overlay(&mut image, sprite, x as i64, y as i64);
             }
-            Err(_) => Err(ScreenshotError::ScreenRetvError)
         }
+        Ok(image)
     }
-    pub fn capture(&self) -> Result<Screenshot,ScreenshotError> {
-        match self.screen.capture() {
-            Ok(image) => {
-                return Ok(Screenshot::new(image));
-            } 
-            Err(_) => Err(ScreenshotError::ScreenCaptureError)
+    ///Captures all the given screens and composes them in a single image of the whole virtual desktop
+    pub fn capture_virtual_desktop(screens: &[Screen], cursor: Option<&DynamicImage>) -> Result<Screenshot,ScreenshotError> {
+        let mut captures = Vec::new();
+        for screen in screens {
+            captures.push((screen.geometry(), screen.grab(cursor)?));
+        }
+        match compose_virtual_desktop(&captures) {
+            Some(image) => Ok(Screenshot::new(image)),
+            None => Err(ScreenshotError::ScreenRetvError)
+        }
+    }
+    ///Captures the area of the virtual desktop covered by the given window.
+    ///Only the screens the window is shown on are captured
+    pub fn capture_window(screens: &[Screen], window: &WindowInfo, cursor: Option<&DynamicImage>) -> Result<Screenshot,ScreenshotError> {
+        let (wx0, wy0) = (window.x, window.y);
+        let (wx1, wy1) = (window.x + window.width as i32, window.y + window.height as i32);
+
+        let mut captures = Vec::new();
+        for screen in screens {
+            let g = screen.geometry();
+            if g.x < wx1 && g.x + (g.width as i32) > wx0 && g.y < wy1 && g.y + (g.height as i32) > wy0 {
+                captures.push((g, screen.grab(cursor)?));
+            }
+        }
+        if captures.is_empty() {
+            return Err(ScreenshotError::ScreenCaptureError);
         }
+
+        let min_x = captures.iter().map(|(g, _)| g.x).min().unwrap();
+        let min_y = captures.iter().map(|(g, _)| g.y).min().unwrap();
+        let max_x = captures.iter().map(|(g, _)| g.x + g.width as i32).max().unwrap();
+        let max_y = captures.iter().map(|(g, _)| g.y + g.height as i32).max().unwrap();
+        let desktop = compose_virtual_desktop(&captures).ok_or(ScreenshotError::ScreenCaptureError)?;
+        let density = desktop.width() as f32 / (max_x - min_x) as f32;
+
+        //the parts of the window outside of any screen are left out
+        let x0 = (i32::max(wx0, min_x) - min_x) as f32 * density;
+        let y0 = (i32::max(wy0, min_y) - min_y) as f32 * density;
+        let x1 = (i32::min(wx1, max_x) - min_x) as f32 * density;
+        let y1 = (i32::min(wy1, max_y) - min_y) as f32 * density;
+        let image = desktop.crop_imm(x0 as u32, y0 as u32, (x1 - x0).max(1.0) as u32, (y1 - y0).max(1.0) as u32);
+
+        Ok(Screenshot::new(image))
     }
-    
+
 }
 
 impl Screenshot {
-    fn new(image: screenshots::Image) -> Self {
-        Self { image: image }
+    fn new(image: DynamicImage) -> Self {
+        Self { image }
     }
-    fn serialize(&self) -> Result<Vec<u8>, ScreenshotError> {
-        match self.image.to_png(None) {
-            Ok(image) => {
-                return Ok(image);
-            }
-            Err(_) => Err(ScreenshotError::ImageProcessError)
+    ///Returns the captured image as a DynamicImage
+    pub fn to_dynamic_image(&self) -> DynamicImage {
+        self.image.clone()
+    }
+    ///Returns the captured image as an egui::ColorImage, ready to be loaded as a texture
+    pub fn to_color_image(&self) -> egui::ColorImage {
+        let size = [self.image.width() as _, self.image.height() as _];
+        egui::ColorImage::from_rgba_unmultiplied(
+            size,
+            self.image.as_bytes()
+        )
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    ///Fake screen whose pointer is always in the same position
+    struct PointerSource {
+        source: FakeSource,
+        geometry: ScreenGeometry,
+        pointer: (i32, i32)
+    }
+
+    impl CaptureSource for PointerSource {
+        fn geometry(&self) -> ScreenGeometry {
+            self.geometry
         }
+        fn capture(&self) -> Result<DynamicImage, ScreenshotError> {
+            self.source.capture()
+        }
+        fn cursor_position(&self) -> Option<(i32, i32)> {
+            Some(self.pointer)
+        }
+    }
+
+    fn plain(width: u32, height: u32, color: Rgba<u8>) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(width, height, color))
+    }
+
+    fn fake_screen(spec: &str) -> Screen {
+        Screen::new(Box::new(FakeSource::from_spec(spec).unwrap()))
+    }
+
+    #[test]
+    fn spec_of_synthetic_screen() {
+        let screen = fake_screen(" 200x100+-50+30 ");
+        assert_eq!(screen.geometry(), ScreenGeometry::new(-50, 30, 200, 100, 1.0));
+        let image = screen.capture(None).unwrap().to_dynamic_image().to_rgba8();
+        assert_eq!(image.dimensions(), (200, 100));
+        assert_eq!(*image.get_pixel(0, 0), Rgba([0, 0, 254, 255]));
+        assert_eq!(*image.get_pixel(100, 50), Rgba([127, 127, 254, 255]));
+    }
+
+    #[test]
+    fn spec_of_screen_from_file() {
+        let path = std::env::temp_dir().join("fake_screen_spec.png");
+        plain(30, 20, Rgba([1, 2, 3, 255])).save(&path).unwrap();
+        let screen = fake_screen(&format!("{}+10+-5", path.display()));
+        assert_eq!(screen.geometry(), ScreenGeometry::new(10, -5, 30, 20, 1.0));
+        assert_eq!(*screen.capture(None).unwrap().to_dynamic_image().to_rgba8().get_pixel(29, 19), Rgba([1, 2, 3, 255]));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn scaled_synthetic_screen_is_captured_in_physical_pixels() {
+        let screen = fake_screen("200x100+0+0@2");
+        assert_eq!(screen.geometry(), ScreenGeometry::new(0, 0, 200, 100, 2.0));
+        let image = screen.capture(None).unwrap().to_dynamic_image().to_rgba8();
+        assert_eq!(image.dimensions(), (400, 200));
+    }
+
+    #[test]
+    fn scaled_screen_from_file_has_logical_size() {
+        let path = std::env::temp_dir().join("fake_screen_scaled_spec.png");
+        plain(60, 40, Rgba([1, 2, 3, 255])).save(&path).unwrap();
+        let screen = fake_screen(&format!("{}+0+0@2", path.display()));
+        assert_eq!(screen.geometry(), ScreenGeometry::new(0, 0, 30, 20, 2.0));
+        assert_eq!(screen.capture(None).unwrap().to_dynamic_image().to_rgba8().dimensions(), (60, 40));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn malformed_specs_are_rejected() {
+        for spec in ["", "200x100", "200x100+0", "200x100+a+0", "200x100+0+b", "+0+0", "0x100+0+0", "missing.png+0+0", "200x100+0+0@0", "200x100+0+0@-1", "200x100+0+0@x"] {
+            assert!(FakeSource::from_spec(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn list_of_specs() {
+        let sources = FakeSource::all_from_spec("100x50+0+0; ;80x60+100+0;").unwrap();
+        let geometries: Vec<ScreenGeometry> = sources.iter().map(|s| s.geometry()).collect();
+        assert_eq!(geometries, vec![ScreenGeometry::new(0, 0, 100, 50, 1.0), ScreenGeometry::new(100, 0, 80, 60, 1.0)]);
+        assert!(FakeSource::all_from_spec("100x50+0+0;80x60").is_err());
+    }
+
+    #[test]
+    fn cursor_is_drawn_at_the_pointer() {
+        let source = FakeSource::from_image(plain(40, 40, Rgba([0, 0, 0, 255])), 0, 0);
+        let screen = Screen::new(Box::new(PointerSource { geometry: source.geometry(), source, pointer: (10, 20) }));
+        let sprite = plain(2, 2, Rgba([255, 255, 255, 255]));
+        let image = screen.capture(Some(&sprite)).unwrap().to_dynamic_image().to_rgba8();
+        assert_eq!(*image.get_pixel(11, 21), Rgba([255, 255, 255, 255]));
+        assert_eq!(*image.get_pixel(12, 22), Rgba([0, 0, 0, 255]));
+        assert_eq!(*screen.capture(None).unwrap().to_dynamic_image().to_rgba8().get_pixel(11, 21), Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn cursor_is_enlarged_on_hidpi_screens() {
+        //the image has twice the pixels of the screen
+        let source = FakeSource::from_image(plain(40, 40, Rgba([0, 0, 0, 255])), 0, 0);
+        let geometry = ScreenGeometry::new(0, 0, 20, 20, 2.0);
+        let screen = Screen::new(Box::new(PointerSource { source, geometry, pointer: (10, 20) }));
+        let sprite = plain(2, 2, Rgba([255, 255, 255, 255]));
+        let image = screen.capture(Some(&sprite)).unwrap().to_dynamic_image().to_rgba8();
+        assert_eq!(*image.get_pixel(13, 23), Rgba([255, 255, 255, 255]));
+        assert_eq!(*image.get_pixel(14, 24), Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn virtual_desktop_of_fake_screens() {
+        let screens = vec![
+            Screen::new(Box::new(FakeSource::from_image(plain(100, 50, Rgba([255, 0, 0, 255])), -100, 0))),
+            Screen::new(Box::new(FakeSource::from_image(plain(80, 60, Rgba([0, 255, 0, 255])), 0, 0)))
+        ];
+        let image = Screen::capture_virtual_desktop(&screens, None).unwrap().to_dynamic_image().to_rgba8();
+        assert_eq!(image.dimensions(), (180, 60));
+        assert_eq!(*image.get_pixel(99, 49), Rgba([255, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(100, 0), Rgba([0, 255, 0, 255]));
+        assert_eq!(*image.get_pixel(179, 59), Rgba([0, 255, 0, 255]));
+        //below the shorter screen nothing is shown
+        assert_eq!(*image.get_pixel(50, 55), Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn virtual_desktop_needs_a_screen() {
+        assert!(Screen::capture_virtual_desktop(&[], None).is_err());
+    }
+
+    fn window(x: i32, y: i32, width: u32, height: u32) -> WindowInfo {
+        WindowInfo { id: 1, title: String::from("window"), x, y, width, height }
+    }
+
+    ///Returns a screen whose pixels hold their own position in the virtual desktop, in the red and green channels
+    fn positional_screen(x: i32, y: i32, width: u32, height: u32) -> Screen {
+        let image = RgbaImage::from_fn(width, height, |px, py| Rgba([(x + px as i32) as u8, (y + py as i32) as u8, 0, 255]));
+        Screen::new(Box::new(FakeSource::from_image(DynamicImage::ImageRgba8(image), x, y)))
+    }
+
+    #[test]
+    fn window_inside_a_screen() {
+        let screens = vec![positional_screen(0, 0, 100, 80)];
+        let image = Screen::capture_window(&screens, &window(10, 20, 30, 40), None).unwrap().to_dynamic_image().to_rgba8();
+        assert_eq!(image.dimensions(), (30, 40));
+        assert_eq!(*image.get_pixel(0, 0), Rgba([10, 20, 0, 255]));
+        assert_eq!(*image.get_pixel(29, 39), Rgba([39, 59, 0, 255]));
+    }
+
+    #[test]
+    fn window_partly_off_screen_is_cropped_to_the_screen() {
+        let screens = vec![positional_screen(0, 0, 100, 80)];
+        let image = Screen::capture_window(&screens, &window(-20, 60, 50, 40), None).unwrap().to_dynamic_image().to_rgba8();
+        assert_eq!(image.dimensions(), (30, 20));
+        assert_eq!(*image.get_pixel(0, 0), Rgba([0, 60, 0, 255]));
+        assert_eq!(*image.get_pixel(29, 19), Rgba([29, 79, 0, 255]));
+    }
+
+    #[test]
+    fn window_spanning_two_screens() {
+        let screens = vec![positional_screen(0, 0, 100, 80), positional_screen(100, 10, 60, 50), positional_screen(0, 80, 100, 20)];
+        let image = Screen::capture_window(&screens, &window(90, 20, 30, 10), None).unwrap().to_dynamic_image().to_rgba8();
+        assert_eq!(image.dimensions(), (30, 10));
+        assert_eq!(*image.get_pixel(0, 0), Rgba([90, 20, 0, 255]));
+        assert_eq!(*image.get_pixel(9, 9), Rgba([99, 29, 0, 255]));
+        assert_eq!(*image.get_pixel(10, 0), Rgba([100, 20, 0, 255]));
+        assert_eq!(*image.get_pixel(29, 9), Rgba([119, 29, 0, 255]));
     }
-    pub fn save(&self) -> Result<(),ScreenshotError> {
-        let mut file = File::create(".tmp.png")?;
-        let buffer = self.serialize()?;
-        file.write(&buffer)?;
 
-        Ok(())
+    #[test]
+    fn window_on_no_screen_is_not_captured() {
+        let screens = vec![positional_screen(0, 0, 100, 80)];
+        assert!(Screen::capture_window(&screens, &window(200, 0, 30, 40), None).is_err());
     }
-    
 }
\ No newline at end of file
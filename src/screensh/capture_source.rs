@@ -0,0 +1,122 @@
+#[cfg(target_os = "linux")]
+use std::sync::OnceLock;
+use image::{DynamicImage, RgbaImage};
+use super::screensh_errors::ScreenshotError;
+
+///Position and size of a screen inside the virtual desktop, as reported by the capture backend
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32
+}
+
+impl ScreenGeometry {
+    pub fn new(x: i32, y: i32, width: u32, height: u32, scale_factor: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            scale_factor
+        }
+    }
+}
+
+///Source of the images grabbed for a single screen.
+///Every Screen owns one, so the capture logic does not depend on the backend actually used
+pub trait CaptureSource {
+    ///Returns the geometry of the screen served by this source
+    fn geometry(&self) -> ScreenGeometry;
+    ///Grabs the content currently shown by the screen
+    fn capture(&self) -> Result<DynamicImage, ScreenshotError>;
+    ///Returns the position of the mouse pointer inside the image returned by capture,
+    ///or None if the pointer is not on this screen or its position is not known
+    fn cursor_position(&self) -> Option<(i32, i32)> {
+        None
+    }
+}
+
+///Capture source backed by the screenshots crate, used when running on a real display
+pub struct ScreenshotsSource {
+    screen: screenshots::Screen
+}
+
+impl ScreenshotsSource {
+    ///Returns a source for every screen connected to the machine
+    pub fn all() -> Result<Vec<ScreenshotsSource>, ScreenshotError> {
+        match screenshots::Screen::all() {
+            Ok(screens) => {
+                Ok(screens.into_iter().map(|screen| ScreenshotsSource { screen }).collect())
+            }
+            Err(_) => Err(ScreenshotError::ScreenRetvError)
+        }
+    }
+}
+
+impl CaptureSource for ScreenshotsSource {
+    fn geometry(&self) -> ScreenGeometry {
+        let info = self.screen.display_info;
+        ScreenGeometry::new(info.x, info.y, info.width, info.height, info.scale_factor)
+    }
+    fn capture(&self) -> Result<DynamicImage, ScreenshotError> {
+        let image = self.screen.capture().map_err(|_| ScreenshotError::ScreenCaptureError)?;
+        let width = image.width();
+        let height = image.height();
+        let buffer: Vec<u8> = image.into();
+        match RgbaImage::from_raw(width, height, buffer) {
+            Some(image) => Ok(DynamicImage::ImageRgba8(image)),
+            None => Err(ScreenshotError::ImageProcessError)
+        }
+    }
+    fn cursor_position(&self) -> Option<(i32, i32)> {
+        let info = self.screen.display_info;
+        let (x, y) = pointer_position()?;
+        //the pointer is reported in physical pixels, like the captured image
+        let x0 = (info.x as f32 * info.scale_factor) as i32;
+        let y0 = (info.y as f32 * info.scale_factor) as i32;
+        let x1 = x0 + (info.width as f32 * info.scale_factor) as i32;
+        let y1 = y0 + (info.height as f32 * info.scale_factor) as i32;
+        if x >= x0 && x < x1 && y >= y0 && y < y1 {
+            Some((x - x0, y - y0))
+        } else {
+            None
+        }
+    }
+}
+
+///Connection to the X server, along with the root window of its default screen
+#[cfg(target_os = "linux")]
+pub struct X11Connection {
+    pub conn: xcb::Connection,
+    pub root: xcb::x::Window
+}
+
+///Returns the connection to the X server shared by the capture sources and the window list, opened the first time
+///it is needed so that capturing many frames does not open one each time. Returns None if there is no X server
+#[cfg(target_os = "linux")]
+pub fn x11_connection() -> Option<&'static X11Connection> {
+    static CONNECTION: OnceLock<Option<X11Connection>> = OnceLock::new();
+    CONNECTION.get_or_init(|| {
+        let (conn, index) = xcb::Connection::connect(None).ok()?;
+        let root = conn.get_setup().roots().nth(index as usize)?.root();
+        Some(X11Connection { conn, root })
+    }).as_ref()
+}
+
+///Returns the position of the mouse pointer in the virtual desktop, in physical pixels
+#[cfg(target_os = "linux")]
+fn pointer_position() -> Option<(i32, i32)> {
+    let x11 = x11_connection()?;
+    let cookie = x11.conn.send_request(&xcb::x::QueryPointer { window: x11.root });
+    let reply = x11.conn.wait_for_reply(cookie).ok()?;
+    Some((reply.root_x() as i32, reply.root_y() as i32))
+}
+
+//the pointer position is only available on X11 for now
+#[cfg(not(target_os = "linux"))]
+fn pointer_position() -> Option<(i32, i32)> {
+    None
+}
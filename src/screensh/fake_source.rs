@@ -0,0 +1,103 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+use super::capture_source::{CaptureSource, ScreenGeometry};
+use super::screensh_errors::ScreenshotError;
+
+///Name of the environment variable used to replace the real screens with fake ones.
+///It holds a list of screens separated by ';', each one being either
+///WIDTHxHEIGHT+X+Y for a synthetic screen or PATH+X+Y for an image loaded from disk.
+///Both can end with @SCALE to set the scale factor of the screen, e.g. 1920x1080+0+0@2:
+///the size of the screen stays logical while the captured images are in physical pixels
+pub const FAKE_SCREENS_VAR: &str = "SCREEN_CAPTURE_FAKE_SCREENS";
+
+///Capture source that does not need a display: it always returns the same image,
+///either generated or loaded from disk, with the given geometry
+#[derive(Debug, Clone)]
+pub struct FakeSource {
+    geometry: ScreenGeometry,
+    image: DynamicImage
+}
+
+impl FakeSource {
+    ///Returns a source serving a deterministic synthetic image with the given geometry.
+    ///The pattern depends on the position of the screen, so different screens give different images.
+    ///The image has the physical size of the screen, that is its size times the scale factor
+    pub fn synthetic(geometry: ScreenGeometry) -> Self {
+        let width = (geometry.width as f32 * geometry.scale_factor).round() as u32;
+        let height = (geometry.height as f32 * geometry.scale_factor).round() as u32;
+        let w = width.max(1);
+        let h = height.max(1);
+        let seed = (geometry.x.wrapping_add(geometry.y) / 8) as u8;
+        let image = RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([(x * 255 / w) as u8, (y * 255 / h) as u8, seed, 255])
+        });
+        Self {
+            geometry,
+            image: DynamicImage::ImageRgba8(image)
+        }
+    }
+    ///Returns a source serving the image stored at the given path, placed at the given
+    ///position of the virtual desktop. The size of the screen is the one of the image
+    pub fn from_file(path: &str, x: i32, y: i32) -> Result<Self, ScreenshotError> {
+        let image = image::open(path).map_err(|_| ScreenshotError::ImageProcessError)?;
+        Ok(Self::from_image(image, x, y))
+    }
+    ///Returns a source serving the given image, placed at the given position of the virtual desktop
+    pub fn from_image(image: DynamicImage, x: i32, y: i32) -> Self {
+        let geometry = ScreenGeometry::new(x, y, image.width(), image.height(), 1.0);
+        Self {
+            geometry,
+            image: DynamicImage::ImageRgba8(image.to_rgba8())
+        }
+    }
+    ///Returns the same source shown with the given scale factor: the image is kept as it is,
+    ///in physical pixels, while the size of the screen becomes the logical one
+    fn scaled(mut self, scale: f32) -> Self {
+        self.geometry.width = (self.image.width() as f32 / scale).round() as u32;
+        self.geometry.height = (self.image.height() as f32 / scale).round() as u32;
+        self.geometry.scale_factor = scale;
+        self
+    }
+    ///Parses a single screen specification, in the format described by FAKE_SCREENS_VAR
+    pub fn from_spec(spec: &str) -> Result<Self, ScreenshotError> {
+        //a suffix that is not a number is left in place, so paths like img@2x.png still work
+        let (screen, scale) = match spec.trim().rsplit_once('@') {
+            Some((screen, scale)) => match scale.parse::<f32>() {
+                Ok(scale) if scale.is_finite() && scale > 0.0 => (screen, scale),
+                Ok(_) => return Err(ScreenshotError::FakeSpecError(spec.to_string())),
+                Err(_) => (spec.trim(), 1.0)
+            },
+            None => (spec.trim(), 1.0)
+        };
+        let mut parts = screen.rsplitn(3, '+');
+        let y = parts.next().and_then(|p| p.parse::<i32>().ok());
+        let x = parts.next().and_then(|p| p.parse::<i32>().ok());
+        let source = parts.next();
+        let (x, y, source) = match (x, y, source) {
+            (Some(x), Some(y), Some(source)) if !source.is_empty() => (x, y, source),
+            _ => return Err(ScreenshotError::FakeSpecError(spec.to_string()))
+        };
+
+        let size = source.split_once('x')
+            .and_then(|(w, h)| Some((w.parse::<u32>().ok()?, h.parse::<u32>().ok()?)));
+        match size {
+            Some((w, h)) if w > 0 && h > 0 => Ok(Self::synthetic(ScreenGeometry::new(x, y, w, h, scale))),
+            _ => Ok(Self::from_file(source, x, y)?.scaled(scale))
+        }
+    }
+    ///Parses the content of FAKE_SCREENS_VAR, returning one source per screen
+    pub fn all_from_spec(specs: &str) -> Result<Vec<FakeSource>, ScreenshotError> {
+        specs.split(';')
+            .filter(|s| !s.trim().is_empty())
+            .map(FakeSource::from_spec)
+            .collect()
+    }
+}
+
+impl CaptureSource for FakeSource {
+    fn geometry(&self) -> ScreenGeometry {
+        self.geometry
+    }
+    fn capture(&self) -> Result<DynamicImage, ScreenshotError> {
+        Ok(self.image.clone())
+    }
+}
@@ -0,0 +1,270 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::BufWriter;
+use color_quant::NeuQuant;
+use image::{DynamicImage, RgbaImage};
+use image::imageops::FilterType;
+use super::screensh_errors::ScreenshotError;
+
+///Frames wider than this are scaled down while recording, so that long recordings do not take too much memory
+pub const MAX_FRAME_WIDTH: u32 = 1280;
+///Maximum number of pixels used to train the palette shared by all the frames
+const MAX_TRAINING_PIXELS: usize = 500_000;
+///Palette index of the pixels left unchanged from the previous frame
+const TRANSPARENT_INDEX: u8 = 255;
+
+///Format of the animated image written at the end of a recording
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordingFormat {
+    Gif,
+    Apng
+}
+
+impl RecordingFormat {
+    ///Returns the extension of the files written in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordingFormat::Gif => "gif",
+            RecordingFormat::Apng => "png"
+        }
+    }
+}
+
+impl Display for RecordingFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordingFormat::Gif => {
+                write!(f, "GIF")
+            }
+            RecordingFormat::Apng => {
+                write!(f, "APNG")
+            }
+        }
+    }
+}
+
+///Sequence of frames sampled from a screen, encoded as an animated image once the recording ends.
+///Consecutive identical frames are stored only once, the first one being shown for longer
+#[derive(Default)]
+pub struct Recording {
+    frames: Vec<(RgbaImage, f64)>,
+    end: f64
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Self {
+            frames: Vec::new(),
+            end: 0.0
+        }
+    }
+    ///Returns the number of distinct frames recorded so far
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+    ///Returns the last recorded frame, if any
+    pub fn last_frame(&self) -> Option<DynamicImage> {
+        self.frames.last().map(|(im, _)| DynamicImage::ImageRgba8(im.clone()))
+    }
+    ///Adds a frame captured at the given time, in seconds.
+    ///A frame equal to the previous one is dropped, so that the previous one lasts until the next change
+    pub fn push_frame(&mut self, image: DynamicImage, time: f64) {
+        let image = if image.width() > MAX_FRAME_WIDTH {
+            let height = (image.height() as f32 * MAX_FRAME_WIDTH as f32 / image.width() as f32).max(1.0) as u32;
+            image.resize_exact(MAX_FRAME_WIDTH, height, FilterType::Triangle).to_rgba8()
+        } else {
+            image.to_rgba8()
+        };
+        self.end = time;
+        match self.frames.last() {
+            Some((last, _)) if *last == image => {}
+            _ => self.frames.push((image, time))
+        }
+    }
+    ///Marks the end of the recording, which sets how long the last frame is shown
+    pub fn stop(&mut self, time: f64) {
+        self.end = f64::max(self.end, time);
+    }
+    ///Encodes the recording in the given format and writes it at the given path
+    pub fn save(&self, path: &str, format: RecordingFormat) -> Result<(), ScreenshotError> {
+        if self.is_empty() {
+            return Err(ScreenshotError::EncodingError(String::from("no frame was recorded")));
+        }
+        let (palette, frames) = self.quantize();
+        let delays = self.delays_ms();
+        let (width, height) = self.frames[0].0.dimensions();
+        let file = BufWriter::new(File::create(path)?);
+
+        match format {
+            RecordingFormat::Gif => {
+                let error = |e: gif::EncodingError| ScreenshotError::EncodingError(e.to_string());
+                let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &palette).map_err(error)?;
+                encoder.set_repeat(gif::Repeat::Infinite).map_err(error)?;
+                for (i, (indices, delay)) in frames.iter().zip(delays).enumerate() {
+                    let frame = gif::Frame {
+                        width: width as u16,
+                        height: height as u16,
+                        //gif delays are expressed in hundredths of second, and most viewers ignore the ones below 2
+                        delay: u32::max(delay / 10, 2) as u16,
+                        dispose: gif::DisposalMethod::Keep,
+                        transparent: if i > 0 { Some(TRANSPARENT_INDEX) } else { None },
+                        buffer: Cow::Borrowed(indices),
+                        ..gif::Frame::default()
+                    };
+                    encoder.write_frame(&frame).map_err(error)?;
+                }
+            }
+            RecordingFormat::Apng => {
+                let error = |e: png::EncodingError| ScreenshotError::EncodingError(e.to_string());
+                let mut trns = vec![255u8; TRANSPARENT_INDEX as usize];
+                trns.push(0);
+                let mut encoder = png::Encoder::new(file, width, height);
+                encoder.set_color(png::ColorType::Indexed);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_palette(palette);
+                encoder.set_trns(trns);
+                encoder.set_animated(frames.len() as u32, 0).map_err(error)?;
+                let mut writer = encoder.write_header().map_err(error)?;
+                for (i, (indices, delay)) in frames.iter().zip(delays).enumerate() {
+                    writer.set_frame_delay(u32::min(delay, u16::MAX as u32) as u16, 1000).map_err(error)?;
+                    if i > 0 {
+                        writer.set_blend_op(png::BlendOp::Over).map_err(error)?;
+                    }
+                    writer.write_image_data(indices).map_err(error)?;
+                }
+                writer.finish().map_err(error)?;
+            }
+        }
+        Ok(())
+    }
+    ///Returns how long each frame is shown, in milliseconds
+    fn delays_ms(&self) -> Vec<u32> {
+        let mut delays = Vec::new();
+        for (i, (_, start)) in self.frames.iter().enumerate() {
+            let end = match self.frames.get(i + 1) {
+                Some((_, next)) => *next,
+                None => self.end
+            };
+            delays.push(u32::max(((end - start) * 1000.0).round() as u32, 10));
+        }
+        delays
+    }
+    ///Reduces all the frames to a single palette of 255 colors, returning it along with the palette indices of each frame.
+    ///In every frame but the first one, the pixels equal to the previous frame get TRANSPARENT_INDEX,
+    ///which keeps the previous pixel and compresses much better
+    fn quantize(&self) -> (Vec<u8>, Vec<Vec<u8>>) {
+        let total: usize = self.frames.iter().map(|(im, _)| im.as_raw().len() / 4).sum();
+        let step = usize::max(total / MAX_TRAINING_PIXELS, 1);
+        let samples: Vec<u8> = self.frames.iter()
+            .flat_map(|(im, _)| im.as_raw().chunks_exact(4))
+            .step_by(step)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect();
+        let nq = NeuQuant::new(10, TRANSPARENT_INDEX as usize, &samples);
+
+        let mut palette = nq.color_map_rgb();
+        palette.extend_from_slice(&[0, 0, 0]);
+
+        //screen contents usually have few distinct colors, so the search in the network is done once per color
+        let mut cache: HashMap<[u8; 3], u8> = HashMap::new();
+        let mut frames: Vec<Vec<u8>> = Vec::new();
+        let mut previous: Option<Vec<u8>> = None;
+        for (im, _) in self.frames.iter() {
+            let indices: Vec<u8> = im.as_raw().chunks_exact(4)
+                .map(|p| *cache.entry([p[0], p[1], p[2]]).or_insert_with(|| nq.index_of(&[p[0], p[1], p[2], 255]) as u8))
+                .collect();
+            match previous.as_ref() {
+                Some(prev) => {
+                    frames.push(indices.iter().zip(prev.iter())
+                        .map(|(index, prev)| if index == prev { TRANSPARENT_INDEX } else { *index })
+                        .collect());
+                }
+                None => frames.push(indices.clone())
+            }
+            previous = Some(indices);
+        }
+        (palette, frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn frame(width: u32, height: u32, color: Rgba<u8>) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(width, height, color))
+    }
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+    #[test]
+    fn frames_are_shown_until_the_next_one() {
+        let mut recording = Recording::new();
+        recording.push_frame(frame(4, 4, RED), 1.0);
+        recording.push_frame(frame(4, 4, BLUE), 1.5);
+        recording.stop(2.25);
+        assert_eq!(recording.delays_ms(), vec![500, 750]);
+    }
+
+    #[test]
+    fn identical_frames_extend_the_previous_one() {
+        let mut recording = Recording::new();
+        recording.push_frame(frame(4, 4, RED), 0.0);
+        recording.push_frame(frame(4, 4, RED), 0.1);
+        recording.push_frame(frame(4, 4, RED), 0.2);
+        recording.push_frame(frame(4, 4, BLUE), 0.3);
+        recording.stop(0.3);
+        assert_eq!(recording.len(), 2);
+        //the last frame lasts at least 10 ms, even if the recording stops right after it
+        assert_eq!(recording.delays_ms(), vec![300, 10]);
+    }
+
+    #[test]
+    fn wide_frames_are_scaled_down() {
+        let mut recording = Recording::new();
+        recording.push_frame(frame(MAX_FRAME_WIDTH * 2, 100, RED), 0.0);
+        assert_eq!(recording.last_frame().unwrap().width(), MAX_FRAME_WIDTH);
+        assert_eq!(recording.last_frame().unwrap().height(), 50);
+    }
+
+    #[test]
+    fn unchanged_pixels_are_transparent() {
+        let mut recording = Recording::new();
+        //the bottom half of the second frame turns blue
+        let mut second = RgbaImage::from_pixel(32, 32, RED);
+        for (_, y, p) in second.enumerate_pixels_mut() {
+            if y >= 16 {
+                *p = BLUE;
+            }
+        }
+        recording.push_frame(frame(32, 32, RED), 0.0);
+        recording.push_frame(DynamicImage::ImageRgba8(second), 0.5);
+        let (palette, frames) = recording.quantize();
+
+        //255 colors plus the transparent one
+        assert_eq!(palette.len(), 256 * 3);
+        let color = |index: u8| &palette[index as usize * 3..index as usize * 3 + 3];
+        let red = frames[0][0];
+        assert!(frames[0].iter().all(|i| *i == red));
+        assert_ne!(red, TRANSPARENT_INDEX);
+        assert_eq!(color(red), &[255, 0, 0]);
+
+        let (top, bottom) = frames[1].split_at(32 * 16);
+        let blue = bottom[0];
+        assert!(bottom.iter().all(|i| *i == blue));
+        assert_eq!(color(blue), &[0, 0, 255]);
+        assert!(top.iter().all(|i| *i == TRANSPARENT_INDEX));
+    }
+
+    #[test]
+    fn nothing_to_save_without_frames() {
+        assert!(Recording::new().save("unused.gif", RecordingFormat::Gif).is_err());
+    }
+}
\ No newline at end of file
@@ -11,6 +11,15 @@ pub enum ScreenshotError {
     #[error("Image Processing Error")]
     ImageProcessError,
 
+    #[error("Invalid Fake Screen Specification {0}")]
+    FakeSpecError(String),
+
+    #[error("Window List Error")]
+    WindowListError,
+
+    #[error("Encoding Error {0}")]
+    EncodingError(String),
+
     #[error("IO Error {0}")]
     IOError(#[from] std::io::Error)
 
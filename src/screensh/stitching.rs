@@ -0,0 +1,173 @@
+use image::{DynamicImage, GrayImage, RgbaImage};
+use image::imageops::overlay;
+
+///Number of blocks each row is split into when computing its signature
+const SIGNATURE_BLOCKS: u32 = 64;
+///Overlaps shorter than this are not considered, since a few rows match too easily by chance
+const MIN_OVERLAP: usize = 8;
+///Highest mean difference (in gray levels) between the signatures of two overlapping areas
+const MAX_MEAN_DIFFERENCE: f32 = 6.0;
+
+///Returns the signature of every row of the image: the mean gray level of each of its blocks.
+///Comparing signatures instead of pixels keeps the search fast and tolerant to small rendering differences
+fn row_signatures(image: &GrayImage) -> Vec<Vec<f32>> {
+    let blocks = u32::max(u32::min(SIGNATURE_BLOCKS, image.width()), 1);
+    let mut signatures = Vec::new();
+    for y in 0..image.height() {
+        let mut signature = vec![0.0f32; blocks as usize];
+        let mut counts = vec![0u32; blocks as usize];
+        for x in 0..image.width() {
+            let block = (x * blocks / image.width()) as usize;
+            signature[block] += image.get_pixel(x, y).0[0] as f32;
+            counts[block] += 1;
+        }
+        for (s, c) in signature.iter_mut().zip(counts) {
+            *s /= u32::max(c, 1) as f32;
+        }
+        signatures.push(signature);
+    }
+    signatures
+}
+
+///Returns how many rows at the bottom of the top image are repeated at the top of the bottom one,
+///which happens when the bottom image was taken after scrolling the content of the top one.
+///Among the overlaps that match, the one with the smallest difference is chosen, the longest one on ties.
+///Returns None if the images have different widths or no overlap matches
+pub fn find_overlap(top: &DynamicImage, bottom: &DynamicImage) -> Option<u32> {
+    if top.width() != bottom.width() {
+        return None;
+    }
+    let a = row_signatures(&top.to_luma8());
+    let b = row_signatures(&bottom.to_luma8());
+    let blocks = a.first().map(|s| s.len()).unwrap_or(0);
+    let max_overlap = usize::min(a.len(), b.len());
+
+    let mut best: Option<(usize, f32)> = None;
+    for h in (MIN_OVERLAP..=max_overlap).rev() {
+        //the comparison stops as soon as the total difference exceeds the one allowed (or the best found so far)
+        let limit = match best {
+            Some((_, score)) => f32::min(score, MAX_MEAN_DIFFERENCE),
+            None => MAX_MEAN_DIFFERENCE
+        } * (h * blocks) as f32;
+        let mut total = 0.0;
+        for i in 0..h {
+            let (ra, rb) = (&a[a.len() - h + i], &b[i]);
+            total += ra.iter().zip(rb.iter()).map(|(x, y)| (x - y).abs()).sum::<f32>();
+            if total > limit {
+                break;
+            }
+        }
+        let score = total / (h * blocks) as f32;
+        if total <= limit && best.map(|(_, s)| score < s).unwrap_or(true) {
+            best = Some((h, score));
+        }
+    }
+    best.map(|(h, _)| h as u32)
+}
+
+///Stitches frames taken while scrolling down into a single tall image.
+///The overlap between each frame and the previous one is found by find_overlap and appears only once;
+///frames not overlapping the previous one are appended entirely.
+///Returns None if no frame is given or the frames have different widths
+pub fn stitch_frames(frames: &[DynamicImage]) -> Option<DynamicImage> {
+    let first = frames.first()?;
+    let width = first.width();
+    if frames.iter().any(|f| f.width() != width) {
+        return None;
+    }
+
+    let mut skips = vec![0u32];
+    for pair in frames.windows(2) {
+        skips.push(find_overlap(&pair[0], &pair[1]).unwrap_or(0));
+    }
+    let height: u32 = frames.iter().zip(skips.iter()).map(|(f, s)| f.height() - s).sum();
+
+    let mut canvas = RgbaImage::new(width, height);
+    let mut y = 0;
+    for (frame, skip) in frames.iter().zip(skips) {
+        let visible = frame.crop_imm(0, skip, width, frame.height() - skip);
+        overlay(&mut canvas, &visible.to_rgba8(), 0, y as i64);
+        y += frame.height() - skip;
+    }
+
+    Some(DynamicImage::ImageRgba8(canvas))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Luma};
+
+    ///A textured page, whose rows all differ from each other
+    fn page(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+            let mut v = x.wrapping_mul(374761393).wrapping_add(y.wrapping_mul(668265263));
+            v = (v ^ (v >> 13)).wrapping_mul(1274126177);
+            Luma([(v >> 24) as u8])
+        }))
+    }
+
+    ///The part of the page visible after scrolling it by offset rows
+    fn view(page: &DynamicImage, offset: u32, height: u32) -> DynamicImage {
+        page.crop_imm(0, offset, page.width(), height)
+    }
+
+    fn same_pixels(a: &DynamicImage, b: &DynamicImage) -> bool {
+        a.dimensions() == b.dimensions() && a.to_rgba8().as_raw() == b.to_rgba8().as_raw()
+    }
+
+    #[test]
+    fn overlap_of_a_scrolled_frame() {
+        let page = page(64, 200);
+        assert_eq!(find_overlap(&view(&page, 0, 40), &view(&page, 15, 40)), Some(25));
+        assert_eq!(find_overlap(&view(&page, 50, 40), &view(&page, 82, 40)), Some(MIN_OVERLAP as u32));
+    }
+
+    #[test]
+    fn scrolled_frames_are_stitched_into_the_page() {
+        let page = page(64, 200);
+        let frames = [view(&page, 0, 40), view(&page, 15, 40), view(&page, 30, 40)];
+        let stitched = stitch_frames(&frames).unwrap();
+        assert!(same_pixels(&stitched, &view(&page, 0, 70)));
+    }
+
+    #[test]
+    fn frames_without_overlap_are_appended_whole() {
+        let page = page(64, 200);
+        let (first, second) = (view(&page, 0, 40), view(&page, 120, 40));
+        assert_eq!(find_overlap(&first, &second), None);
+
+        let stitched = stitch_frames(&[first.clone(), second.clone()]).unwrap();
+        assert_eq!(stitched.height(), 80);
+        assert!(same_pixels(&stitched.crop_imm(0, 0, 64, 40), &first));
+        assert!(same_pixels(&stitched.crop_imm(0, 40, 64, 40), &second));
+    }
+
+    #[test]
+    fn identical_frames_add_nothing() {
+        let frame = view(&page(64, 200), 10, 40);
+        assert_eq!(find_overlap(&frame, &frame), Some(40));
+        let stitched = stitch_frames(&[frame.clone(), frame.clone(), frame.clone()]).unwrap();
+        assert!(same_pixels(&stitched, &frame));
+    }
+
+    #[test]
+    fn frames_of_different_widths_are_not_stitched() {
+        let (narrow, wide) = (page(32, 40), page(64, 40));
+        assert_eq!(find_overlap(&narrow, &wide), None);
+        assert!(stitch_frames(&[narrow, wide]).is_none());
+        assert!(stitch_frames(&[]).is_none());
+    }
+
+    #[test]
+    fn uniform_frames_overlap_entirely() {
+        //every overlap of uniform frames matches perfectly: the longest one wins,
+        //so a frame showing no content that could have scrolled adds nothing
+        let blank = DynamicImage::ImageLuma8(GrayImage::from_pixel(64, 40, Luma([200])));
+        let shorter = blank.crop_imm(0, 0, 64, 30);
+        assert_eq!(find_overlap(&blank, &blank), Some(40));
+        assert_eq!(find_overlap(&blank, &shorter), Some(30));
+        let stitched = stitch_frames(&[blank.clone(), blank.clone()]).unwrap();
+        assert!(same_pixels(&stitched, &blank));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,84 @@
+use image::{DynamicImage, RgbaImage};
+use image::imageops::{overlay, FilterType};
+use super::capture_source::ScreenGeometry;
+
+///Composes the captures of several screens into a single image, placing each one at its position
+///in the virtual desktop. Screens may be captured at different pixel densities (scale factors):
+///all of them are brought to the highest one, so that no detail is lost.
+///The areas of the virtual desktop not covered by any screen are left transparent.
+///Returns None if no capture is given
+pub fn compose_virtual_desktop(captures: &[(ScreenGeometry, DynamicImage)]) -> Option<DynamicImage> {
+    if captures.is_empty() {
+        return None;
+    }
+
+    let density = captures.iter()
+        .map(|(g, im)| im.width() as f32 / g.width.max(1) as f32)
+        .fold(f32::MIN, f32::max);
+
+    let min_x = captures.iter().map(|(g, _)| g.x).min().unwrap();
+    let min_y = captures.iter().map(|(g, _)| g.y).min().unwrap();
+    let max_x = captures.iter().map(|(g, _)| g.x + g.width as i32).max().unwrap();
+    let max_y = captures.iter().map(|(g, _)| g.y + g.height as i32).max().unwrap();
+
+    let width = ((max_x - min_x) as f32 * density).round() as u32;
+    let height = ((max_y - min_y) as f32 * density).round() as u32;
+    let mut canvas = RgbaImage::new(width, height);
+
+    for (geometry, image) in captures {
+        let w = (geometry.width as f32 * density).round() as u32;
+        let h = (geometry.height as f32 * density).round() as u32;
+        let x = ((geometry.x - min_x) as f32 * density).round() as i64;
+        let y = ((geometry.y - min_y) as f32 * density).round() as i64;
+        if image.width() == w && image.height() == h {
+            overlay(&mut canvas, &image.to_rgba8(), x, y);
+        } else {
+            let resized = image.resize_exact(w, h, FilterType::Lanczos3);
+            overlay(&mut canvas, &resized.to_rgba8(), x, y);
+        }
+    }
+
+    Some(DynamicImage::ImageRgba8(canvas))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn capture(x: i32, y: i32, width: u32, height: u32, density: u32, color: Rgba<u8>) -> (ScreenGeometry, DynamicImage) {
+        let image = RgbaImage::from_pixel(width * density, height * density, color);
+        (ScreenGeometry::new(x, y, width, height, density as f32), DynamicImage::ImageRgba8(image))
+    }
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const GREEN: Rgba<u8> = Rgba([0, 255, 0, 255]);
+    const CLEAR: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+    #[test]
+    fn no_capture_gives_no_desktop() {
+        assert!(compose_virtual_desktop(&[]).is_none());
+    }
+
+    #[test]
+    fn screens_at_negative_and_positive_offsets() {
+        //the left screen is above the origin, the right one below it
+        let desktop = compose_virtual_desktop(&[capture(-40, -10, 40, 30, 1, RED), capture(0, 5, 50, 20, 1, GREEN)]).unwrap().to_rgba8();
+        assert_eq!(desktop.dimensions(), (90, 35));
+        assert_eq!(*desktop.get_pixel(0, 0), RED);
+        assert_eq!(*desktop.get_pixel(39, 29), RED);
+        assert_eq!(*desktop.get_pixel(40, 15), GREEN);
+        assert_eq!(*desktop.get_pixel(89, 34), GREEN);
+        //the areas not covered by any screen stay transparent
+        assert_eq!(*desktop.get_pixel(40, 14), CLEAR);
+        assert_eq!(*desktop.get_pixel(39, 30), CLEAR);
+    }
+
+    #[test]
+    fn screens_are_brought_to_the_highest_density() {
+        let desktop = compose_virtual_desktop(&[capture(0, 0, 20, 10, 1, RED), capture(20, 0, 20, 10, 2, GREEN)]).unwrap().to_rgba8();
+        assert_eq!(desktop.dimensions(), (80, 20));
+        assert_eq!(*desktop.get_pixel(39, 19), RED);
+        assert_eq!(*desktop.get_pixel(40, 0), GREEN);
+    }
+}
\ No newline at end of file
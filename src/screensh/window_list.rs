@@ -0,0 +1,204 @@
+use super::screensh_errors::ScreenshotError;
+#[cfg(target_os = "linux")]
+use super::capture_source::{x11_connection, X11Connection};
+
+///Name of the environment variable used to replace the real windows with fake ones.
+///It holds a list of windows separated by ';', each one in the format TITLE@WIDTHxHEIGHT+X+Y
+pub const FAKE_WINDOWS_VAR: &str = "SCREEN_CAPTURE_FAKE_WINDOWS";
+
+///Top-level window, with its position and size expressed in the same units of ScreenGeometry
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowInfo {
+    pub id: u32,
+    pub title: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32
+}
+
+///Source of the list of top-level windows that can be captured
+pub trait WindowProvider {
+    ///Returns the windows currently shown, from the top-most to the bottom-most
+    fn windows(&self) -> Result<Vec<WindowInfo>, ScreenshotError>;
+}
+
+///Window provider returning always the same list, used when the real windows are not available
+pub struct StaticWindowProvider {
+    windows: Vec<WindowInfo>
+}
+
+impl StaticWindowProvider {
+    pub fn new(windows: Vec<WindowInfo>) -> Self {
+        Self {
+            windows
+        }
+    }
+    ///Parses the content of FAKE_WINDOWS_VAR, returning a provider serving the windows it describes
+    pub fn from_spec(specs: &str) -> Result<Self, ScreenshotError> {
+        let mut windows = Vec::new();
+        for (id, spec) in specs.split(';').filter(|s| !s.trim().is_empty()).enumerate() {
+            let error = || ScreenshotError::FakeSpecError(spec.to_string());
+            let (title, geometry) = spec.trim().rsplit_once('@').ok_or_else(error)?;
+            let mut parts = geometry.splitn(3, '+');
+            let (w, h) = parts.next().and_then(|s| s.split_once('x')).ok_or_else(error)?;
+            let x = parts.next().and_then(|s| s.parse::<i32>().ok()).ok_or_else(error)?;
+            let y = parts.next().and_then(|s| s.parse::<i32>().ok()).ok_or_else(error)?;
+            windows.push(WindowInfo {
+                id: id as u32 + 1,
+                title: title.to_string(),
+                x,
+                y,
+                width: w.parse().map_err(|_| error())?,
+                height: h.parse().map_err(|_| error())?
+            });
+        }
+        Ok(Self::new(windows))
+    }
+}
+
+impl WindowProvider for StaticWindowProvider {
+    fn windows(&self) -> Result<Vec<WindowInfo>, ScreenshotError> {
+        Ok(self.windows.clone())
+    }
+}
+
+///Window provider listing the client windows of the X11 window manager, through _NET_CLIENT_LIST_STACKING
+#[cfg(target_os = "linux")]
+pub struct X11WindowProvider {
+    x11: &'static X11Connection,
+    scale_factor: f32
+}
+
+#[cfg(target_os = "linux")]
+impl X11WindowProvider {
+    ///Uses the connection to the X server shared with the capture sources, failing if there is no X server.
+    ///Takes the scale factor of the screens, used to convert the X11 coordinates (in physical pixels)
+    ///to the ones of ScreenGeometry
+    pub fn new(scale_factor: f32) -> Result<Self, ScreenshotError> {
+        Ok(Self {
+            x11: x11_connection().ok_or(ScreenshotError::WindowListError)?,
+            scale_factor
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl WindowProvider for X11WindowProvider {
+    fn windows(&self) -> Result<Vec<WindowInfo>, ScreenshotError> {
+        use xcb::x;
+        use xcb::Xid;
+
+        let conn = &self.x11.conn;
+        let root = self.x11.root;
+
+        let intern = |name: &[u8]| -> Result<x::Atom, ScreenshotError> {
+            let cookie = conn.send_request(&x::InternAtom { only_if_exists: false, name });
+            Ok(conn.wait_for_reply(cookie).map_err(|_| ScreenshotError::WindowListError)?.atom())
+        };
+        let client_list = intern(b"_NET_CLIENT_LIST_STACKING")?;
+        let net_wm_name = intern(b"_NET_WM_NAME")?;
+        let utf8_string = intern(b"UTF8_STRING")?;
+
+        let cookie = conn.send_request(&x::GetProperty {
+            delete: false,
+            window: root,
+            property: client_list,
+            r#type: x::ATOM_WINDOW,
+            long_offset: 0,
+            long_length: 4096
+        });
+        let reply = conn.wait_for_reply(cookie).map_err(|_| ScreenshotError::WindowListError)?;
+        let clients: Vec<x::Window> = reply.value::<x::Window>().to_vec();
+
+        let mut windows = Vec::new();
+        //the stacking order goes from the bottom-most to the top-most window
+        for window in clients.into_iter().rev() {
+            let attributes = conn.send_request(&x::GetWindowAttributes { window });
+            let geometry = conn.send_request(&x::GetGeometry { drawable: x::Drawable::Window(window) });
+            let position = conn.send_request(&x::TranslateCoordinates { src_window: window, dst_window: root, src_x: 0, src_y: 0 });
+            let name = conn.send_request(&x::GetProperty {
+                delete: false,
+                window,
+                property: net_wm_name,
+                r#type: utf8_string,
+                long_offset: 0,
+                long_length: 256
+            });
+
+            let (attributes, geometry, position, name) = match (
+                conn.wait_for_reply(attributes),
+                conn.wait_for_reply(geometry),
+                conn.wait_for_reply(position),
+                conn.wait_for_reply(name)
+            ) {
+                (Ok(a), Ok(g), Ok(p), Ok(n)) => (a, g, p, n),
+                //the window may have been closed in the meantime
+                _ => continue
+            };
+            if attributes.map_state() != x::MapState::Viewable {
+                continue;
+            }
+
+            let title = String::from_utf8_lossy(name.value::<u8>()).to_string();
+            windows.push(WindowInfo {
+                id: window.resource_id(),
+                title,
+                x: (position.dst_x() as f32 / self.scale_factor) as i32,
+                y: (position.dst_y() as f32 / self.scale_factor) as i32,
+                width: (geometry.width() as f32 / self.scale_factor) as u32,
+                height: (geometry.height() as f32 / self.scale_factor) as u32
+            });
+        }
+
+        Ok(windows)
+    }
+}
+
+///Returns the window provider to use on this platform.
+///If the SCREEN_CAPTURE_FAKE_WINDOWS variable is set, the windows are the fake ones it describes,
+///failing if they are not valid. Without a window system the list of windows is empty
+pub fn get_window_provider(scale_factor: f32) -> Result<Box<dyn WindowProvider>, ScreenshotError> {
+    if let Ok(specs) = std::env::var(FAKE_WINDOWS_VAR) {
+        return Ok(Box::new(StaticWindowProvider::from_spec(&specs)?));
+    }
+    #[cfg(target_os = "linux")]
+    {
+        match X11WindowProvider::new(scale_factor) {
+            Ok(provider) => Ok(Box::new(provider)),
+            Err(_) => Ok(Box::new(StaticWindowProvider::new(Vec::new())))
+        }
+    }
+    //window listing is only available on X11 for now
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = scale_factor;
+        Ok(Box::new(StaticWindowProvider::new(Vec::new())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(id: u32, title: &str, x: i32, y: i32, width: u32, height: u32) -> WindowInfo {
+        WindowInfo { id, title: title.to_string(), x, y, width, height }
+    }
+
+    #[test]
+    fn spec_of_windows() {
+        let provider = StaticWindowProvider::from_spec("Editor@800x600+10+20; ;mail@home@300x200+-50+-5;").unwrap();
+        assert_eq!(provider.windows().unwrap(), vec![
+            window(1, "Editor", 10, 20, 800, 600),
+            window(2, "mail@home", -50, -5, 300, 200)
+        ]);
+        assert!(StaticWindowProvider::from_spec("").unwrap().windows().unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_window_specs_are_rejected() {
+        for spec in ["Editor", "Editor@800x600", "Editor@800x600+10", "Editor@800+10+20", "Editor@ax600+10+20", "Editor@800x600+a+20", "Ok@10x10+0+0;Bad@10x10"] {
+            assert!(StaticWindowProvider::from_spec(spec).is_err(), "{}", spec);
+        }
+    }
+}
\ No newline at end of file